@@ -0,0 +1,44 @@
+use std::{env, time::Duration};
+
+use mouse_configurator::{enumerate, Event, HpMouse, HpMouseEvents};
+
+// Long enough for a Bluetooth mouse waking up
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+fn print_firmware(mouse: &HpMouse, mut events: HpMouseEvents) {
+    match mouse.request_firmware(&mut events, TIMEOUT) {
+        Ok(Event::Firmware {
+            version,
+            device,
+            serial,
+        }) => println!("{}: firmware {}, serial {}", device, version, serial),
+        Ok(event) => println!("{:?}", event),
+        Err(err) => eprintln!("failed to query firmware: {}", err),
+    }
+}
+
+fn main() {
+    // Device node or serial number of one mouse
+    if let Some(path_or_serial) = env::args().nth(1) {
+        match HpMouse::open(&path_or_serial) {
+            Ok((mouse, events)) => print_firmware(&mouse, events),
+            Err(err) => eprintln!("failed to open HP mouse: {}", err),
+        }
+        return;
+    }
+
+    match enumerate() {
+        Ok(devices) => {
+            for device in devices {
+                match device.open() {
+                    Ok(mouse) => {
+                        let events = mouse.read();
+                        print_firmware(&mouse, events)
+                    }
+                    Err(err) => eprintln!("failed to open HP mouse: {}", err),
+                }
+            }
+        }
+        Err(err) => eprintln!("failed to list HID devices: {}", err),
+    }
+}
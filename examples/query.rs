@@ -1,16 +1,17 @@
-use std::collections::BTreeSet;
+use std::{collections::BTreeSet, env};
 
-use mouse_configurator::{enumerate, Event, HpMouse};
+use mouse_configurator::{enumerate, Event, HpMouse, HpMouseEvents};
 
-fn hp_mouse(mouse: HpMouse) {
+fn hp_mouse(mouse: HpMouse, mut events: HpMouseEvents) {
     println!("Found HP mouse");
+    events.log_panics(true);
 
     mouse.query_firmware().unwrap();
     mouse.query_battery().unwrap();
     mouse.query_button().unwrap();
     mouse.query_dpi().unwrap();
 
-    for event in mouse.read() {
+    for event in events {
         println!("{:?}", event);
         if let Ok(Event::Buttons { buttons, .. }) = event {
             let host_ids: BTreeSet<_> = buttons.iter().map(|b| b.host_id).collect();
@@ -34,12 +35,24 @@ fn hp_mouse(mouse: HpMouse) {
 }
 
 fn main() {
+    // Device node or serial number of one mouse
+    if let Some(path_or_serial) = env::args().nth(1) {
+        match HpMouse::open(&path_or_serial) {
+            Ok((mouse, events)) => hp_mouse(mouse, events),
+            Err(err) => eprintln!("failed to open HP mouse: {}", err),
+        }
+        return;
+    }
+
     match enumerate() {
         Ok(devices) => {
             for device in devices {
                 println!("{:?}", device);
                 match device.open() {
-                    Ok(mouse) => hp_mouse(mouse),
+                    Ok(mouse) => {
+                        let events = mouse.read();
+                        hp_mouse(mouse, events)
+                    }
                     Err(err) => eprintln!("failed to open HP mouse: {}", err),
                 }
             }
@@ -0,0 +1,61 @@
+//! Counts heap allocations while reading a burst of battery reports from a
+//! mock device, as a stand-in for a high auto-report rate.
+//!
+//! `cargo run --release --example read_allocs [REPORTS]`
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    env,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use mouse_configurator::{Event, HpMouse, MockHid};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn main() {
+    let count = env::args()
+        .nth(1)
+        .map_or(10_000, |x| x.parse().expect("Invalid report count"));
+
+    let reports = (0..count)
+        .map(|i| MockHid::report_1(6, &[10, 5, 0, 6, (i % 100) as u8]))
+        .collect();
+    let mut events = HpMouse::from_device(MockHid::with_reports(reports)).read();
+    // Skip `Event::Connected`, and allocations made by the first read
+    events.next();
+    events.next();
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let read = events
+        .filter(|event| matches!(event, Ok(Event::Battery { .. })))
+        .count();
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    eprintln!(
+        "{} reports in {:?}, {} allocations ({:.2} per report)",
+        read,
+        elapsed,
+        allocations,
+        allocations as f64 / read.max(1) as f64
+    );
+}
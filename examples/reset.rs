@@ -1,5 +1,11 @@
+use std::env;
+
 fn main() {
+    // Only log what would be written
+    let dry_run = env::args().nth(1).as_deref() == Some("--dry-run");
     for device in mouse_configurator::enumerate().unwrap() {
-        device.open().unwrap().reset().unwrap();
+        let mouse = device.open().unwrap();
+        mouse.set_dry_run(dry_run);
+        mouse.reset().unwrap();
     }
 }
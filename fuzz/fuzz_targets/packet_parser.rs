@@ -0,0 +1,17 @@
+//! Reads arbitrary bytes as HID reports, through packet reassembly and
+//! parsing, which must not panic. The input is split into reports of
+//! `REPORT_LEN` bytes, as read from the device.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mouse_configurator::{HpMouse, MockHid};
+
+const REPORT_LEN: usize = 21;
+
+fuzz_target!(|data: &[u8]| {
+    let reports = data.chunks(REPORT_LEN).map(<[u8]>::to_vec).collect();
+    let mouse = HpMouse::from_device(MockHid::with_reports(reports));
+    // Errors are fine; keep reading until the reports run out
+    for _ in mouse.read() {}
+});
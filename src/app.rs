@@ -0,0 +1,9 @@
+//! Button bindings and saved configuration of the `mouse-configurator` app,
+//! shared with `hp-mouse-daemon`, which applies the configuration without
+//! the app running.
+
+#[macro_use]
+pub mod i18n;
+pub mod bindings;
+pub mod config;
+pub mod keycode;
@@ -0,0 +1,1170 @@
+use once_cell::sync::Lazy;
+use serde::de::{self, Error};
+use std::{collections::HashMap, slice};
+
+use super::{i18n::gettext, keycode::*};
+use crate::{Button, MouseButton, Op, Value::*};
+
+// TODO better naming? Important if serialized in json.
+#[repr(u8)]
+#[derive(Clone, Copy, Hash, Eq, PartialEq, Debug)]
+pub enum HardwareButton {
+    Right = 0,
+    Middle = 1,
+    LeftBottom = 2,
+    LeftTop = 3,
+    ScrollLeft = 4,
+    ScrollRight = 5,
+    LeftCenter = 6,
+}
+
+// Serialize as int to be future-proof for more devices, and not depend on naming
+impl serde::Serialize for HardwareButton {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (*self as u8).serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for HardwareButton {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let num = u8::deserialize(deserializer)?;
+        Self::from_u8(num).ok_or_else(|| {
+            D::Error::invalid_value(de::Unexpected::Unsigned(num.into()), &"button index 0-6")
+        })
+    }
+}
+
+impl HardwareButton {
+    pub fn iter() -> impl Iterator<Item = Self> {
+        (0..7).map(|i| Self::from_u8(i).unwrap())
+    }
+
+    pub fn from_u8(num: u8) -> Option<Self> {
+        match num {
+            0 => Some(Self::Right),
+            1 => Some(Self::Middle),
+            2 => Some(Self::LeftBottom),
+            3 => Some(Self::LeftTop),
+            4 => Some(Self::ScrollLeft),
+            5 => Some(Self::ScrollRight),
+            6 => Some(Self::LeftCenter),
+            _ => None,
+        }
+    }
+
+    /// Slot of a button reported by or written to the mouse. The same slot
+    /// exists on every host, so `host_id` isn't considered; callers that care
+    /// which host a button belongs to need to check it.
+    pub fn from_button(button: &Button) -> Option<Self> {
+        Self::from_u8(button.id)
+    }
+
+    pub fn label(self) -> String {
+        gettext(match self {
+            Self::Right => N_!("Right Button"),
+            Self::Middle => N_!("Middle Button"),
+            Self::LeftBottom => N_!("Lower Side Button"),
+            Self::LeftTop => N_!("Upper Side Button"),
+            Self::ScrollLeft => N_!("Scroll Left"),
+            Self::ScrollRight => N_!("Scroll Right"),
+            Self::LeftCenter => N_!("Center Side Button"),
+        })
+    }
+
+    pub fn def_binding(self) -> &'static Entry {
+        match self {
+            Self::Right => PresetBinding::RightClick,
+            Self::Middle => PresetBinding::MiddleClick,
+            Self::LeftBottom => PresetBinding::Back,
+            Self::LeftTop => PresetBinding::Forward,
+            Self::ScrollLeft => PresetBinding::ScrollLeft,
+            Self::ScrollRight => PresetBinding::ScrollRight,
+            Self::LeftCenter => PresetBinding::SwitchApplications,
+        }
+        .entry()
+    }
+
+    /// Whether programming `def_binding` behaves the same as the factory
+    /// action. The tilt wheel's factory action hasn't been captured from a
+    /// device, and may differ from a single pan (e.g. by auto-repeating), so
+    /// selecting the preset for it programs the preset instead of resetting.
+    pub fn def_binding_is_factory(self) -> bool {
+        !matches!(self, Self::ScrollLeft | Self::ScrollRight)
+    }
+}
+
+pub struct Category {
+    // Untranslated; shown with `label()`
+    pub label: &'static str,
+    pub entries: Vec<Entry>,
+}
+
+impl Category {
+    pub fn label(&self) -> String {
+        gettext(self.label)
+    }
+}
+
+#[derive(Debug)]
+pub struct Entry {
+    pub id: PresetBinding,
+    // Untranslated; shown with `label()`
+    pub label: &'static str,
+    pub binding: Vec<Op>,
+    pub keybind: Option<&'static str>,
+}
+
+#[derive(Clone, Copy, Eq, Hash, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PresetBinding {
+    RightClick,
+    LeftClick,
+    MiddleClick,
+    ScrollLeft,
+    ScrollRight,
+    ScrollUp,
+    ScrollDown,
+    ScrollUpThreeLines,
+    ScrollDownThreeLines,
+    Back,
+    Forward,
+    SwitchApplications,
+    SwitchApplicationsAlt,
+    SwitchWindows,
+    Disabled,
+    VolumeDown,
+    VolumeUp,
+    NextTrack,
+    PreviousTrack,
+    PlayPause,
+    Mute,
+    VolumeDownKey,
+    VolumeUpKey,
+    MuteKey,
+    Copy,
+    Cut,
+    Paste,
+    Undo,
+    SelectAll,
+    Redo,
+    Find,
+    Refresh,
+    OpenTab,
+    CloseTab,
+    NewWindow,
+    Home,
+    SaveBookmark,
+    ZoomIn,
+    ZoomOut,
+    MoveToWorkspaceBelow,
+    MoveToWorkspaceAbove,
+    WorkspacesOverview,
+    ToggleFullscreen,
+    LogOut,
+    LockScreen,
+    Super,
+    PrintScreen,
+    ShowApplications,
+    ShowLauncher,
+    BrightnessUp,
+    BrightnessDown,
+    ScreenshotScreen,
+    ScreenshotWindow,
+    ClipboardHistory,
+    LockScreenSuperL,
+    LaunchCalculator,
+    LaunchMail,
+    LaunchBrowser,
+    LaunchFiles,
+}
+
+impl PresetBinding {
+    const ALL: &'static [Self] = &[
+        Self::RightClick,
+        Self::LeftClick,
+        Self::MiddleClick,
+        Self::ScrollLeft,
+        Self::ScrollRight,
+        Self::ScrollUp,
+        Self::ScrollDown,
+        Self::ScrollUpThreeLines,
+        Self::ScrollDownThreeLines,
+        Self::Back,
+        Self::Forward,
+        Self::SwitchApplications,
+        Self::SwitchApplicationsAlt,
+        Self::SwitchWindows,
+        Self::Disabled,
+        Self::VolumeDown,
+        Self::VolumeUp,
+        Self::NextTrack,
+        Self::PreviousTrack,
+        Self::PlayPause,
+        Self::Mute,
+        Self::VolumeDownKey,
+        Self::VolumeUpKey,
+        Self::MuteKey,
+        Self::Copy,
+        Self::Cut,
+        Self::Paste,
+        Self::Undo,
+        Self::SelectAll,
+        Self::Redo,
+        Self::Find,
+        Self::Refresh,
+        Self::OpenTab,
+        Self::CloseTab,
+        Self::NewWindow,
+        Self::Home,
+        Self::SaveBookmark,
+        Self::ZoomIn,
+        Self::ZoomOut,
+        Self::MoveToWorkspaceBelow,
+        Self::MoveToWorkspaceAbove,
+        Self::WorkspacesOverview,
+        Self::ToggleFullscreen,
+        Self::LogOut,
+        Self::LockScreen,
+        Self::Super,
+        Self::PrintScreen,
+        Self::ShowApplications,
+        Self::ShowLauncher,
+        Self::BrightnessUp,
+        Self::BrightnessDown,
+        Self::ScreenshotScreen,
+        Self::ScreenshotWindow,
+        Self::ClipboardHistory,
+        Self::LockScreenSuperL,
+        Self::LaunchCalculator,
+        Self::LaunchMail,
+        Self::LaunchBrowser,
+        Self::LaunchFiles,
+    ];
+
+    /// Every preset, in declaration order
+    pub fn iter() -> impl Iterator<Item = Self> {
+        Self::ALL.iter().copied()
+    }
+
+    pub fn entry(self) -> &'static Entry {
+        static ENTRY_FOR_PRESET: Lazy<HashMap<PresetBinding, &Entry>> = Lazy::new(|| {
+            let mut map = HashMap::new();
+            for category in &*BINDINGS {
+                for entry in &category.entries {
+                    map.insert(entry.id, entry);
+                }
+            }
+            for preset in PresetBinding::iter() {
+                assert!(map.contains_key(&preset), "No entry for {:?}", preset);
+            }
+            map
+        });
+        ENTRY_FOR_PRESET.get(&self).unwrap()
+    }
+
+    /// The preset for the same action sent as `media_keys`, for the media
+    /// controls that exist in both forms. Others are returned unchanged.
+    pub fn with_media_keys(self, media_keys: MediaKeys) -> Self {
+        for (consumer, keyboard) in MEDIA_KEY_PRESETS {
+            if self == consumer || self == keyboard {
+                return match media_keys {
+                    MediaKeys::Consumer => consumer,
+                    MediaKeys::Keyboard => keyboard,
+                };
+            }
+        }
+        self
+    }
+}
+
+/// How the media control presets that have keyboard page equivalents are
+/// sent. Some compositors only handle one of the two.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MediaKeys {
+    /// Consumer control codes, with `Op::media`
+    #[default]
+    Consumer,
+    /// Keyboard page volume keys, with `Op::key`
+    Keyboard,
+}
+
+// Consumer and keyboard forms of the same media control. The keyboard page
+// has no track keys, so those are always sent as consumer codes.
+const MEDIA_KEY_PRESETS: [(PresetBinding, PresetBinding); 3] = [
+    (PresetBinding::VolumeDown, PresetBinding::VolumeDownKey),
+    (PresetBinding::VolumeUp, PresetBinding::VolumeUpKey),
+    (PresetBinding::Mute, PresetBinding::MuteKey),
+];
+
+// Vertical wheel movement of `lines` notches; positive scrolls up
+pub fn scroll(lines: i16) -> Vec<Op> {
+    vec![Op::scroll(0, lines)]
+}
+
+// Wait between steps of a sequence
+pub fn delay(ms: i16) -> Op {
+    Op::pause(ms)
+}
+
+// Sequence sent `count` times in one action
+pub fn repeat(ops: &[Op], count: u8) -> Vec<Op> {
+    (0..count).flat_map(|_| ops.iter().cloned()).collect()
+}
+
+pub static BINDINGS: Lazy<Vec<Category>> = Lazy::new(|| {
+    use PresetBinding::*;
+    vec![
+        Category {
+            label: N_!("Mouse Controls"),
+            entries: vec![
+                Entry {
+                    id: RightClick,
+                    label: N_!("Right Click"),
+                    binding: vec![Op::mouse_click(MouseButton::Right)],
+                    keybind: None,
+                },
+                Entry {
+                    id: LeftClick,
+                    label: N_!("Left Click"),
+                    binding: vec![Op::mouse_click(MouseButton::Left)],
+                    keybind: None,
+                },
+                Entry {
+                    id: MiddleClick,
+                    label: N_!("Middle Click"),
+                    binding: vec![Op::mouse_click(MouseButton::Middle)],
+                    keybind: None,
+                },
+                Entry {
+                    id: ScrollLeft,
+                    label: N_!("Scroll Left"),
+                    binding: vec![Op::scroll(-1, 0)],
+                    keybind: None,
+                },
+                Entry {
+                    id: ScrollRight,
+                    label: N_!("Scroll Right"),
+                    binding: vec![Op::scroll(1, 0)],
+                    keybind: None,
+                },
+                Entry {
+                    id: ScrollUp,
+                    label: N_!("Scroll Up"),
+                    binding: scroll(1),
+                    keybind: None,
+                },
+                Entry {
+                    id: ScrollDown,
+                    label: N_!("Scroll Down"),
+                    binding: scroll(-1),
+                    keybind: None,
+                },
+                Entry {
+                    id: ScrollUpThreeLines,
+                    label: N_!("Scroll Up 3 Lines"),
+                    binding: scroll(3),
+                    keybind: None,
+                },
+                Entry {
+                    id: ScrollDownThreeLines,
+                    label: N_!("Scroll Down 3 Lines"),
+                    binding: scroll(-3),
+                    keybind: None,
+                },
+                Entry {
+                    id: Back,
+                    label: N_!("Back"),
+                    binding: vec![Op::mouse_click(MouseButton::Back)],
+                    keybind: None,
+                },
+                Entry {
+                    id: Forward,
+                    label: N_!("Forward"),
+                    binding: vec![Op::mouse_click(MouseButton::Forward)],
+                    keybind: None,
+                },
+                Entry {
+                    id: Disabled,
+                    label: N_!("Disabled"),
+                    binding: vec![Op::Kill],
+                    keybind: None,
+                },
+            ],
+        },
+        Category {
+            label: N_!("Media Controls"),
+            entries: vec![
+                Entry {
+                    id: VolumeDown,
+                    label: N_!("Volume Down"),
+                    binding: vec![Op::media(true, vec![Const(MEDIA_VolumeDown)])],
+                    keybind: None,
+                },
+                Entry {
+                    id: VolumeUp,
+                    label: N_!("Volume Up"),
+                    binding: vec![Op::media(true, vec![Const(MEDIA_VolumeUp)])],
+                    keybind: None,
+                },
+                Entry {
+                    id: NextTrack,
+                    label: N_!("Next Track"),
+                    binding: vec![Op::media(true, vec![Const(MEDIA_NextSong)])],
+                    keybind: None,
+                },
+                Entry {
+                    id: PreviousTrack,
+                    label: N_!("Previous Track"),
+                    binding: vec![Op::media(true, vec![Const(MEDIA_PreviousSong)])],
+                    keybind: None,
+                },
+                Entry {
+                    id: PlayPause,
+                    label: N_!("Play / Pause"),
+                    binding: vec![Op::media(true, vec![Const(MEDIA_PlayPause)])],
+                    keybind: None,
+                },
+                Entry {
+                    id: Mute,
+                    label: N_!("Mute"),
+                    binding: vec![Op::media(true, vec![Const(MEDIA_Mute)])],
+                    keybind: None,
+                },
+                // Keyboard usages, for compositors that ignore consumer control codes
+                Entry {
+                    id: VolumeDownKey,
+                    label: N_!("Volume Down (Keyboard)"),
+                    binding: vec![Op::key(true, vec![Const(0), Const(KEY_VolumeDown)])],
+                    keybind: None,
+                },
+                Entry {
+                    id: VolumeUpKey,
+                    label: N_!("Volume Up (Keyboard)"),
+                    binding: vec![Op::key(true, vec![Const(0), Const(KEY_VolumeUp)])],
+                    keybind: None,
+                },
+                Entry {
+                    id: MuteKey,
+                    label: N_!("Mute (Keyboard)"),
+                    binding: vec![Op::key(true, vec![Const(0), Const(KEY_Mute)])],
+                    keybind: None,
+                },
+            ],
+        },
+        Category {
+            label: N_!("Edit Features"),
+            entries: vec![
+                Entry {
+                    id: Copy,
+                    label: N_!("Copy"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_C)])],
+                    keybind: Some("Ctrl+C"),
+                },
+                Entry {
+                    id: Cut,
+                    label: N_!("Cut"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_X)])],
+                    keybind: Some("Ctrl+X"),
+                },
+                Entry {
+                    id: Paste,
+                    label: N_!("Paste"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_V)])],
+                    keybind: Some("Ctrl+V"),
+                },
+                Entry {
+                    id: Undo,
+                    label: N_!("Undo"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_Z)])],
+                    keybind: Some("Ctrl+Z"),
+                },
+                Entry {
+                    id: Redo,
+                    label: N_!("Redo"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_Y)])],
+                    keybind: Some("Ctrl+Y"),
+                },
+                Entry {
+                    id: SelectAll,
+                    label: N_!("Select All"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_A)])],
+                    keybind: Some("Ctrl+A"),
+                },
+                Entry {
+                    id: Find,
+                    label: N_!("Find"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_F)])],
+                    keybind: Some("Ctrl+F"),
+                },
+            ],
+        },
+        Category {
+            label: N_!("App/Browser Controls"),
+            entries: vec![
+                Entry {
+                    id: Refresh,
+                    label: N_!("Refresh"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_R)])],
+                    keybind: Some("Ctrl+R"),
+                },
+                Entry {
+                    id: OpenTab,
+                    label: N_!("Open New Tab/Page"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_T)])],
+                    keybind: Some("Ctrl+T"),
+                },
+                Entry {
+                    id: CloseTab,
+                    label: N_!("Close Current Tab/Page"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_W)])],
+                    keybind: Some("Ctrl+W"),
+                },
+                Entry {
+                    id: NewWindow,
+                    label: N_!("New Window"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_N)])],
+                    keybind: Some("Ctrl+N"),
+                },
+                Entry {
+                    id: Home,
+                    label: N_!("Home"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Alt), Const(KEY_Home)])],
+                    keybind: Some("Alt+Home"),
+                },
+                Entry {
+                    id: SaveBookmark,
+                    label: N_!("Save Page as Bookmark"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_D)])],
+                    keybind: Some("Ctrl+D"),
+                },
+                Entry {
+                    id: ZoomIn,
+                    label: N_!("Zoom In"),
+                    binding: vec![Op::key(
+                        true,
+                        vec![Const(MOD_Ctrl | MOD_Shift), Const(KEY_Equal)],
+                    )],
+                    keybind: Some("Ctrl +"),
+                },
+                Entry {
+                    id: ZoomOut,
+                    label: N_!("Zoom Out"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_Minus)])],
+                    keybind: Some("Ctrl -"),
+                },
+            ],
+        },
+        Category {
+            label: N_!("System and Window Controls"),
+            entries: vec![
+                Entry {
+                    id: MoveToWorkspaceBelow,
+                    label: N_!("Move To Workspace Left"),
+                    binding: vec![Op::key(
+                        true,
+                        vec![Const(MOD_Ctrl | MOD_Alt), Const(KEY_Left)],
+                    )],
+                    keybind: Some("Ctrl+Alt+Left"),
+                },
+                Entry {
+                    id: MoveToWorkspaceAbove,
+                    label: N_!("Move To Workspace Right"),
+                    binding: vec![Op::key(
+                        true,
+                        vec![Const(MOD_Ctrl | MOD_Alt), Const(KEY_Right)],
+                    )],
+                    keybind: Some("Ctrl+Alt+Right"),
+                },
+                Entry {
+                    id: WorkspacesOverview,
+                    label: N_!("Workspaces Overview"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Super)])],
+                    keybind: Some("Ctrl+D"),
+                },
+                Entry {
+                    id: ToggleFullscreen,
+                    label: N_!("Toggle Fullscreen"),
+                    binding: vec![Op::key(true, vec![Const(0), Const(KEY_F11)])],
+                    keybind: Some("F11"),
+                },
+                Entry {
+                    id: LogOut,
+                    label: N_!("Log Out"),
+                    binding: vec![Op::key(
+                        true,
+                        vec![Const(MOD_Ctrl | MOD_Alt), Const(KEY_Delete)],
+                    )],
+                    keybind: Some("Ctrl+Alt+Delete"),
+                },
+                Entry {
+                    id: LockScreen,
+                    label: N_!("Lock Screen"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Super), Const(KEY_Esc)])],
+                    keybind: Some("Super+Escape"),
+                },
+                Entry {
+                    id: Super,
+                    label: N_!("Super"),
+                    binding: vec![Op::key(true, vec![Const(0), Const(KEY_LeftSuper)])],
+                    keybind: None,
+                },
+                Entry {
+                    id: PrintScreen,
+                    label: N_!("Print Screen"),
+                    binding: vec![Op::key(true, vec![Const(0), Const(KEY_SysRq)])],
+                    keybind: Some("Print"),
+                },
+                Entry {
+                    id: ShowApplications,
+                    label: N_!("Show All Applications"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Super), Const(KEY_A)])],
+                    keybind: Some("Super+A"),
+                },
+                Entry {
+                    id: SwitchApplications,
+                    label: N_!("Switch Applications"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Super), Const(KEY_Tab)])],
+                    keybind: Some("Super+Tab"),
+                },
+                Entry {
+                    id: SwitchApplicationsAlt,
+                    label: N_!("Switch Applications (Alt)"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Alt), Const(KEY_Tab)])],
+                    keybind: Some("Alt+Tab"),
+                },
+                Entry {
+                    id: SwitchWindows,
+                    label: N_!("Switch Windows of an Application"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Super), Const(KEY_Grave)])],
+                    keybind: Some("Super+`"),
+                },
+                Entry {
+                    id: ShowLauncher,
+                    label: N_!("Open Launcher"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Super), Const(KEY_Slash)])],
+                    keybind: Some("Super+/"),
+                },
+                Entry {
+                    id: BrightnessUp,
+                    label: N_!("Increase Display Brightness"),
+                    binding: vec![Op::media(true, vec![Const(MEDIA_BrightnessUp)])],
+                    keybind: None,
+                },
+                Entry {
+                    id: BrightnessDown,
+                    label: N_!("Decrease Display Brightness"),
+                    binding: vec![Op::media(true, vec![Const(MEDIA_BrightnessDown)])],
+                    keybind: None,
+                },
+            ],
+        },
+        // GNOME's default shortcuts
+        Category {
+            label: N_!("Desktop"),
+            entries: vec![
+                Entry {
+                    id: ScreenshotScreen,
+                    label: N_!("Take Screenshot of Screen"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Shift), Const(KEY_SysRq)])],
+                    keybind: Some("Shift+Print"),
+                },
+                Entry {
+                    id: ScreenshotWindow,
+                    label: N_!("Take Screenshot of Window"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Alt), Const(KEY_SysRq)])],
+                    keybind: Some("Alt+Print"),
+                },
+                Entry {
+                    id: ClipboardHistory,
+                    label: N_!("Clipboard History"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Super), Const(KEY_V)])],
+                    keybind: Some("Super+V"),
+                },
+                Entry {
+                    id: LockScreenSuperL,
+                    label: N_!("Lock Screen (Super+L)"),
+                    binding: vec![Op::key(true, vec![Const(MOD_Super), Const(KEY_L)])],
+                    keybind: Some("Super+L"),
+                },
+            ],
+        },
+        // Application launch keys, named by their XF86 keysyms. Desktops
+        // usually open the default app for each.
+        Category {
+            label: N_!("Launch"),
+            entries: vec![
+                Entry {
+                    id: LaunchCalculator,
+                    label: N_!("Calculator"),
+                    binding: vec![Op::media_usage(true, MEDIA_Calculator)],
+                    keybind: Some("XF86Calculator"),
+                },
+                Entry {
+                    id: LaunchMail,
+                    label: N_!("Mail"),
+                    binding: vec![Op::media_usage(true, MEDIA_Mail)],
+                    keybind: Some("XF86Mail"),
+                },
+                Entry {
+                    id: LaunchBrowser,
+                    label: N_!("Web Browser"),
+                    binding: vec![Op::media_usage(true, MEDIA_Browser)],
+                    keybind: Some("XF86WWW"),
+                },
+                Entry {
+                    id: LaunchFiles,
+                    label: N_!("Files"),
+                    binding: vec![Op::media_usage(true, MEDIA_Files)],
+                    keybind: Some("XF86Explorer"),
+                },
+            ],
+        },
+    ]
+});
+
+impl Entry {
+    pub fn label(&self) -> String {
+        gettext(self.label)
+    }
+
+    pub fn for_binding(binding: &[Op]) -> Option<&'static Entry> {
+        static ENTRY_FOR_BINDING: Lazy<HashMap<&[Op], &Entry>> = Lazy::new(|| {
+            let mut map = HashMap::new();
+            for category in &*BINDINGS {
+                for entry in &category.entries {
+                    map.insert(entry.binding.as_slice(), entry);
+                }
+            }
+            map
+        });
+        ENTRY_FOR_BINDING.get(binding).copied()
+    }
+}
+
+/// JSON form of a binding, naming presets instead of spelling out their
+/// actions: `{"preset": "copy"}` or `{"custom": [<ops>]}`
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BindingRepr {
+    Preset(PresetBinding),
+    Custom(Vec<Op>),
+}
+
+impl BindingRepr {
+    /// Preset matching `ops`, if there is one. `ops` may be read back from
+    /// the device, so presets also match as `device_ops` sends them.
+    pub fn from_ops(ops: &[Op]) -> Self {
+        let entry = Entry::for_binding(ops)
+            .or_else(|| Entry::for_binding(&map_key_mods(ops, logical_super)));
+        match entry {
+            Some(entry) => Self::Preset(entry.id),
+            None => Self::Custom(ops.to_vec()),
+        }
+    }
+}
+
+impl From<&Entry> for BindingRepr {
+    fn from(entry: &Entry) -> Self {
+        Self::Preset(entry.id)
+    }
+}
+
+impl From<BindingRepr> for Vec<Op> {
+    fn from(repr: BindingRepr) -> Self {
+        match repr {
+            BindingRepr::Preset(preset) => preset.entry().binding.clone(),
+            BindingRepr::Custom(ops) => ops,
+        }
+    }
+}
+
+// `ops` with the modifiers of key operations mapped with `f`
+fn map_key_mods(ops: &[Op], f: fn(i8) -> i8) -> Vec<Op> {
+    ops.iter()
+        .map(|op| match op {
+            Op::Key {
+                auto_release,
+                payload,
+            } => match payload.split_first() {
+                Some((Const(mods), keys)) => {
+                    let mut payload = vec![Const(f(*mods))];
+                    payload.extend_from_slice(keys);
+                    Op::key(*auto_release, payload)
+                }
+                _ => op.clone(),
+            },
+            _ => op.clone(),
+        })
+        .collect()
+}
+
+/// Operations to program on the device for `ops`, with "Super" sent as
+/// `resolve_super` maps it
+pub fn device_ops(ops: &[Op]) -> Vec<Op> {
+    map_key_mods(ops, resolve_super)
+}
+
+// Label for one step of a custom sequence
+pub fn describe_op(op: &Op) -> String {
+    if let Op::Pause(Const(ms)) = op {
+        format!("{} {} ms", gettext("Delay"), ms)
+    } else if let Some(entry) = Entry::for_binding(slice::from_ref(op)) {
+        entry.label()
+    } else if let Op::Key { payload, .. } = op {
+        // Key combination, if every key has a name
+        let keys = match payload.as_slice() {
+            [Const(mods), Const(key)] => {
+                key_name(*key).map(|_| shortcut_accelerator(*mods, Some(*key)))
+            }
+            _ => None,
+        };
+        keys.unwrap_or_else(|| format!("{:?}", op))
+    } else {
+        format!("{:?}", op)
+    }
+}
+
+// Modifiers offered by the keyboard shortcut picker, in accelerator order
+pub const SHORTCUT_MODIFIERS: [(&str, i8); 4] = [
+    ("Ctrl", MOD_Ctrl),
+    ("Alt", MOD_Alt),
+    ("Shift", MOD_Shift),
+    ("Super", MOD_Super),
+];
+
+// Non-modifier keys offered by the keyboard shortcut picker
+pub const SHORTCUT_KEYS: &[(&str, i8)] = &[
+    ("A", KEY_A),
+    ("B", KEY_B),
+    ("C", KEY_C),
+    ("D", KEY_D),
+    ("E", KEY_E),
+    ("F", KEY_F),
+    ("G", KEY_G),
+    ("H", KEY_H),
+    ("I", KEY_I),
+    ("J", KEY_J),
+    ("K", KEY_K),
+    ("L", KEY_L),
+    ("M", KEY_M),
+    ("N", KEY_N),
+    ("O", KEY_O),
+    ("P", KEY_P),
+    ("Q", KEY_Q),
+    ("R", KEY_R),
+    ("S", KEY_S),
+    ("T", KEY_T),
+    ("U", KEY_U),
+    ("V", KEY_V),
+    ("W", KEY_W),
+    ("X", KEY_X),
+    ("Y", KEY_Y),
+    ("Z", KEY_Z),
+    ("1", KEY_1),
+    ("2", KEY_2),
+    ("3", KEY_3),
+    ("4", KEY_4),
+    ("5", KEY_5),
+    ("6", KEY_6),
+    ("7", KEY_7),
+    ("8", KEY_8),
+    ("9", KEY_9),
+    ("0", KEY_0),
+    ("Enter", KEY_Enter),
+    ("Escape", KEY_Esc),
+    ("Backspace", KEY_Backspace),
+    ("Tab", KEY_Tab),
+    ("Space", KEY_Space),
+    ("-", KEY_Minus),
+    ("=", KEY_Equal),
+    ("[", KEY_LeftBrace),
+    ("]", KEY_RightBrace),
+    ("`", KEY_Grave),
+    ("/", KEY_Slash),
+    ("F1", KEY_F1),
+    ("F2", KEY_F2),
+    ("F3", KEY_F3),
+    ("F4", KEY_F4),
+    ("F5", KEY_F5),
+    ("F6", KEY_F6),
+    ("F7", KEY_F7),
+    ("F8", KEY_F8),
+    ("F9", KEY_F9),
+    ("F10", KEY_F10),
+    ("F11", KEY_F11),
+    ("F12", KEY_F12),
+    ("Print", KEY_SysRq),
+    ("Home", KEY_Home),
+    ("Page Up", KEY_PageUp),
+    ("Delete", KEY_Delete),
+    ("End", KEY_End),
+    ("Page Down", KEY_PageDown),
+    ("Right", KEY_Right),
+    ("Left", KEY_Left),
+    ("Down", KEY_Down),
+    ("Up", KEY_Up),
+];
+
+pub fn shortcut_binding(mods: i8, key: i8) -> Vec<Op> {
+    vec![Op::key(true, vec![Const(mods), Const(key)])]
+}
+
+// Down and up actions holding `key` while the button is, so the desktop
+// auto-repeats it. The up action sends a report with no keys.
+pub fn held_key_binding(key: i8) -> (Vec<Op>, Vec<Op>) {
+    (
+        vec![Op::key(false, vec![Const(0), Const(key)])],
+        vec![Op::key(true, vec![Const(0)])],
+    )
+}
+
+// Accelerator string like "Ctrl+Shift+A", in the style of `Entry::keybind`
+pub fn shortcut_accelerator(mods: i8, key: Option<i8>) -> String {
+    let key_name = key.and_then(key_name);
+    SHORTCUT_MODIFIERS
+        .iter()
+        .filter(|(_, mod_)| mods & mod_ != 0)
+        .map(|(name, _)| *name)
+        .chain(key_name)
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        button::{decode_action, encode_action},
+        PressType,
+    };
+
+    use super::*;
+
+    #[test]
+    fn preset_iter() {
+        let entries: Vec<_> = BINDINGS.iter().flat_map(|x| &x.entries).collect();
+        assert_eq!(PresetBinding::iter().count(), entries.len());
+        for preset in PresetBinding::iter() {
+            assert_eq!(preset.entry().id, preset);
+        }
+        for entry in entries {
+            assert!(PresetBinding::iter().any(|x| x == entry.id));
+        }
+    }
+
+    #[test]
+    fn from_button() {
+        for button in HardwareButton::iter() {
+            for host_id in 0..3 {
+                let reported = Button::new(button as u8, host_id, PressType::Normal, &[]);
+                assert_eq!(HardwareButton::from_button(&reported), Some(button));
+            }
+        }
+        let reported = Button::new(7, 0, PressType::Normal, &[]);
+        assert_eq!(HardwareButton::from_button(&reported), None);
+    }
+
+    #[test]
+    fn binding_repr_json() {
+        let copy = BindingRepr::from(PresetBinding::Copy.entry());
+        assert_eq!(
+            serde_json::to_string(&copy).unwrap(),
+            r#"{"preset":"copy"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<BindingRepr>(r#"{"preset":"copy"}"#).unwrap(),
+            copy
+        );
+        assert_eq!(Vec::from(copy), PresetBinding::Copy.entry().binding);
+
+        let ops = shortcut_binding(MOD_Ctrl | MOD_Shift, KEY_E);
+        let custom = BindingRepr::from_ops(&ops);
+        assert_eq!(custom, BindingRepr::Custom(ops.clone()));
+        let json = serde_json::to_string(&custom).unwrap();
+        assert!(json.starts_with(r#"{"custom":["#));
+        assert_eq!(serde_json::from_str::<BindingRepr>(&json).unwrap(), custom);
+
+        // Actions that match a preset are named
+        let ops = &PresetBinding::Paste.entry().binding;
+        assert_eq!(
+            BindingRepr::from_ops(ops),
+            BindingRepr::Preset(PresetBinding::Paste)
+        );
+    }
+
+    #[test]
+    fn def_bindings_are_presets() {
+        let expected = [
+            (HardwareButton::Right, PresetBinding::RightClick),
+            (HardwareButton::Middle, PresetBinding::MiddleClick),
+            (HardwareButton::LeftBottom, PresetBinding::Back),
+            (HardwareButton::LeftTop, PresetBinding::Forward),
+            (HardwareButton::ScrollLeft, PresetBinding::ScrollLeft),
+            (HardwareButton::ScrollRight, PresetBinding::ScrollRight),
+            (
+                HardwareButton::LeftCenter,
+                PresetBinding::SwitchApplications,
+            ),
+        ];
+        assert_eq!(expected.len(), HardwareButton::iter().count());
+        for (button, preset) in expected {
+            let entry = button.def_binding();
+            assert_eq!(entry.id, preset);
+            assert_eq!(Entry::for_binding(&entry.binding).unwrap().id, preset);
+
+            // Also once read back from the device
+            let decoded = decode_action(&encode_action(&entry.binding)).unwrap();
+            assert_eq!(Entry::for_binding(&decoded).unwrap().id, preset);
+        }
+    }
+
+    #[test]
+    fn switch_applications_variants() {
+        for preset in [
+            PresetBinding::SwitchApplications,
+            PresetBinding::SwitchApplicationsAlt,
+            PresetBinding::SwitchWindows,
+        ] {
+            let binding = &preset.entry().binding;
+            assert_eq!(Entry::for_binding(binding).unwrap().id, preset);
+        }
+    }
+
+    #[test]
+    fn distinct_labels() {
+        let mut labels = HashMap::new();
+        for preset in PresetBinding::iter() {
+            let label = preset.entry().label;
+            if let Some(other) = labels.insert(label, preset) {
+                panic!("{:?} and {:?} are both {:?}", other, preset, label);
+            }
+        }
+    }
+
+    #[test]
+    fn disable_middle_button() {
+        let binding = &PresetBinding::Disabled.entry().binding;
+        assert_eq!(binding, &[Op::Kill]);
+        let button = Button::new(HardwareButton::Middle as u8, 0, PressType::Normal, binding);
+        let mut data = Vec::new();
+        button.encode(&mut data);
+        assert_eq!(data, &[1, 0, 0, 1, 0]);
+        assert_eq!(button.decode_action().unwrap(), &[Op::Kill]);
+    }
+
+    #[test]
+    fn invertible_bindings() {
+        for category in &*BINDINGS {
+            for entry in &category.entries {
+                assert_eq!(
+                    decode_action(&encode_action(&entry.binding)).unwrap(),
+                    entry.binding
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn shortcut_bindings_invertible() {
+        for mods in 0..16 {
+            for (_, key) in SHORTCUT_KEYS {
+                let binding = shortcut_binding(mods, *key);
+                assert_eq!(decode_action(&encode_action(&binding)).unwrap(), binding);
+            }
+        }
+    }
+
+    #[test]
+    fn held_key_bindings() {
+        let (down, up) = held_key_binding(KEY_Down);
+        assert_eq!(decode_action(&encode_action(&down)).unwrap(), down);
+        assert_eq!(decode_action(&encode_action(&up)).unwrap(), up);
+        assert_eq!(describe_op(&down[0]), "Down");
+    }
+
+    #[test]
+    fn shortcut_keys_in_keycodes() {
+        for (name, key) in SHORTCUT_KEYS {
+            assert_eq!(key_name(*key), Some(*name));
+        }
+    }
+
+    #[test]
+    fn shortcut_accelerators() {
+        assert_eq!(shortcut_accelerator(0, None), "");
+        assert_eq!(shortcut_accelerator(0, Some(KEY_F11)), "F11");
+        assert_eq!(shortcut_accelerator(MOD_Super, Some(KEY_Tab)), "Super+Tab");
+        assert_eq!(
+            shortcut_accelerator(MOD_Shift | MOD_Ctrl, Some(KEY_A)),
+            "Ctrl+Shift+A"
+        );
+        assert_eq!(
+            shortcut_accelerator(MOD_Ctrl | MOD_Alt | MOD_Shift | MOD_Super, None),
+            "Ctrl+Alt+Shift+Super"
+        );
+    }
+
+    #[test]
+    fn describe_shortcut() {
+        let binding = shortcut_binding(MOD_Ctrl | MOD_Shift, KEY_E);
+        assert_eq!(describe_op(&binding[0]), "Ctrl+Shift+E");
+    }
+
+    #[test]
+    fn desktop_shortcuts() {
+        let desktop = BINDINGS.iter().find(|x| x.label == "Desktop").unwrap();
+        for entry in &desktop.entries {
+            let (mods, key) = match entry.binding.as_slice() {
+                [Op::Key { payload, .. }] => match payload.as_slice() {
+                    [Const(mods), Const(key)] => (*mods, *key),
+                    _ => panic!("{} isn't a shortcut", entry.label),
+                },
+                _ => panic!("{} isn't a shortcut", entry.label),
+            };
+            assert_eq!(
+                entry.keybind,
+                Some(shortcut_accelerator(mods, Some(key)).as_str())
+            );
+            let binding = shortcut_binding(mods, key);
+            assert_eq!(Entry::for_binding(&binding).unwrap().id, entry.id);
+        }
+    }
+
+    #[test]
+    fn media_keys_presets() {
+        use PresetBinding::*;
+        assert_eq!(VolumeUp.with_media_keys(MediaKeys::Keyboard), VolumeUpKey);
+        assert_eq!(VolumeUpKey.with_media_keys(MediaKeys::Consumer), VolumeUp);
+        assert_eq!(MuteKey.with_media_keys(MediaKeys::Keyboard), MuteKey);
+        assert_eq!(PlayPause.with_media_keys(MediaKeys::Keyboard), PlayPause);
+        for (consumer, keyboard) in MEDIA_KEY_PRESETS {
+            assert!(matches!(consumer.entry().binding[..], [Op::Media { .. }]));
+            assert!(matches!(keyboard.entry().binding[..], [Op::Key { .. }]));
+        }
+    }
+
+    #[test]
+    fn launch_keys() {
+        let launch = BINDINGS.iter().find(|x| x.label == "Launch").unwrap();
+        for entry in &launch.entries {
+            // One consumer usage the key picker also offers
+            let usage = KEYCODES.iter().find_map(|(_, _, usage)| match usage {
+                Usage::Consumer(usage) if entry.binding == [Op::media_usage(true, *usage)] => {
+                    Some(*usage)
+                }
+                _ => None,
+            });
+            assert!(usage.is_some(), "{}", entry.label);
+            assert!(entry.keybind.unwrap().starts_with("XF86"));
+        }
+    }
+
+    #[test]
+    fn shortcut_binding_matches_preset() {
+        let binding = shortcut_binding(MOD_Ctrl | MOD_Alt, KEY_Delete);
+        assert_eq!(
+            Entry::for_binding(&binding).unwrap().keybind,
+            Some(shortcut_accelerator(MOD_Ctrl | MOD_Alt, Some(KEY_Delete)).as_str())
+        );
+    }
+}
@@ -0,0 +1,845 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    bindings::{
+        describe_op, device_ops, repeat, BindingRepr, HardwareButton, MediaKeys, PresetBinding,
+    },
+    i18n::gettext,
+    keycode::*,
+};
+use crate::{Button, Op, PressType, Value::*};
+
+#[derive(Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Binding {
+    Preset(PresetBinding),
+    // Sequence of operations built in the binding dialog
+    Custom(Vec<Op>),
+    // Custom sequence sent `count` times per press, as repeated ops
+    Repeat { count: u8, ops: Vec<Op> },
+    // Shell command, run through a desktop shortcut. See `command_binding`.
+    Command { command: String },
+    // Separate actions on pressing and releasing the button, for devices that
+    // report `ButtonsCapabilities::down_up_press`
+    DownUp { down: Vec<Op>, up: Vec<Op> },
+    // Binding read from device, that isn't recognized
+    Unknown,
+}
+
+impl Binding {
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Preset(binding) => binding.entry().label(),
+            Binding::Custom(_) | Binding::Repeat { .. } => gettext("Custom"),
+            Binding::Command { .. } => gettext("Run Command"),
+            Binding::DownUp { .. } => gettext("Press and Release"),
+            Binding::Unknown => gettext("Unknown"),
+        }
+    }
+
+    /// Label with the keys the binding sends, where known
+    pub fn describe(&self) -> String {
+        match self {
+            Binding::Preset(binding) => {
+                let entry = binding.entry();
+                match entry.keybind {
+                    Some(keybind) => format!("{} ({})", entry.label(), keybind),
+                    None => entry.label(),
+                }
+            }
+            Binding::Custom(ops) => describe_ops(ops),
+            Binding::Repeat { count, ops } => format!("{} ×{}", describe_ops(ops), count),
+            Binding::Command { command } => format!("{}: {}", gettext("Run Command"), command),
+            Binding::DownUp { down, up } => format!(
+                "{}: {}; {}: {}",
+                gettext("Press"),
+                describe_ops(down),
+                gettext("Release"),
+                describe_ops(up)
+            ),
+            Binding::Unknown => gettext("Unknown"),
+        }
+    }
+
+    /// Normal press action to program on `button`, or `None` if unknown or
+    /// `DownUp`
+    fn ops(&self, button: HardwareButton) -> Option<Vec<Op>> {
+        match self {
+            Binding::Preset(preset) => Some(device_ops(&preset.entry().binding)),
+            Binding::Custom(ops) => Some(ops.clone()),
+            Binding::Repeat { count, ops } => Some(repeat(ops, *count)),
+            Binding::Command { .. } => Some(command_sentinel(button)),
+            Binding::DownUp { .. } | Binding::Unknown => None,
+        }
+    }
+
+    /// Whether this can be programmed, on a device that supports separate
+    /// down and up actions if `support_down_up`
+    pub fn can_program(&self, support_down_up: bool) -> bool {
+        match self {
+            Binding::DownUp { .. } => support_down_up,
+            Binding::Unknown => false,
+            _ => true,
+        }
+    }
+
+    /// The same binding with media control presets sent as `media_keys`
+    pub fn with_media_keys(self, media_keys: MediaKeys) -> Self {
+        match self {
+            Binding::Preset(preset) => Binding::Preset(preset.with_media_keys(media_keys)),
+            binding => binding,
+        }
+    }
+
+    /// `Custom` binding for `ops`, or `Repeat` if `count` is more than 1
+    pub fn repeat(ops: Vec<Op>, count: u8) -> Self {
+        if count > 1 {
+            Binding::Repeat { count, ops }
+        } else {
+            Binding::Custom(ops)
+        }
+    }
+}
+
+impl From<BindingRepr> for Binding {
+    fn from(repr: BindingRepr) -> Self {
+        match repr {
+            BindingRepr::Preset(preset) => Binding::Preset(preset),
+            BindingRepr::Custom(ops) => Binding::Custom(ops),
+        }
+    }
+}
+
+fn describe_ops(ops: &[Op]) -> String {
+    let steps: Vec<String> = ops.iter().map(describe_op).collect();
+    steps.join(", ")
+}
+
+/// Buttons to write to program `binding` on `button`, with `None` being the
+/// default, or `None` if the binding is unknown. The down and up actions are
+/// separate from the normal one, so with `clear_down_up` they are also
+/// written, empty, for other bindings.
+pub fn binding_buttons(
+    button: HardwareButton,
+    binding: Option<&Binding>,
+    clear_down_up: bool,
+) -> Option<Vec<Button>> {
+    let (normal, down, up) = match binding {
+        None => Default::default(),
+        Some(Binding::DownUp { down, up }) => (Vec::new(), down.clone(), up.clone()),
+        Some(binding) => (binding.ops(button)?, Vec::new(), Vec::new()),
+    };
+    let id = button as u8;
+    let mut buttons = vec![Button::new(id, 0, PressType::Normal, &normal)];
+    if clear_down_up || !down.is_empty() || !up.is_empty() {
+        buttons.push(Button::new(id, 0, PressType::Down, &down));
+        buttons.push(Button::new(id, 0, PressType::Up, &up));
+    }
+    Some(buttons)
+}
+
+// Whether `a` and `b` program the same actions on `button`. Compares the
+// decoded operations, so a binding read back from the device matches the one
+// it was programmed from, such as `Command` and its sentinel.
+fn same_actions(button: HardwareButton, a: Option<&Binding>, b: Option<&Binding>) -> bool {
+    fn actions(
+        button: HardwareButton,
+        binding: Option<&Binding>,
+    ) -> Option<Vec<(PressType, Vec<Op>)>> {
+        binding_buttons(button, binding, false)?
+            .iter()
+            .map(|x| Some((x.press_type, x.decode_action().ok()?)))
+            .collect()
+    }
+    match (actions(button, a), actions(button, b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[derive(Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Profile {
+    pub name: Option<String>,
+    pub bindings: HashMap<HardwareButton, Binding>,
+    pub left_handed: bool,
+    // Sensitivities to switch between, in increasing order
+    #[serde(default)]
+    pub dpi_stages: Vec<u16>,
+    // Buttons programmed as disabled, keeping their binding for re-enabling
+    #[serde(default)]
+    pub disabled: HashSet<HardwareButton>,
+    // Read-only in the GUI, so stray clicks don't reprogram the device
+    #[serde(default)]
+    pub locked: bool,
+}
+
+// Interop with other programs; the GUI itself keeps profiles in JSON
+#[cfg(feature = "binary-profiles")]
+#[allow(dead_code)]
+impl Profile {
+    /// Compact binary form, in MessagePack, with the same fields as the JSON
+    pub fn to_binary(&self) -> Result<Vec<u8>, String> {
+        rmp_serde::to_vec_named(self).map_err(|x| x.to_string())
+    }
+
+    pub fn from_binary(data: &[u8]) -> Result<Self, String> {
+        rmp_serde::from_slice(data).map_err(|x| x.to_string())
+    }
+
+    pub fn save_binary(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_binary()?).map_err(|x| x.to_string())
+    }
+
+    pub fn load_binary(path: &Path) -> Result<Self, String> {
+        Self::from_binary(&std::fs::read(path).map_err(|x| x.to_string())?)
+    }
+}
+
+impl Profile {
+    /// Set the binding of `button`, leaving it unset where it is the default
+    pub fn set_binding(&mut self, button: HardwareButton, binding: Binding) {
+        if button.def_binding_is_factory() && binding == Binding::Preset(button.def_binding().id) {
+            self.bindings.remove(&button);
+        } else {
+            self.bindings.insert(button, binding);
+        }
+    }
+
+    /// Send the media control presets of every binding as `media_keys`
+    pub fn set_media_keys(&mut self, media_keys: MediaKeys) {
+        for binding in self.bindings.values_mut() {
+            *binding = binding.clone().with_media_keys(media_keys);
+        }
+    }
+
+    /// Binding to program on `button`, with `None` being the default.
+    /// `Disabled` while the button is in `disabled`.
+    pub fn binding(&self, button: HardwareButton) -> Option<Binding> {
+        if self.disabled.contains(&button) {
+            Some(Binding::Preset(PresetBinding::Disabled))
+        } else {
+            self.bindings.get(&button).cloned()
+        }
+    }
+
+    /// Settings that change when switching from this profile to `other`
+    pub fn diff(&self, other: &Profile) -> Vec<Change> {
+        let mut changes = Vec::new();
+        for button in HardwareButton::iter() {
+            let from = self.binding(button);
+            let to = other.binding(button);
+            if from != to {
+                changes.push(Change::Binding { button, from, to });
+            }
+        }
+        if self.left_handed != other.left_handed {
+            changes.push(Change::LeftHanded(other.left_handed));
+        }
+        if self.dpi_stages != other.dpi_stages {
+            changes.push(Change::DpiStages(other.dpi_stages.clone()));
+        }
+        changes
+    }
+
+    /// Bindings that applying this profile would change on a device that
+    /// has `device`, as read from it
+    pub fn binding_changes(&self, device: &HashMap<HardwareButton, Binding>) -> Vec<Change> {
+        let mut changes = Vec::new();
+        for button in HardwareButton::iter() {
+            let from = device.get(&button);
+            let to = self.binding(button);
+            if !same_actions(button, from, to.as_ref()) {
+                changes.push(Change::Binding {
+                    button,
+                    from: from.cloned(),
+                    to,
+                });
+            }
+        }
+        changes
+    }
+
+    /// Markdown table of what each button does, for reference or sharing
+    pub fn to_cheatsheet(&self) -> String {
+        // Keep labels from breaking the table
+        fn cell(text: &str) -> String {
+            text.replace('|', "\\|").replace('\n', " ")
+        }
+
+        let name = self
+            .name
+            .clone()
+            .unwrap_or_else(|| gettext("Mouse Configuration"));
+        let mut sheet = format!("# {}\n\n", cell(&name));
+        sheet.push_str(&format!(
+            "| {} | {} |\n| --- | --- |\n",
+            gettext("Button"),
+            gettext("Action")
+        ));
+        let primary = if self.left_handed {
+            gettext("Right")
+        } else {
+            gettext("Left")
+        };
+        sheet.push_str(&format!(
+            "| {} | {} |\n",
+            gettext("Primary Button"),
+            primary
+        ));
+        for button in HardwareButton::iter() {
+            let action = match self.binding(button) {
+                Some(binding) => binding.describe(),
+                None => Binding::Preset(button.def_binding().id).describe(),
+            };
+            sheet.push_str(&format!("| {} | {} |\n", button.label(), cell(&action)));
+        }
+        sheet
+    }
+}
+
+/// Setting that differs between two configurations. `None` is the default.
+pub enum Change {
+    Binding {
+        button: HardwareButton,
+        from: Option<Binding>,
+        to: Option<Binding>,
+    },
+    LeftHanded(bool),
+    DpiStages(Vec<u16>),
+    Dpi {
+        from: f64,
+        to: f64,
+    },
+    CutOff {
+        from: Option<u8>,
+        to: Option<u8>,
+    },
+}
+
+impl Change {
+    pub fn describe(&self) -> String {
+        fn binding_label(button: HardwareButton, binding: &Option<Binding>) -> String {
+            match binding {
+                Some(binding) => binding.label(),
+                None => button.def_binding().label(),
+            }
+        }
+        fn cut_off_label(cut_off: Option<u8>) -> String {
+            cut_off.map_or_else(|| gettext("Unchanged"), |x| x.to_string())
+        }
+
+        match self {
+            Change::Binding { button, from, to } => format!(
+                "{:?} {}: {} → {}",
+                button,
+                gettext("Button"),
+                binding_label(*button, from),
+                binding_label(*button, to)
+            ),
+            Change::LeftHanded(left_handed) => format!(
+                "{}: {}",
+                gettext("Primary Button"),
+                if *left_handed {
+                    gettext("Right")
+                } else {
+                    gettext("Left")
+                }
+            ),
+            Change::DpiStages(stages) => {
+                let stages: Vec<String> = stages.iter().map(u16::to_string).collect();
+                format!("{}: {}", gettext("DPI Stages"), stages.join(", "))
+            }
+            Change::Dpi { from, to } => format!(
+                "{}: {} → {}",
+                gettext("Sensitivity (DPI)"),
+                from.round(),
+                to.round()
+            ),
+            Change::CutOff { from, to } => format!(
+                "{}: {} → {}",
+                gettext("Lift-off Distance"),
+                cut_off_label(*from),
+                cut_off_label(*to)
+            ),
+        }
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct MouseConfig {
+    // Must always be non-empty
+    profiles: Vec<Profile>,
+    // Must Always be in range
+    profile_num: usize,
+    pub dpi: f64,
+    // Lift-off distance; device's own setting if unset
+    #[serde(default)]
+    pub cut_off: Option<u8>,
+    // How media control presets are sent, in every profile
+    #[serde(default)]
+    pub media_keys: MediaKeys,
+    pub device: String,
+}
+
+impl MouseConfig {
+    // XXX Default DPI should depend on device model
+    pub fn new(device: String) -> Self {
+        let profiles = (0..4).map(|_| Profile::default()).collect();
+        Self {
+            profiles,
+            profile_num: 0,
+            device,
+            dpi: 1200.,
+            cut_off: None,
+            media_keys: MediaKeys::default(),
+        }
+    }
+
+    pub fn profile(&self) -> &Profile {
+        &self.profiles[self.profile_num]
+    }
+
+    pub fn profile_mut(&mut self) -> &mut Profile {
+        &mut self.profiles[self.profile_num]
+    }
+
+    pub fn profiles(&self) -> &[Profile] {
+        &self.profiles
+    }
+
+    pub fn profiles_mut(&mut self) -> &mut [Profile] {
+        &mut self.profiles
+    }
+
+    pub fn select_profile(&mut self, profile: usize) {
+        if profile < self.profiles.len() {
+            self.profile_num = profile;
+        }
+    }
+
+    pub fn profile_num(&self) -> usize {
+        self.profile_num
+    }
+
+    /// Settings that change when replacing this configuration with `other`,
+    /// including the selected profile's
+    pub fn diff(&self, other: &MouseConfig) -> Vec<Change> {
+        let mut changes = Vec::new();
+        if self.dpi.round() != other.dpi.round() {
+            changes.push(Change::Dpi {
+                from: self.dpi,
+                to: other.dpi,
+            });
+        }
+        if self.cut_off != other.cut_off {
+            changes.push(Change::CutOff {
+                from: self.cut_off,
+                to: other.cut_off,
+            });
+        }
+        changes.extend(self.profile().diff(other.profile()));
+        changes
+    }
+
+    pub fn import(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|x| x.to_string())?;
+        let mut config: Self = serde_json::from_reader(file).map_err(|x| x.to_string())?;
+        // Commands must be created explicitly, not come from a shared file
+        for profile in &mut config.profiles {
+            profile
+                .bindings
+                .retain(|_, binding| !matches!(binding, Binding::Command { .. }));
+        }
+        Ok(config)
+    }
+
+    pub fn export(&self, path: &Path) -> Result<(), String> {
+        let file = File::create(path).map_err(|x| x.to_string())?;
+        serde_json::to_writer_pretty(file, self).map_err(|x| x.to_string())
+    }
+}
+
+// Ctrl+Alt+Super+<digit>, with the digit chosen by button. See `command_binding`.
+pub fn command_sentinel(button: HardwareButton) -> Vec<Op> {
+    vec![Op::key(
+        true,
+        vec![
+            Const(MOD_Ctrl | MOD_Alt | MOD_Super),
+            Const(KEY_1 + button as i8),
+        ],
+    )]
+}
+
+fn data_dir() -> PathBuf {
+    if let Ok(dir) = env::var("XDG_DATA_HOME") {
+        dir.into()
+    } else if let Ok(dir) = env::var("HOME") {
+        let mut path = PathBuf::from(dir);
+        path.push(".local/share");
+        path
+    } else {
+        panic!("`XDG_DATA_HOME` and `HOME` undefined")
+    }
+}
+
+fn app_data_dir() -> PathBuf {
+    let mut dir = data_dir();
+    dir.push("org.pop_os.mouseconfigurator");
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        panic!("Failed to create directory `{}`: {}", dir.display(), err);
+    }
+    dir
+}
+
+// TODO: format? Multiple files?
+// XXX error handling? Don't run `app_data_dir` every save?
+pub fn load_config() -> HashMap<String, MouseConfig> {
+    let mut path = app_data_dir();
+    path.push("config.json");
+
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(_) => {
+            return HashMap::new();
+        }
+    };
+
+    let mut config: HashMap<String, MouseConfig> = match serde_json::from_reader(file) {
+        Ok(config) => config,
+        Err(err) => {
+            log::error!("Failed to load config: {}", err);
+            return HashMap::new();
+        }
+    };
+    // Ensure there are exactly 4 profiles. May change in future.
+    for mouse in config.values_mut() {
+        mouse.profiles.truncate(4);
+        while mouse.profiles.len() < 4 {
+            mouse.profiles.push(Profile::default());
+        }
+    }
+    config
+}
+
+// TODO: atomic replace
+// TODO: don't collect? `SeqIteratorVisitor`
+pub fn save_config<'a, T: Iterator<Item = (&'a String, &'a MouseConfig)>>(config: T) {
+    let mut path = app_data_dir();
+    path.push("config.json");
+
+    let config: HashMap<_, _> = config.collect();
+
+    let file = File::create(&path).unwrap();
+    serde_json::to_writer(file, &config).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_diff() {
+        let mut from = Profile::default();
+        from.bindings
+            .insert(HardwareButton::Right, Binding::Preset(PresetBinding::Copy));
+        let mut to = Profile::default();
+        to.bindings.insert(
+            HardwareButton::Middle,
+            Binding::Preset(PresetBinding::Paste),
+        );
+        to.left_handed = true;
+        to.dpi_stages = vec![800, 1600];
+
+        let changes = from.diff(&to);
+        assert_eq!(changes.len(), 4);
+        assert!(matches!(
+            &changes[0],
+            Change::Binding {
+                button: HardwareButton::Right,
+                from: Some(_),
+                to: None,
+            }
+        ));
+        assert!(matches!(
+            &changes[1],
+            Change::Binding {
+                button: HardwareButton::Middle,
+                from: None,
+                to: Some(_),
+            }
+        ));
+        assert!(matches!(changes[2], Change::LeftHanded(true)));
+        assert_eq!(changes[3].describe(), "DPI Stages: 800, 1600");
+        assert!(to.diff(&to).is_empty());
+    }
+
+    #[test]
+    fn binding_changes() {
+        let mut profile = Profile::default();
+        profile
+            .bindings
+            .insert(HardwareButton::Right, Binding::Preset(PresetBinding::Copy));
+        profile.bindings.insert(
+            HardwareButton::Middle,
+            Binding::Command {
+                command: "true".to_string(),
+            },
+        );
+
+        // As read back after programming the profile
+        let mut device = HashMap::new();
+        device.insert(
+            HardwareButton::Right,
+            Binding::Custom(PresetBinding::Copy.entry().binding.clone()),
+        );
+        device.insert(
+            HardwareButton::Middle,
+            Binding::Custom(command_sentinel(HardwareButton::Middle)),
+        );
+        assert!(profile.binding_changes(&device).is_empty());
+
+        device.insert(HardwareButton::Right, Binding::Preset(PresetBinding::Paste));
+        device.insert(HardwareButton::LeftTop, Binding::Unknown);
+        let changes = profile.binding_changes(&device);
+        assert_eq!(changes.len(), 2);
+        assert!(matches!(
+            &changes[0],
+            Change::Binding {
+                button: HardwareButton::Right,
+                from: Some(Binding::Preset(PresetBinding::Paste)),
+                to: Some(_),
+            }
+        ));
+    }
+
+    #[test]
+    fn config_diff() {
+        let from = MouseConfig::new("Brain".to_string());
+        let mut to = from.clone();
+        assert!(from.diff(&to).is_empty());
+        to.dpi = 1600.;
+        to.cut_off = Some(2);
+        let changes = from.diff(&to);
+        assert!(matches!(changes[0], Change::Dpi { .. }));
+        assert!(matches!(
+            changes[1],
+            Change::CutOff {
+                from: None,
+                to: Some(2)
+            }
+        ));
+        assert_eq!(changes.len(), 2);
+    }
+
+    #[test]
+    fn set_binding() {
+        let mut profile = Profile::default();
+        let copy = Binding::Preset(PresetBinding::Copy);
+        for button in [HardwareButton::Right, HardwareButton::Middle] {
+            profile.set_binding(button, copy.clone());
+        }
+        assert_eq!(profile.bindings.len(), 2);
+        let def = Binding::Preset(HardwareButton::Right.def_binding().id);
+        profile.set_binding(HardwareButton::Right, def);
+        assert!(!profile.bindings.contains_key(&HardwareButton::Right));
+
+        let down_up = Binding::DownUp {
+            down: Vec::new(),
+            up: Vec::new(),
+        };
+        assert!(!down_up.can_program(false));
+        assert!(down_up.can_program(true));
+        assert!(!Binding::Unknown.can_program(true));
+    }
+
+    #[test]
+    fn disabled_keeps_binding() {
+        let copy = Binding::Preset(PresetBinding::Copy);
+        let mut from = Profile::default();
+        from.bindings.insert(HardwareButton::Right, copy.clone());
+        let mut to = from.clone();
+        to.disabled.insert(HardwareButton::Right);
+
+        let disabled = Binding::Preset(PresetBinding::Disabled);
+        assert!(to.binding(HardwareButton::Right) == Some(disabled.clone()));
+        assert!(to.bindings.get(&HardwareButton::Right) == Some(&copy));
+        let changes = from.diff(&to);
+        assert!(matches!(
+            changes.as_slice(),
+            [Change::Binding { from: Some(a), to: Some(b), .. }] if *a == copy && *b == disabled
+        ));
+
+        // The device reporting the button disabled matches
+        let device = HashMap::from([(HardwareButton::Right, disabled)]);
+        assert!(to.binding_changes(&device).is_empty());
+        assert_eq!(from.binding_changes(&device).len(), 1);
+    }
+
+    #[test]
+    fn locked_persists() {
+        let json = r#"{"name":null,"bindings":{},"left_handed":false}"#;
+        let profile: Profile = serde_json::from_str(json).unwrap();
+        assert!(!profile.locked);
+
+        let locked = Profile {
+            locked: true,
+            ..Profile::default()
+        };
+        let json = serde_json::to_string(&locked).unwrap();
+        let profile: Profile = serde_json::from_str(&json).unwrap();
+        assert!(profile.locked);
+        assert!(locked.diff(&profile).is_empty());
+    }
+
+    #[test]
+    fn set_media_keys() {
+        let mut profile = Profile::default();
+        profile.set_binding(
+            HardwareButton::Right,
+            Binding::Preset(PresetBinding::VolumeUp),
+        );
+        profile.set_binding(HardwareButton::Middle, Binding::Preset(PresetBinding::Copy));
+        profile.set_media_keys(MediaKeys::Keyboard);
+        assert!(
+            profile.binding(HardwareButton::Right)
+                == Some(Binding::Preset(PresetBinding::VolumeUpKey))
+        );
+        assert!(
+            profile.binding(HardwareButton::Middle) == Some(Binding::Preset(PresetBinding::Copy))
+        );
+        profile.set_media_keys(MediaKeys::Consumer);
+        assert!(
+            profile.binding(HardwareButton::Right)
+                == Some(Binding::Preset(PresetBinding::VolumeUp))
+        );
+    }
+
+    #[cfg(feature = "binary-profiles")]
+    #[test]
+    fn binary_matches_json() {
+        let mut profile = Profile {
+            name: Some("Binary".to_string()),
+            left_handed: true,
+            dpi_stages: vec![800, 1600],
+            locked: true,
+            ..Profile::default()
+        };
+        let down = vec![Op::key(false, vec![Const(0), Const(KEY_F8)])];
+        let up = vec![Op::key(true, vec![Const(0), Const(KEY_F8)])];
+        profile
+            .bindings
+            .insert(HardwareButton::Right, Binding::Preset(PresetBinding::Copy));
+        profile.bindings.insert(
+            HardwareButton::Middle,
+            Binding::Custom(vec![Op::pause(100), Op::Kill]),
+        );
+        profile
+            .bindings
+            .insert(HardwareButton::LeftTop, Binding::repeat(down.clone(), 2));
+        profile
+            .bindings
+            .insert(HardwareButton::LeftBottom, Binding::DownUp { down, up });
+        profile.disabled.insert(HardwareButton::Middle);
+
+        let json = serde_json::to_string(&profile).unwrap();
+        let from_json: Profile = serde_json::from_str(&json).unwrap();
+        let binary = profile.to_binary().unwrap();
+        let from_binary = Profile::from_binary(&binary).unwrap();
+        assert!(binary.len() < json.len());
+        assert!(from_json.diff(&from_binary).is_empty());
+        assert!(from_json.bindings == from_binary.bindings);
+        assert_eq!(from_json.disabled, from_binary.disabled);
+        assert_eq!(from_json.name, from_binary.name);
+        assert_eq!(from_json.dpi_stages, from_binary.dpi_stages);
+        assert!(from_binary.locked && from_binary.left_handed);
+    }
+
+    #[test]
+    fn cheatsheet() {
+        let mut profile = Profile {
+            name: Some("Editing".to_string()),
+            ..Profile::default()
+        };
+        profile
+            .bindings
+            .insert(HardwareButton::Right, Binding::Preset(PresetBinding::Copy));
+        profile.bindings.insert(
+            HardwareButton::LeftTop,
+            Binding::Custom(vec![Op::key(
+                true,
+                vec![Const(MOD_Ctrl | MOD_Shift), Const(KEY_E)],
+            )]),
+        );
+        profile.bindings.insert(
+            HardwareButton::LeftBottom,
+            Binding::Command {
+                command: "a | b".to_string(),
+            },
+        );
+
+        let sheet = profile.to_cheatsheet();
+        let lines: Vec<&str> = sheet.lines().collect();
+        assert_eq!(lines[0], "# Editing");
+        assert_eq!(lines[2], "| Button | Action |");
+        assert_eq!(lines[4], "| Primary Button | Left |");
+        assert!(lines.contains(&"| Right Button | Copy (Ctrl+C) |"));
+        assert!(lines.contains(&"| Upper Side Button | Ctrl+Shift+E |"));
+        assert!(lines.contains(&"| Lower Side Button | Run Command: a \\| b |"));
+        // Header, table header, primary button, and every other button
+        assert_eq!(lines.len(), 5 + HardwareButton::iter().count());
+    }
+
+    #[test]
+    fn down_up_buttons() {
+        let down = vec![Op::key(false, vec![Const(0), Const(KEY_F8)])];
+        let up = vec![Op::key(true, vec![Const(0), Const(KEY_F9)])];
+        let binding = Binding::DownUp {
+            down: down.clone(),
+            up: up.clone(),
+        };
+        let json = serde_json::to_string(&binding).unwrap();
+        assert!(serde_json::from_str::<Binding>(&json).unwrap() == binding);
+
+        let buttons = binding_buttons(HardwareButton::Middle, Some(&binding), false).unwrap();
+        let press_types: Vec<_> = buttons.iter().map(|x| x.press_type).collect();
+        assert_eq!(
+            press_types,
+            [PressType::Normal, PressType::Down, PressType::Up]
+        );
+        assert!(buttons[0].decode_action().unwrap().is_empty());
+        assert_eq!(buttons[1].decode_action().unwrap(), down);
+        assert_eq!(buttons[2].decode_action().unwrap(), up);
+
+        // Other bindings only clear down and up actions if asked to
+        let copy = Binding::Preset(PresetBinding::Copy);
+        assert_eq!(
+            binding_buttons(HardwareButton::Middle, Some(&copy), false)
+                .unwrap()
+                .len(),
+            1
+        );
+        let buttons = binding_buttons(HardwareButton::Middle, None, true).unwrap();
+        assert_eq!(buttons.len(), 3);
+        assert!(buttons
+            .iter()
+            .all(|x| x.decode_action().unwrap().is_empty()));
+    }
+
+    #[test]
+    fn repeat_buttons() {
+        let ops = vec![Op::mouse(true, 0, 0, 0, -1, 0)];
+        assert!(Binding::repeat(ops.clone(), 1) == Binding::Custom(ops.clone()));
+
+        let binding = Binding::repeat(ops.clone(), 3);
+        let json = serde_json::to_string(&binding).unwrap();
+        assert!(serde_json::from_str::<Binding>(&json).unwrap() == binding);
+
+        let buttons = binding_buttons(HardwareButton::Middle, Some(&binding), false).unwrap();
+        assert_eq!(buttons[0].decode_action().unwrap(), vec![ops[0].clone(); 3]);
+    }
+}
@@ -0,0 +1,31 @@
+//! Translation of user-facing strings, with gettext. Strings that can't be
+//! translated where they're defined, such as in statics, are marked with
+//! `N_!` so `xgettext` extracts them, and translated where they're shown.
+
+pub use gettextrs::gettext;
+use gettextrs::{bind_textdomain_codeset, bindtextdomain, setlocale, textdomain, LocaleCategory};
+
+const DOMAIN: &str = "mouse-configurator";
+// Set by the Makefile, for installing to another prefix
+const LOCALE_DIR: &str = match option_env!("LOCALEDIR") {
+    Some(dir) => dir,
+    None => "/usr/share/locale",
+};
+
+/// Mark a string for translation, without translating it
+#[macro_export]
+macro_rules! N_ {
+    ($msgid:literal) => {
+        $msgid
+    };
+}
+
+pub fn init() {
+    setlocale(LocaleCategory::LcAll, "");
+    let res = bindtextdomain(DOMAIN, LOCALE_DIR)
+        .and_then(|_| bind_textdomain_codeset(DOMAIN, "UTF-8"))
+        .and_then(|_| textdomain(DOMAIN));
+    if let Err(err) = res {
+        log::warn!("Failed to set up translations: {}", err);
+    }
+}
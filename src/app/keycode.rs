@@ -0,0 +1,405 @@
+#![allow(unused, non_upper_case_globals, overflowing_literals)]
+
+use once_cell::sync::Lazy;
+use std::env;
+
+// Matches /sys/kernel/debug/hid/*/rdesc
+
+pub const MOD_Ctrl: i8 = 1 << 0;
+pub const MOD_Shift: i8 = 1 << 1;
+pub const MOD_Alt: i8 = 1 << 2;
+pub const MOD_Super: i8 = 1 << 3;
+pub const MOD_RightCtrl: i8 = 1 << 4;
+pub const MOD_RightShift: i8 = 1 << 5;
+pub const MOD_RightAlt: i8 = 1 << 6;
+pub const MOD_RightSuper: i8 = 1 << 7;
+
+// Names for `MOUSE_CONFIGURATOR_SUPER`
+const MODIFIER_NAMES: &[(&str, i8)] = &[
+    ("Ctrl", MOD_Ctrl),
+    ("Shift", MOD_Shift),
+    ("Alt", MOD_Alt),
+    ("Meta", MOD_Alt),
+    ("Super", MOD_Super),
+    ("RightCtrl", MOD_RightCtrl),
+    ("RightShift", MOD_RightShift),
+    ("RightAlt", MOD_RightAlt),
+    ("RightSuper", MOD_RightSuper),
+];
+
+// Modifiers for `name`, such as "Ctrl+Alt"
+fn parse_modifiers(name: &str) -> Option<i8> {
+    name.split('+').try_fold(0, |mods, name| {
+        let (_, x) = MODIFIER_NAMES
+            .iter()
+            .find(|(x, _)| x.eq_ignore_ascii_case(name.trim()))?;
+        Some(mods | x)
+    })
+}
+
+// Modifiers for "Super" in `spec`, a comma separated list of modifier names,
+// optionally prefixed with a desktop from `XDG_CURRENT_DESKTOP` and `=`. The
+// first entry for one of `desktops`, or without a desktop, applies.
+fn super_alias(spec: &str, desktops: &str) -> Option<i8> {
+    spec.split(',').find_map(|entry| {
+        let name = match entry.split_once('=') {
+            Some((desktop, name)) => {
+                let desktop = desktop.trim();
+                desktops
+                    .split(':')
+                    .any(|x| x.eq_ignore_ascii_case(desktop))
+                    .then_some(name)?
+            }
+            None => entry,
+        };
+        parse_modifiers(name)
+    })
+}
+
+static MOD_SUPER_ALIAS: Lazy<i8> = Lazy::new(|| {
+    let spec = env::var("MOUSE_CONFIGURATOR_SUPER").unwrap_or_default();
+    let desktops = env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    super_alias(&spec, &desktops).unwrap_or_else(|| {
+        if !spec.is_empty() {
+            log::warn!("No modifier for this desktop in `MOUSE_CONFIGURATOR_SUPER`");
+        }
+        MOD_Super
+    })
+});
+
+// `mods` with all of `from` replaced by `to`
+fn replace_mods(mods: i8, from: i8, to: i8) -> i8 {
+    if mods & from == from {
+        (mods & !from) | to
+    } else {
+        mods
+    }
+}
+
+/// Modifiers to program on the device for `mods`, with `MOD_Super` sent as
+/// set with `MOUSE_CONFIGURATOR_SUPER`, such as `Meta` or `KDE=Meta,Super`,
+/// for layouts or desktops where the key differs. Presets keep `MOD_Super`,
+/// so they are the same whatever the setting.
+pub fn resolve_super(mods: i8) -> i8 {
+    replace_mods(mods, MOD_Super, *MOD_SUPER_ALIAS)
+}
+
+/// Inverse of `resolve_super`, for modifiers read back from the device
+pub fn logical_super(mods: i8) -> i8 {
+    if *MOD_SUPER_ALIAS == MOD_Super {
+        return mods;
+    }
+    replace_mods(mods, *MOD_SUPER_ALIAS, MOD_Super)
+}
+
+pub const KEY_A: i8 = 0x04;
+pub const KEY_B: i8 = 0x05;
+pub const KEY_C: i8 = 0x06;
+pub const KEY_D: i8 = 0x07;
+pub const KEY_E: i8 = 0x08;
+pub const KEY_F: i8 = 0x09;
+pub const KEY_G: i8 = 0x0A;
+pub const KEY_H: i8 = 0x0B;
+pub const KEY_I: i8 = 0x0C;
+pub const KEY_J: i8 = 0x0D;
+pub const KEY_K: i8 = 0x0E;
+pub const KEY_L: i8 = 0x0F;
+pub const KEY_M: i8 = 0x10;
+pub const KEY_N: i8 = 0x11;
+pub const KEY_O: i8 = 0x12;
+pub const KEY_P: i8 = 0x13;
+pub const KEY_Q: i8 = 0x14;
+pub const KEY_R: i8 = 0x15;
+pub const KEY_S: i8 = 0x16;
+pub const KEY_T: i8 = 0x17;
+pub const KEY_U: i8 = 0x18;
+pub const KEY_V: i8 = 0x19;
+pub const KEY_W: i8 = 0x1A;
+pub const KEY_X: i8 = 0x1B;
+pub const KEY_Y: i8 = 0x1C;
+pub const KEY_Z: i8 = 0x1D;
+pub const KEY_1: i8 = 0x1E;
+pub const KEY_2: i8 = 0x1F;
+pub const KEY_3: i8 = 0x20;
+pub const KEY_4: i8 = 0x21;
+pub const KEY_5: i8 = 0x22;
+pub const KEY_6: i8 = 0x23;
+pub const KEY_7: i8 = 0x24;
+pub const KEY_8: i8 = 0x25;
+pub const KEY_9: i8 = 0x26;
+pub const KEY_0: i8 = 0x27;
+pub const KEY_Enter: i8 = 0x28;
+pub const KEY_Esc: i8 = 0x29;
+pub const KEY_Backspace: i8 = 0x2A;
+pub const KEY_Tab: i8 = 0x2B;
+pub const KEY_Space: i8 = 0x2C;
+pub const KEY_Minus: i8 = 0x2D;
+pub const KEY_Equal: i8 = 0x2E;
+pub const KEY_LeftBrace: i8 = 0x2F;
+pub const KEY_RightBrace: i8 = 0x30;
+// ...
+pub const KEY_Grave: i8 = 0x35;
+// ...
+pub const KEY_Slash: i8 = 0x38;
+// ...
+pub const KEY_F1: i8 = 0x3A;
+pub const KEY_F2: i8 = 0x3B;
+pub const KEY_F3: i8 = 0x3C;
+pub const KEY_F4: i8 = 0x3D;
+pub const KEY_F5: i8 = 0x3E;
+pub const KEY_F6: i8 = 0x3F;
+pub const KEY_F7: i8 = 0x40;
+pub const KEY_F8: i8 = 0x41;
+pub const KEY_F9: i8 = 0x42;
+pub const KEY_F10: i8 = 0x43;
+pub const KEY_F11: i8 = 0x44;
+pub const KEY_F12: i8 = 0x45;
+pub const KEY_SysRq: i8 = 0x46;
+// ...
+pub const KEY_Home: i8 = 0x4A;
+pub const KEY_PageUp: i8 = 0x4B;
+pub const KEY_Delete: i8 = 0x4C;
+pub const KEY_End: i8 = 0x4D;
+pub const KEY_PageDown: i8 = 0x4E;
+pub const KEY_Right: i8 = 0x4F;
+pub const KEY_Left: i8 = 0x50;
+pub const KEY_Down: i8 = 0x51;
+pub const KEY_Up: i8 = 0x52;
+// ...
+pub const KEY_Mute: i8 = 0x7F;
+pub const KEY_VolumeUp: i8 = 0x80;
+pub const KEY_VolumeDown: i8 = 0x81;
+// ...
+pub const KEY_LeftCtrl: i8 = 0xE0;
+pub const KEY_LeftShift: i8 = 0xE1;
+pub const KEY_LeftAlt: i8 = 0xE2;
+pub const KEY_LeftSuper: i8 = 0xE3;
+pub const KEY_RightCtrl: i8 = 0xE4;
+pub const KEY_RightShift: i8 = 0xE5;
+pub const KEY_RightAlt: i8 = 0xE6;
+pub const KEY_RighSuper: i8 = 0xE7;
+
+// Consumer page
+pub const MEDIA_Restart: i8 = 0x31;
+// ...
+pub const MEDIA_BrightnessUp: i8 = 0x6F;
+pub const MEDIA_BrightnessDown: i8 = 0x70;
+// ...
+pub const MEDIA_Play: i8 = 0xB0;
+pub const MEDIA_Pause: i8 = 0xB1;
+pub const MEDIA_Record: i8 = 0xB2;
+pub const MEDIA_FastForward: i8 = 0xB3;
+pub const MEDIA_Rewind: i8 = 0xB4;
+pub const MEDIA_NextSong: i8 = 0xB5;
+pub const MEDIA_PreviousSong: i8 = 0xB6;
+// ...
+pub const MEDIA_PlayPause: i8 = 0xCD;
+// ...
+pub const MEDIA_Mute: i8 = 0xE2;
+// ...
+pub const MEDIA_VolumeUp: i8 = 0xE9;
+pub const MEDIA_VolumeDown: i8 = 0xEA;
+// Usages above 0xFF, for `Op::media_usage`
+pub const MEDIA_Mail: u16 = 0x18A;
+pub const MEDIA_Calculator: u16 = 0x192;
+pub const MEDIA_Files: u16 = 0x194;
+pub const MEDIA_Browser: u16 = 0x196;
+pub const MEDIA_Home: u16 = 0x223;
+pub const MEDIA_Back: u16 = 0x224;
+pub const MEDIA_Forward: u16 = 0x225;
+
+// TODO: Other supported codes, as needed
+
+/// Groups of `KEYCODES`, for a key picker
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyGroup {
+    Letters,
+    Digits,
+    Function,
+    Editing,
+    Symbols,
+    Navigation,
+    Modifiers,
+    Media,
+}
+
+/// A code from this module, by where it goes in an `Op`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Usage {
+    /// Keyboard page usage, a key of `Op::Key`
+    Key(i8),
+    /// `MOD_*` bits, the first byte of `Op::Key`
+    Modifier(i8),
+    /// Consumer page usage, for `Op::media_usage`
+    Consumer(u16),
+}
+
+// Usage of the `MEDIA_*` constants below 0x100, stored as `i8`
+const fn consumer(usage: i8) -> Usage {
+    Usage::Consumer(usage as u8 as u16)
+}
+
+use KeyGroup::*;
+use Usage::*;
+
+/// Named codes, in picker order. Key names match `SHORTCUT_KEYS`.
+pub static KEYCODES: &[(KeyGroup, &str, Usage)] = &[
+    (Letters, "A", Key(KEY_A)),
+    (Letters, "B", Key(KEY_B)),
+    (Letters, "C", Key(KEY_C)),
+    (Letters, "D", Key(KEY_D)),
+    (Letters, "E", Key(KEY_E)),
+    (Letters, "F", Key(KEY_F)),
+    (Letters, "G", Key(KEY_G)),
+    (Letters, "H", Key(KEY_H)),
+    (Letters, "I", Key(KEY_I)),
+    (Letters, "J", Key(KEY_J)),
+    (Letters, "K", Key(KEY_K)),
+    (Letters, "L", Key(KEY_L)),
+    (Letters, "M", Key(KEY_M)),
+    (Letters, "N", Key(KEY_N)),
+    (Letters, "O", Key(KEY_O)),
+    (Letters, "P", Key(KEY_P)),
+    (Letters, "Q", Key(KEY_Q)),
+    (Letters, "R", Key(KEY_R)),
+    (Letters, "S", Key(KEY_S)),
+    (Letters, "T", Key(KEY_T)),
+    (Letters, "U", Key(KEY_U)),
+    (Letters, "V", Key(KEY_V)),
+    (Letters, "W", Key(KEY_W)),
+    (Letters, "X", Key(KEY_X)),
+    (Letters, "Y", Key(KEY_Y)),
+    (Letters, "Z", Key(KEY_Z)),
+    (Digits, "1", Key(KEY_1)),
+    (Digits, "2", Key(KEY_2)),
+    (Digits, "3", Key(KEY_3)),
+    (Digits, "4", Key(KEY_4)),
+    (Digits, "5", Key(KEY_5)),
+    (Digits, "6", Key(KEY_6)),
+    (Digits, "7", Key(KEY_7)),
+    (Digits, "8", Key(KEY_8)),
+    (Digits, "9", Key(KEY_9)),
+    (Digits, "0", Key(KEY_0)),
+    (Function, "F1", Key(KEY_F1)),
+    (Function, "F2", Key(KEY_F2)),
+    (Function, "F3", Key(KEY_F3)),
+    (Function, "F4", Key(KEY_F4)),
+    (Function, "F5", Key(KEY_F5)),
+    (Function, "F6", Key(KEY_F6)),
+    (Function, "F7", Key(KEY_F7)),
+    (Function, "F8", Key(KEY_F8)),
+    (Function, "F9", Key(KEY_F9)),
+    (Function, "F10", Key(KEY_F10)),
+    (Function, "F11", Key(KEY_F11)),
+    (Function, "F12", Key(KEY_F12)),
+    (Editing, "Enter", Key(KEY_Enter)),
+    (Editing, "Escape", Key(KEY_Esc)),
+    (Editing, "Backspace", Key(KEY_Backspace)),
+    (Editing, "Tab", Key(KEY_Tab)),
+    (Editing, "Space", Key(KEY_Space)),
+    (Editing, "Delete", Key(KEY_Delete)),
+    (Editing, "Print", Key(KEY_SysRq)),
+    (Symbols, "-", Key(KEY_Minus)),
+    (Symbols, "=", Key(KEY_Equal)),
+    (Symbols, "[", Key(KEY_LeftBrace)),
+    (Symbols, "]", Key(KEY_RightBrace)),
+    (Symbols, "`", Key(KEY_Grave)),
+    (Symbols, "/", Key(KEY_Slash)),
+    (Navigation, "Home", Key(KEY_Home)),
+    (Navigation, "End", Key(KEY_End)),
+    (Navigation, "Page Up", Key(KEY_PageUp)),
+    (Navigation, "Page Down", Key(KEY_PageDown)),
+    (Navigation, "Left", Key(KEY_Left)),
+    (Navigation, "Right", Key(KEY_Right)),
+    (Navigation, "Up", Key(KEY_Up)),
+    (Navigation, "Down", Key(KEY_Down)),
+    (Modifiers, "Ctrl", Modifier(MOD_Ctrl)),
+    (Modifiers, "Shift", Modifier(MOD_Shift)),
+    (Modifiers, "Alt", Modifier(MOD_Alt)),
+    (Modifiers, "Super", Modifier(MOD_Super)),
+    (Modifiers, "Right Ctrl", Modifier(MOD_RightCtrl)),
+    (Modifiers, "Right Shift", Modifier(MOD_RightShift)),
+    (Modifiers, "Right Alt", Modifier(MOD_RightAlt)),
+    (Modifiers, "Right Super", Modifier(MOD_RightSuper)),
+    (Media, "Mute Key", Key(KEY_Mute)),
+    (Media, "Volume Up Key", Key(KEY_VolumeUp)),
+    (Media, "Volume Down Key", Key(KEY_VolumeDown)),
+    (Media, "Play", consumer(MEDIA_Play)),
+    (Media, "Pause", consumer(MEDIA_Pause)),
+    (Media, "Play/Pause", consumer(MEDIA_PlayPause)),
+    (Media, "Record", consumer(MEDIA_Record)),
+    (Media, "Fast Forward", consumer(MEDIA_FastForward)),
+    (Media, "Rewind", consumer(MEDIA_Rewind)),
+    (Media, "Next Track", consumer(MEDIA_NextSong)),
+    (Media, "Previous Track", consumer(MEDIA_PreviousSong)),
+    (Media, "Mute", consumer(MEDIA_Mute)),
+    (Media, "Volume Up", consumer(MEDIA_VolumeUp)),
+    (Media, "Volume Down", consumer(MEDIA_VolumeDown)),
+    (Media, "Brightness Up", consumer(MEDIA_BrightnessUp)),
+    (Media, "Brightness Down", consumer(MEDIA_BrightnessDown)),
+    (Media, "Restart", consumer(MEDIA_Restart)),
+    (Media, "Mail", Consumer(MEDIA_Mail)),
+    (Media, "Calculator", Consumer(MEDIA_Calculator)),
+    (Media, "Files", Consumer(MEDIA_Files)),
+    (Media, "Browser", Consumer(MEDIA_Browser)),
+    (Media, "Home Page", Consumer(MEDIA_Home)),
+    (Media, "Back", Consumer(MEDIA_Back)),
+    (Media, "Forward", Consumer(MEDIA_Forward)),
+];
+
+/// Name of a keyboard page key in `KEYCODES`
+pub fn key_name(key: i8) -> Option<&'static str> {
+    KEYCODES
+        .iter()
+        .find(|(_, _, usage)| *usage == Key(key))
+        .map(|(_, name, _)| *name)
+}
+
+/// Entries of `KEYCODES` with `query` in their name, ignoring case
+pub fn search_keycodes(
+    query: &str,
+) -> impl Iterator<Item = &'static (KeyGroup, &'static str, Usage)> {
+    let query = query.to_lowercase();
+    KEYCODES
+        .iter()
+        .filter(move |(_, name, _)| name.to_lowercase().contains(&query))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_super_alias() {
+        assert_eq!(super_alias("", "GNOME"), None);
+        assert_eq!(super_alias("Meta", "GNOME"), Some(MOD_Alt));
+        assert_eq!(super_alias("ctrl+alt", ""), Some(MOD_Ctrl | MOD_Alt));
+        let spec = "KDE=RightSuper, Super";
+        assert_eq!(super_alias(spec, "ubuntu:GNOME"), Some(MOD_Super));
+        assert_eq!(super_alias(spec, "KDE"), Some(MOD_RightSuper));
+        assert_eq!(super_alias("Hyper", ""), None);
+    }
+
+    #[test]
+    fn test_replace_mods() {
+        let mods = MOD_Ctrl | MOD_Super;
+        assert_eq!(replace_mods(mods, MOD_Super, MOD_Alt), MOD_Ctrl | MOD_Alt);
+        assert_eq!(replace_mods(mods, MOD_Alt, MOD_Super), mods);
+        let alias = MOD_Ctrl | MOD_Alt;
+        assert_eq!(replace_mods(MOD_Super, MOD_Super, alias), alias);
+        assert_eq!(replace_mods(alias, alias, MOD_Super), MOD_Super);
+        assert_eq!(replace_mods(MOD_Ctrl, alias, MOD_Super), MOD_Ctrl);
+    }
+
+    #[test]
+    fn test_keycodes() {
+        for (i, (_, name, _)) in KEYCODES.iter().enumerate() {
+            assert!(!KEYCODES[..i].iter().any(|(_, x, _)| x == name), "{}", name);
+        }
+        assert_eq!(key_name(KEY_PageUp), Some("Page Up"));
+        assert_eq!(key_name(KEY_LeftCtrl), None);
+        let found: Vec<_> = search_keycodes("page").map(|(_, name, _)| *name).collect();
+        assert_eq!(found, ["Page Up", "Page Down", "Home Page"]);
+        assert!(search_keycodes("volume").all(|(group, _, _)| *group == Media));
+    }
+}
@@ -50,12 +50,25 @@ impl<'a> BitStream<'a> {
     }
 }
 
+/// Serialized as a number for a constant, or `{"var": 3}` for a variable
 #[derive(PartialEq, Eq, Clone, Copy, Hash)]
 pub enum Value<T> {
+    /// Firmware variable 0-15, read when the action runs instead of being
+    /// fixed when it is programmed
     Var(u8),
     Const(T),
 }
 
+impl<T> Value<T> {
+    pub const MAX_VAR: u8 = 0xF;
+
+    /// Panics if `var` is more than `MAX_VAR`, since it is encoded as a nibble
+    pub fn var(var: u8) -> Self {
+        assert!(var <= Self::MAX_VAR, "Value::var: invalid variable {}", var);
+        Self::Var(var)
+    }
+}
+
 impl<T> From<T> for Value<T> {
     fn from(val: T) -> Self {
         Self::Const(val)
@@ -77,16 +90,32 @@ impl Default for Value<i16> {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+/// Serialized with a `type` field, in a form meant to be edited by hand:
+///
+/// ```json
+/// [
+///   {"type": "key", "auto_release": true, "values": [1, 6]},
+///   {"type": "pause", "ms": 50},
+///   {"type": "mouse", "auto_release": true, "buttons": 1}
+/// ]
+/// ```
+///
+/// Key and media values are bytes, as in `encode_action`. Mouse fields other
+/// than `buttons` default to 0.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Op {
     Kill,
+    /// Delay in milliseconds
     Pause(Value<i16>),
+    /// Fields in the order of a HID mouse report, and of the payload
     Mouse {
         auto_release: bool,
         buttons: Value<i16>,
         dx: Value<i16>,
         dy: Value<i16>,
+        /// Vertical wheel, positive scrolling up
         wheel1: Value<i16>,
+        /// Horizontal wheel or tilt (AC Pan), positive scrolling right
         wheel2: Value<i16>,
     },
     Key {
@@ -99,6 +128,33 @@ pub enum Op {
     },
 }
 
+/// Button of a mouse op, as its bit in the `buttons` of `Op::Mouse`. Bits
+/// are in the order of a HID mouse report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left = 1 << 0,
+    Right = 1 << 1,
+    Middle = 1 << 2,
+    Back = 1 << 3,
+    Forward = 1 << 4,
+}
+
+impl MouseButton {
+    const ALL: [Self; 5] = [
+        Self::Left,
+        Self::Right,
+        Self::Middle,
+        Self::Back,
+        Self::Forward,
+    ];
+
+    /// Button with the single bit set in `buttons`. `None` if no bit, more
+    /// than one, or an unknown one is set.
+    pub fn from_bits(buttons: i16) -> Option<Self> {
+        Self::ALL.into_iter().find(|x| *x as i16 == buttons)
+    }
+}
+
 impl Op {
     pub fn pause(value: impl Into<Value<i16>>) -> Self {
         Self::Pause(value.into())
@@ -122,6 +178,33 @@ impl Op {
         }
     }
 
+    /// Press and release `button`
+    pub fn mouse_click(button: MouseButton) -> Self {
+        Self::mouse(true, button as i16, 0, 0, 0, 0)
+    }
+
+    /// Scroll `horizontal` notches right and `vertical` notches up; negative
+    /// values scroll left and down. Horizontal scrolling is AC Pan, which
+    /// the tilt wheel sends.
+    pub fn scroll(horizontal: i16, vertical: i16) -> Self {
+        Self::mouse(false, 0, 0, 0, vertical, horizontal)
+    }
+
+    /// Button this op clicks, if it is a `mouse_click`
+    pub fn clicked_button(&self) -> Option<MouseButton> {
+        match self {
+            Self::Mouse {
+                auto_release: true,
+                buttons: Value::Const(buttons),
+                dx: Value::Const(0),
+                dy: Value::Const(0),
+                wheel1: Value::Const(0),
+                wheel2: Value::Const(0),
+            } => MouseButton::from_bits(*buttons),
+            _ => None,
+        }
+    }
+
     pub fn key(auto_release: bool, payload: Vec<Value<i8>>) -> Self {
         Self::Key {
             auto_release,
@@ -135,6 +218,166 @@ impl Op {
             payload: payload,
         }
     }
+
+    /// Media op for a consumer page usage, which may not fit in one byte.
+    /// The usage is sent little endian, with the high byte omitted if zero.
+    pub fn media_usage(auto_release: bool, usage: u16) -> Self {
+        let [low, high] = usage.to_le_bytes();
+        let mut payload = vec![Value::Const(low as i8)];
+        if high != 0 {
+            payload.push(Value::Const(high as i8));
+        }
+        Self::media(auto_release, payload)
+    }
+}
+
+// Representations for serde
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum ValueRepr<T> {
+    Const(T),
+    Var { var: u8 },
+}
+
+impl<T: serde::Serialize + Copy> serde::Serialize for Value<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Self::Var(var) => ValueRepr::Var { var },
+            Self::Const(val) => ValueRepr::Const(val),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for Value<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match ValueRepr::deserialize(deserializer)? {
+            ValueRepr::Const(val) => Ok(Self::Const(val)),
+            ValueRepr::Var { var } => {
+                if var > Self::MAX_VAR {
+                    return Err(serde::de::Error::custom(format!(
+                        "invalid variable {}",
+                        var
+                    )));
+                }
+                Ok(Self::Var(var))
+            }
+        }
+    }
+}
+
+fn payload_bytes(payload: &[Value<i8>]) -> Vec<Value<u8>> {
+    payload
+        .iter()
+        .map(|value| match *value {
+            Value::Var(var) => Value::Var(var),
+            Value::Const(val) => Value::Const(val as u8),
+        })
+        .collect()
+}
+
+fn payload_from_bytes(values: Vec<Value<u8>>) -> Vec<Value<i8>> {
+    values
+        .into_iter()
+        .map(|value| match value {
+            Value::Var(var) => Value::Var(var),
+            Value::Const(val) => Value::Const(val as i8),
+        })
+        .collect()
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum OpRepr {
+    Kill,
+    Pause {
+        ms: Value<i16>,
+    },
+    Mouse {
+        auto_release: bool,
+        buttons: Value<i16>,
+        #[serde(default)]
+        dx: Value<i16>,
+        #[serde(default)]
+        dy: Value<i16>,
+        #[serde(default)]
+        wheel1: Value<i16>,
+        #[serde(default)]
+        wheel2: Value<i16>,
+    },
+    Key {
+        auto_release: bool,
+        values: Vec<Value<u8>>,
+    },
+    Media {
+        auto_release: bool,
+        values: Vec<Value<u8>>,
+    },
+}
+
+impl serde::Serialize for Op {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Kill => OpRepr::Kill,
+            Self::Pause(ms) => OpRepr::Pause { ms: *ms },
+            Self::Mouse {
+                auto_release,
+                buttons,
+                dx,
+                dy,
+                wheel1,
+                wheel2,
+            } => OpRepr::Mouse {
+                auto_release: *auto_release,
+                buttons: *buttons,
+                dx: *dx,
+                dy: *dy,
+                wheel1: *wheel1,
+                wheel2: *wheel2,
+            },
+            Self::Key {
+                auto_release,
+                payload,
+            } => OpRepr::Key {
+                auto_release: *auto_release,
+                values: payload_bytes(payload),
+            },
+            Self::Media {
+                auto_release,
+                payload,
+            } => OpRepr::Media {
+                auto_release: *auto_release,
+                values: payload_bytes(payload),
+            },
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Op {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match OpRepr::deserialize(deserializer)? {
+            OpRepr::Kill => Self::Kill,
+            OpRepr::Pause { ms } => Self::Pause(ms),
+            OpRepr::Mouse {
+                auto_release,
+                buttons,
+                dx,
+                dy,
+                wheel1,
+                wheel2,
+            } => Self::mouse(auto_release, buttons, dx, dy, wheel1, wheel2),
+            OpRepr::Key {
+                auto_release,
+                values,
+            } => Self::key(auto_release, payload_from_bytes(values)),
+            OpRepr::Media {
+                auto_release,
+                values,
+            } => Self::media(auto_release, payload_from_bytes(values)),
+        })
+    }
 }
 
 fn get_payload(bitstream: &mut BitStream) -> Result<Vec<Value<i8>>, &'static str> {
@@ -192,15 +435,19 @@ fn get_payload2(bitstream: &mut BitStream) -> Result<Vec<Value<i16>>, &'static s
     Ok(values)
 }
 
-// XXX signed
-fn get_value2(bitstream: &mut BitStream, _signed: bool) -> Result<Value<i16>, &'static str> {
+// Single byte values are sign extended only if `signed`
+fn get_value2(bitstream: &mut BitStream, signed: bool) -> Result<Value<i16>, &'static str> {
     if !bitstream.bit().ok_or("Failed to read value bit")? {
         Ok(Value::Var(
             bitstream.bits(4).ok_or("Failed to read value nibble")?,
         ))
     } else if !bitstream.bit().ok_or("Failed to read value bit")? {
         let byte = bitstream.bits(8).ok_or("Failed to read value byte")?;
-        Ok(Value::Const(i16::from(byte as i8)))
+        if signed {
+            Ok(Value::Const(i16::from(byte as i8)))
+        } else {
+            Ok(Value::Const(i16::from(byte)))
+        }
     } else {
         Ok(Value::Const(i16::from_le_bytes([
             bitstream.bits(8).ok_or("Failed to read value byte")?,
@@ -266,16 +513,21 @@ fn push_payload2(bitvec: &mut BitVec<u8, Lsb0>, payload: &[Value<i16>]) {
     push_bits(bitvec, 0b00, 2);
 }
 
-fn push_value2(bitvec: &mut BitVec<u8, Lsb0>, value: &Value<i16>) {
+fn push_value2(bitvec: &mut BitVec<u8, Lsb0>, value: &Value<i16>, signed: bool) {
     bitvec.push(matches!(value, Value::Const(_)));
     match value {
         Value::Var(var) => push_bits(bitvec, *var, 4),
         Value::Const(val) => {
             let bytes = val.to_le_bytes();
-            bitvec.push(bytes[1] != 0);
+            let single_byte = if signed {
+                i8::try_from(*val).is_ok()
+            } else {
+                u8::try_from(*val).is_ok()
+            };
+            bitvec.push(!single_byte);
 
             push_bits(bitvec, bytes[0], 8);
-            if bytes[1] != 0 {
+            if !single_byte {
                 push_bits(bitvec, bytes[1], 8);
             }
         }
@@ -291,7 +543,7 @@ pub fn encode_action(ops: &[Op]) -> Vec<u8> {
             }
             Op::Pause(value) => {
                 push_bits(&mut bitvec, 21, 5);
-                push_value2(&mut bitvec, value);
+                push_value2(&mut bitvec, value, false);
             }
             Op::Mouse {
                 auto_release,
@@ -384,7 +636,10 @@ pub fn decode_action(action: &[u8]) -> Result<Vec<Op>, String> {
     Ok(ops)
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
 #[repr(u8)]
 pub enum PressType {
     Normal = 0,
@@ -395,6 +650,9 @@ pub enum PressType {
 }
 
 impl PressType {
+    // Number of press types
+    pub(crate) const COUNT: usize = 5;
+
     fn from_u8(value: u8) -> Option<Self> {
         match value {
             0 => Some(Self::Normal),
@@ -407,7 +665,8 @@ impl PressType {
     }
 }
 
-#[derive(Debug)]
+// `action` is serialized encoded, as the device stores it
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Button {
     pub id: u8,
     pub host_id: u8,
@@ -426,17 +685,18 @@ impl Button {
     }
 
     pub fn decode(data: &[u8]) -> Option<(Self, usize)> {
-        if data.len() <= 3 {
+        let (id, host_id, press_type, size, rest) = match data {
+            [id, host_id, press_type, size, rest @ ..] => {
+                (*id, *host_id, *press_type, *size as usize, rest)
+            }
             // Buffer too small
-            return None;
-        }
-
-        let size = data[3] as usize;
+            _ => return None,
+        };
         let button = Self {
-            id: data[0],
-            host_id: data[1],
-            press_type: PressType::from_u8(data[2])?,
-            action: data.get(4..4 + size)?.to_vec(),
+            id,
+            host_id,
+            press_type: PressType::from_u8(press_type)?,
+            action: rest.get(..size)?.to_vec(),
         };
         Some((button, 4 + size))
     }
@@ -452,6 +712,25 @@ impl Button {
     pub fn decode_action(&self) -> Result<Vec<Op>, String> {
         decode_action(&self.action)
     }
+
+    /// Whether buttons reported in `Event::Buttons` for `host_id` show this
+    /// button as programmed. Buttons with the default (empty) action aren't
+    /// reported.
+    pub fn applied_in(&self, host_id: u8, reported: &[Button]) -> bool {
+        let host_id = if self.host_id == 0 {
+            // Current host
+            host_id
+        } else {
+            self.host_id
+        };
+        match reported
+            .iter()
+            .find(|x| x.id == self.id && x.host_id == host_id && x.press_type == self.press_type)
+        {
+            Some(button) => button.action == self.action,
+            None => self.action.is_empty(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -478,21 +757,103 @@ mod tests {
         ]
     }
 
+    #[test]
+    fn test_decode_truncated() {
+        let button = Button::new(1, 0, PressType::Normal, &[Op::Kill]);
+        let mut data = Vec::new();
+        button.encode(&mut data);
+        for len in 0..data.len() {
+            assert!(Button::decode(&data[..len]).is_none(), "length {}", len);
+        }
+        let (decoded, count) = Button::decode(&data).unwrap();
+        assert_eq!(decoded.action, button.action);
+        assert_eq!(count, data.len());
+    }
+
+    #[test]
+    fn test_mouse_builders() {
+        assert_eq!(
+            Op::mouse_click(MouseButton::Right),
+            Op::mouse(true, 2, 0, 0, 0, 0)
+        );
+        assert_eq!(Op::scroll(0, 1), Op::mouse(false, 0, 0, 0, 1, 0));
+        assert_eq!(Op::scroll(-1, 0), Op::mouse(false, 0, 0, 0, 0, -1));
+        for button in MouseButton::ALL {
+            assert_eq!(Op::mouse_click(button).clicked_button(), Some(button));
+        }
+
+        // Exactly one known button
+        assert_eq!(MouseButton::from_bits(0), None);
+        assert_eq!(MouseButton::from_bits(1 | 2), None);
+        assert_eq!(MouseButton::from_bits(1 << 5), None);
+        assert_eq!(Op::mouse(true, 3, 0, 0, 0, 0).clicked_button(), None);
+        assert_eq!(Op::mouse(false, 1, 0, 0, 0, 0).clicked_button(), None);
+        assert_eq!(Op::mouse(true, 1, 0, 0, 1, 0).clicked_button(), None);
+        assert_eq!(Op::scroll(0, 1).clicked_button(), None);
+    }
+
     #[test]
     fn test_value2() {
         let mut bitvec = BitVec::<u8, Lsb0>::new();
-        push_value2(&mut bitvec, &Const(100));
+        push_value2(&mut bitvec, &Const(100), true);
         let mut bitstream = BitStream::for_bitslice(&bitvec);
         assert_eq!(get_value2(&mut bitstream, true).unwrap(), Const(100));
         assert_eq!(bitstream.len(), 0);
     }
 
+    #[test]
+    fn test_var() {
+        let ops = vec![
+            Op::key(true, vec![Const(1), Value::var(2), Const(3)]),
+            Op::pause(Value::var(0xF)),
+            Op::mouse(false, 0, Value::var(4), Value::var(5), 0, 0),
+            Op::media(true, vec![Value::var(6)]),
+        ];
+        assert_eq!(decode_action(&encode_action(&ops)).unwrap(), ops);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_var_range() {
+        Value::<i8>::var(16);
+    }
+
+    #[test]
+    fn test_kill() {
+        assert_eq!(encode_action(&[Kill]), &[0]);
+        assert_eq!(decode_action(&[0]).unwrap(), vec![Kill]);
+        // Ends the action; anything after it is ignored
+        assert_eq!(decode_action(&[0, 0xFF]).unwrap(), vec![Kill]);
+        // Kill after other operations is only a terminator
+        let pause = vec![Pause(Const(100)), Kill];
+        assert_eq!(decode_action(&encode_action(&pause)).unwrap(), &pause[..1]);
+    }
+
     #[test]
     fn test_pause() {
         let pause = vec![Pause(Const(100))];
         assert_eq!(decode_action(&encode_action(&pause)).unwrap(), pause);
     }
 
+    #[test]
+    fn test_value2_single_byte() {
+        for (value, signed) in [(-100, true), (200, false)] {
+            let mut bitvec = BitVec::<u8, Lsb0>::new();
+            push_value2(&mut bitvec, &Const(value), signed);
+            assert_eq!(bitvec.len(), 10);
+            let mut bitstream = BitStream::for_bitslice(&bitvec);
+            assert_eq!(get_value2(&mut bitstream, signed).unwrap(), Const(value));
+        }
+    }
+
+    #[test]
+    fn test_delay() {
+        for ms in [50, 200, 1000, 5000] {
+            let delay = vec![Op::pause(ms)];
+            assert_eq!(decode_action(&encode_action(&delay)).unwrap(), delay);
+        }
+    }
+
     #[test]
     fn test_zoom_in() {
         let zoom_in = zoom_in();
@@ -519,6 +880,60 @@ mod tests {
         assert_eq!(decode_action(bytes).unwrap(), zoom_out);
     }
 
+    #[test]
+    fn test_tilt() {
+        // Vertical scroll is the fourth value, as in the zoom captures, and
+        // tilt the fifth. Zero values at the end are left out.
+        let scroll_up = vec![Op::mouse(false, 0, 0, 0, 1, 0)];
+        assert_eq!(encode_action(&scroll_up), &[151, 0, 2, 8, 96, 0]);
+        let tilt_right = vec![Op::mouse(false, 0, 0, 0, 0, 1)];
+        assert_eq!(encode_action(&tilt_right), &[151, 0, 2, 8, 32, 128, 1, 0]);
+        let tilt_left = vec![Op::mouse(false, 0, 0, 0, 0, -1)];
+        let bytes = &[151, 0, 2, 8, 32, 192, 255, 255, 0];
+        assert_eq!(encode_action(&tilt_left), bytes);
+        assert_eq!(decode_action(bytes).unwrap(), tilt_left);
+    }
+
+    #[test]
+    fn test_sequence() {
+        let sequence = vec![
+            Op::key(true, vec![Const(1), Const(0x06)]),
+            Op::pause(50),
+            Op::key(true, vec![Const(1), Const(0x19)]),
+            Op::mouse(true, 1, 0, 0, 0, 0),
+        ];
+        assert_eq!(decode_action(&encode_action(&sequence)).unwrap(), sequence);
+    }
+
+    #[test]
+    fn test_media_scan() {
+        // Scan next/previous track; above `i8::MAX`
+        for usage in [0xB5, 0xB6] {
+            let scan = vec![Op::media_usage(true, usage)];
+            assert_eq!(scan, vec![Op::media(true, vec![Const(usage as i8)])]);
+            assert_eq!(decode_action(&encode_action(&scan)).unwrap(), scan);
+        }
+    }
+
+    #[test]
+    fn test_media_usage_16_bit() {
+        // AL Calculator, AC Home, AC Pan
+        for usage in [0x192, 0x223, 0x238] {
+            let media = vec![Op::media_usage(true, usage)];
+            assert_eq!(
+                media,
+                vec![Op::media(
+                    true,
+                    vec![Const(usage as u8 as i8), Const((usage >> 8) as i8)]
+                )]
+            );
+            let action = encode_action(&media);
+            // Opcode, auto release, two byte payload, both bytes, end
+            assert_eq!(action.len(), (5 + 1 + 2 + 16 + 2usize).div_ceil(8));
+            assert_eq!(decode_action(&action).unwrap(), media);
+        }
+    }
+
     #[test]
     fn test_media_play_pause() {
         #[allow(overflowing_literals)]
@@ -528,4 +943,65 @@ mod tests {
             play_pause
         );
     }
+
+    #[test]
+    fn test_serde() {
+        // Ctrl+Z, then a left click
+        let json = r#"[
+            {"type": "key", "auto_release": true, "values": [1, 29]},
+            {"type": "pause", "ms": 50},
+            {"type": "mouse", "auto_release": true, "buttons": 1}
+        ]"#;
+        let ops: Vec<Op> = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            ops,
+            vec![
+                Op::key(true, vec![Const(1), Const(29)]),
+                Op::pause(50),
+                Op::mouse(true, 1, 0, 0, 0, 0),
+            ]
+        );
+        let bytes = &[248, 1, 29, 212, 100, 110, 3, 0];
+        assert_eq!(encode_action(&ops), bytes);
+        assert_eq!(decode_action(bytes).unwrap(), ops);
+        let json = serde_json::to_string(&ops).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<Op>>(&json).unwrap(), ops);
+
+        // Bytes above `i8::MAX`, and variables
+        let json = r#"{"type":"media","auto_release":false,"values":[205,{"var":2}]}"#;
+        #[allow(overflowing_literals)]
+        let media = Op::media(false, vec![Const(0xCD), Value::var(2)]);
+        assert_eq!(serde_json::from_str::<Op>(json).unwrap(), media);
+        assert_eq!(serde_json::to_string(&media).unwrap(), json);
+        assert!(serde_json::from_str::<Op>(r#"{"type":"pause","ms":{"var":16}}"#).is_err());
+    }
+
+    #[test]
+    fn test_decode_unknown_ops() {
+        // Opcode in the low 5 bits of the first byte
+        for op in 0..32u8 {
+            let res = decode_action(&[op, 0xff, 0xff, 0xff]);
+            if ![0, 21, 23, 24, 27].contains(&op) {
+                assert_eq!(res, Err(format!("Unsupported OP {}", op)));
+            }
+        }
+        let button = Button::decode(&[1, 0, 0, 3, 31, 0, 0]).unwrap().0;
+        assert!(button.decode_action().is_err());
+    }
+
+    #[test]
+    fn test_decode_random_actions() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        // Arbitrary bytes decode or fail, without panicking or looping
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..10_000 {
+            let len = rng.gen_range(0..=crate::MAX_ACTION_LEN);
+            let action: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+            if let Ok(ops) = decode_action(&action) {
+                // Anything decoded can be encoded again
+                encode_action(&ops);
+            }
+        }
+    }
 }
@@ -0,0 +1,129 @@
+//! Applies the configuration saved by `mouse-configurator` whenever a device
+//! is connected, so settings are restored without the GUI running. Settings
+//! are not saved to flash if the device supports that, since they are
+//! reapplied on every connection. Intended to be run as a systemd user
+//! service.
+//!
+//! Opens devices directly, so requires access to the hidraw device, such as
+//! from the `uaccess` udev rule.
+//!
+//! Command bindings are programmed as their key combination only. Running
+//! the command is left to the GNOME shortcut the app registered, so they do
+//! nothing outside GNOME.
+
+use std::{env, io, thread};
+
+use mouse_configurator::app::{
+    bindings::{HardwareButton, PresetBinding},
+    config::{binding_buttons, load_config, MouseConfig},
+    i18n,
+};
+use mouse_configurator::{DeviceInfo, Event, HpMouse};
+
+fn apply_config(mouse: &HpMouse, config: &MouseConfig) -> io::Result<()> {
+    let no_save_to_flash = mouse.support_no_save_to_flash() == Some(true);
+    let profile = config.profile();
+    mouse.set_dpi(0, config.dpi as u16, no_save_to_flash)?;
+    mouse.set_left_handed(profile.left_handed, no_save_to_flash)?;
+    if !profile.dpi_stages.is_empty() && mouse.profile().dpi_stages.is_some() {
+        // Such as with firmware too old for stages
+        match mouse.set_dpi_stages(&profile.dpi_stages, no_save_to_flash) {
+            Err(err) if err.kind() == io::ErrorKind::Unsupported => {
+                eprintln!("Not applying DPI stages: {}", err);
+            }
+            res => res?,
+        }
+    }
+    let buttons: Vec<_> = HardwareButton::iter()
+        .filter_map(|i| binding_buttons(i, profile.binding(i).as_ref(), false))
+        .flatten()
+        .collect();
+    mouse.set_buttons(&buttons, no_save_to_flash)?;
+    if let Some(cut_off) = config.cut_off {
+        mouse.set_cut_off(cut_off, no_save_to_flash)?;
+    }
+    Ok(())
+}
+
+fn handle_device(device_info: DeviceInfo) {
+    let mouse = match device_info.open() {
+        Ok(mouse) => mouse,
+        Err(err) => {
+            eprintln!(
+                "Failed to open `{}`: {}",
+                device_info.devnode.display(),
+                err
+            );
+            return;
+        }
+    };
+    let events = mouse.read();
+    if let Err(err) = mouse.query_firmware() {
+        eprintln!("Failed to query firmware: {}", err);
+        return;
+    }
+
+    // Reapply if the device reports firmware again, such as after a reset
+    let mut serial = None;
+    for event in events {
+        match event {
+            Ok(Event::Firmware { serial: x, .. }) => {
+                // Query support for not saving to flash before applying
+                serial = Some(x);
+                if let Err(err) = mouse.query_dpi() {
+                    eprintln!("Failed to query DPI: {}", err);
+                }
+            }
+            Ok(Event::Mouse { .. }) => {
+                let serial = match serial.take() {
+                    Some(serial) => serial,
+                    None => continue,
+                };
+                // Load each time, to pick up changes saved by the GUI
+                match load_config().get(&serial) {
+                    Some(config) => {
+                        eprintln!("Applying configuration to {}", serial);
+                        if let Err(err) = apply_config(&mouse, config) {
+                            eprintln!("Failed to apply configuration: {}", err);
+                        }
+                    }
+                    None => eprintln!("No configuration for {}", serial),
+                }
+            }
+            Ok(_) => {}
+            Err(err) if err.is_gone() => {
+                eprintln!("Device gone: {}", err);
+                break;
+            }
+            Err(err) => eprintln!("Error reading event: {}", err),
+        }
+    }
+}
+
+// Names used for presets in the config file
+fn list_presets() {
+    for preset in PresetBinding::iter() {
+        let name = serde_json::to_string(&preset).unwrap();
+        println!("{}\t{}", name.trim_matches('"'), preset.entry().label());
+    }
+}
+
+fn main() {
+    // Warnings from the library and `app`, and more with `RUST_LOG`
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    i18n::init();
+
+    if env::args().nth(1).as_deref() == Some("--list-presets") {
+        list_presets();
+        return;
+    }
+
+    // Listen before enumerating, so no device is missed in between
+    let monitor_devices = mouse_configurator::monitor().expect("Failed to monitor devices");
+    let current_devices = mouse_configurator::enumerate().expect("Failed to enumerate devices");
+
+    // Bluetooth devices reconnecting after suspend are added again
+    for device_info in current_devices.into_iter().chain(monitor_devices) {
+        thread::spawn(move || handle_device(device_info));
+    }
+}
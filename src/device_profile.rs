@@ -0,0 +1,347 @@
+use crate::FirmwareVersion;
+
+/// On-device profile slots. No HP firmware is known to have these yet; this
+/// describes where a model reports and selects the active slot, so support
+/// only needs a profile entry once one does.
+///
+/// The packet of kind `packet_kind` is assumed to follow the layout of the
+/// other status packets: a command/status byte, then the active slot index.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ProfileSlots {
+    /// Packet kind reporting the active slot, also written to query or select it
+    pub packet_kind: u16,
+    /// Number of slots, with indices from 0
+    pub count: u8,
+    /// First firmware version with slots
+    pub min_firmware: FirmwareVersion,
+}
+
+/// Status LED with a settable color. No supported model is known to have one;
+/// like `ProfileSlots`, this describes where a model reports and sets it, so
+/// support only needs a profile entry.
+///
+/// The packet of kind `packet_kind` is assumed to have a command/status byte,
+/// then red, green, and blue bytes. If a model encodes the color differently,
+/// `HpMouseEvents::report_1_led` and `HpMouse::set_led` are where to handle
+/// that.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Led {
+    /// Packet kind reporting the color, also written to query or set it
+    pub packet_kind: u16,
+    /// First firmware version with the LED packet
+    pub min_firmware: FirmwareVersion,
+}
+
+/// DPI stages, cycled by a button on the mouse. No supported model is known
+/// to have them; like `ProfileSlots`, this describes how a model sets them,
+/// so support only needs a profile entry.
+///
+/// Stages are assumed to be set with `command` in the DPI packet (kind 17),
+/// followed by the number of stages and each stage as a little endian `u16`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DpiStages {
+    /// Command byte setting the stages
+    pub command: u8,
+    /// Most stages the firmware holds
+    pub max_count: u8,
+    /// First firmware version with stages
+    pub min_firmware: FirmwareVersion,
+}
+
+/// Firmware command restoring factory settings, including DPI, lift-off
+/// distance, and power settings along with the buttons. No supported model
+/// is known to have one; like `ProfileSlots`, this describes how a model
+/// would do it, so support only needs a profile entry.
+///
+/// The reset is assumed to be written as `command` in a packet of kind
+/// `packet_kind`, which the device answers with a status packet of the same
+/// kind once done.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FactoryReset {
+    /// Packet kind of the command and its status reply
+    pub packet_kind: u16,
+    /// Command byte starting the reset
+    pub command: u8,
+    /// First firmware version with the command
+    pub min_firmware: FirmwareVersion,
+}
+
+/// Protocol details that vary between mouse models
+#[derive(Debug, PartialEq, Eq)]
+pub struct DeviceProfile {
+    pub name: &'static str,
+    pub vendor_id: u16,
+    /// Product IDs, with the interface to open, if it has to be a specific
+    /// USB interface
+    pub product_ids: &'static [(u16, Option<u8>)],
+    /// Base of the signature in report 1 headers; the packet kind is added
+    pub signature: u16,
+    /// Packet kinds parsed into events. Others are `Event::Unknown`.
+    pub packet_kinds: &'static [u16],
+    /// Number of programmable buttons, with ids from 0
+    pub buttons: u8,
+    /// Number of hosts the mouse pairs with, with ids from 1. Commands take
+    /// host id 0 for the current host.
+    pub hosts: u8,
+    pub profile_slots: Option<ProfileSlots>,
+    pub led: Option<Led>,
+    pub dpi_stages: Option<DpiStages>,
+    pub factory_reset: Option<FactoryReset>,
+}
+
+pub const HP_930: DeviceProfile = DeviceProfile {
+    name: "HP 930 series Creator Wireless Mouse",
+    vendor_id: 0x03F0,
+    product_ids: &[
+        // Bluetooth
+        (0x524A, None),
+        // USB receiver
+        (0x544A, Some(1)),
+    ],
+    signature: 0xCF3,
+    packet_kinds: &[1, 6, 14, 18],
+    buttons: 7,
+    hosts: 3,
+    profile_slots: None,
+    led: None,
+    dpi_stages: None,
+    factory_reset: None,
+};
+
+/// Supported models
+pub const DEVICE_PROFILES: &[&DeviceProfile] = &[&HP_930];
+
+impl DeviceProfile {
+    /// Profile for a device with these IDs, if it is supported
+    pub fn for_device(
+        vendor_id: u16,
+        product_id: u16,
+        interface: Option<u8>,
+    ) -> Option<&'static Self> {
+        DEVICE_PROFILES.iter().copied().find(|profile| {
+            profile.vendor_id == vendor_id
+                && profile.product_ids.iter().any(|(id, required_interface)| {
+                    *id == product_id
+                        && (required_interface.is_none() || *required_interface == interface)
+                })
+        })
+    }
+
+    pub fn supports_packet(&self, kind: u16) -> bool {
+        self.packet_kinds.contains(&kind)
+            || self
+                .profile_slots
+                .as_ref()
+                .is_some_and(|slots| slots.packet_kind == kind)
+            || self.led.as_ref().is_some_and(|led| led.packet_kind == kind)
+            || self
+                .factory_reset
+                .as_ref()
+                .is_some_and(|reset| reset.packet_kind == kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::mouse_report, Event, HpMouse, MockHid};
+
+    #[test]
+    fn test_for_device() {
+        assert_eq!(
+            DeviceProfile::for_device(0x03F0, 0x524A, None),
+            Some(&HP_930)
+        );
+        assert_eq!(
+            DeviceProfile::for_device(0x03F0, 0x544A, Some(1)),
+            Some(&HP_930)
+        );
+        // Other interfaces of the receiver
+        assert_eq!(DeviceProfile::for_device(0x03F0, 0x544A, Some(0)), None);
+        assert_eq!(DeviceProfile::for_device(0x03F0, 0x544A, None), None);
+        assert_eq!(DeviceProfile::for_device(0x1234, 0x524A, None), None);
+    }
+
+    #[test]
+    fn test_profile_signature() {
+        static OTHER: DeviceProfile = DeviceProfile {
+            name: "Other",
+            vendor_id: 0x03F0,
+            product_ids: &[],
+            signature: 0xD00,
+            packet_kinds: &[6],
+            buttons: 3,
+            hosts: 3,
+            profile_slots: None,
+            led: None,
+            dpi_stages: None,
+            factory_reset: None,
+        };
+        // `MockHid::report_1` uses the HP 930 signature, offset by the
+        // difference so it's battery packet 6 for `OTHER`
+        let battery = MockHid::report_1(6 + 0xD00 - HP_930.signature, &[10, 5, 0, 6, 80]);
+        let firmware = MockHid::report_1(1 + 0xD00 - HP_930.signature, &[0; 4]);
+        let dev = MockHid::with_reports(vec![battery, firmware]);
+        let mut events = HpMouse::from_device_with_profile(dev, &OTHER)
+            .read()
+            .skip(1);
+        assert!(matches!(
+            events.next(),
+            Some(Ok(Event::Battery { level: 80, .. }))
+        ));
+        // Not in `packet_kinds`
+        assert!(matches!(
+            events.next(),
+            Some(Ok(Event::Unknown { kind: 1, .. }))
+        ));
+    }
+
+    #[test]
+    fn test_profile_slots() {
+        static SLOTS: DeviceProfile = DeviceProfile {
+            name: "Slots",
+            profile_slots: Some(ProfileSlots {
+                packet_kind: 21,
+                count: 3,
+                min_firmware: FirmwareVersion(2, 0, 0),
+            }),
+            ..HP_930
+        };
+        let dev = MockHid::with_reports(vec![
+            MockHid::report_1(21, &[0, 2]),
+            MockHid::report_1(21, &[0, 3]),
+        ]);
+        let mouse = HpMouse::from_device_with_profile(dev.clone(), &SLOTS);
+        let mut events = mouse.read().skip(1);
+        assert!(matches!(
+            events.next(),
+            Some(Ok(Event::ActiveProfile { index: 2 }))
+        ));
+        assert!(events.next().unwrap().is_err());
+
+        mouse.query_active_profile().unwrap();
+        mouse.set_active_profile(1).unwrap();
+        assert!(mouse.set_active_profile(3).is_err());
+        let writes = dev.writes();
+        assert_eq!(writes.len(), 2);
+        assert_eq!(&writes[0][3..7], &[2, 0, 0, 0]);
+        assert_eq!(&writes[1][3..7], &[2, 0, 1, 1]);
+
+        // Firmware older than `min_firmware`: 1.10.3
+        let firmware = MockHid::report_1(1, &[0x4F, 0x04, 0, 0, 0, 0]);
+        let dev = MockHid::with_reports(vec![firmware, MockHid::report_1(21, &[0, 2])]);
+        let mouse = HpMouse::from_device_with_profile(dev, &SLOTS);
+        let mut events = mouse.read().skip(2);
+        assert!(matches!(
+            events.next(),
+            Some(Ok(Event::Unknown { kind: 21, .. }))
+        ));
+        assert!(mouse.set_active_profile(0).is_err());
+
+        let mouse = HpMouse::from_device(MockHid::with_reports(Vec::new()));
+        assert_eq!(
+            mouse.set_active_profile(0).unwrap_err().kind(),
+            std::io::ErrorKind::Unsupported
+        );
+    }
+
+    #[test]
+    fn test_led() {
+        static LED: DeviceProfile = DeviceProfile {
+            name: "LED",
+            led: Some(Led {
+                packet_kind: 22,
+                min_firmware: FirmwareVersion(2, 0, 0),
+            }),
+            ..HP_930
+        };
+        let dev = MockHid::with_reports(vec![MockHid::report_1(22, &[0, 255, 128, 0])]);
+        let mouse = HpMouse::from_device_with_profile(dev.clone(), &LED);
+        assert!(matches!(
+            mouse.read().nth(1),
+            Some(Ok(Event::Led {
+                red: 255,
+                green: 128,
+                blue: 0
+            }))
+        ));
+
+        mouse.query_led().unwrap();
+        mouse.set_led(1, 2, 3, false).unwrap();
+        let writes = dev.writes();
+        assert_eq!(&writes[0][3..9], &[4, 0, 0, 0, 0, 0]);
+        assert_eq!(&writes[1][3..9], &[4, 0, 1, 1, 2, 3]);
+
+        let mouse = HpMouse::from_device(MockHid::with_reports(Vec::new()));
+        assert_eq!(
+            mouse.set_led(0, 0, 0, false).unwrap_err().kind(),
+            std::io::ErrorKind::Unsupported
+        );
+    }
+
+    #[test]
+    fn test_factory_reset() {
+        static RESET: DeviceProfile = DeviceProfile {
+            name: "Reset",
+            factory_reset: Some(FactoryReset {
+                packet_kind: 23,
+                command: 1,
+                min_firmware: FirmwareVersion(2, 0, 0),
+            }),
+            ..HP_930
+        };
+        let dev = MockHid::with_reports(vec![MockHid::report_1(23, &[0])]);
+        let mouse = HpMouse::from_device_with_profile(dev.clone(), &RESET);
+        mouse.factory_reset(&mut mouse.read()).unwrap();
+        let writes = dev.writes();
+        assert_eq!(&writes[0][3..6], &[1, 0, 1]);
+        // Then queries firmware, battery, DPI, and buttons
+        assert_eq!(writes.len(), 5);
+
+        let dev = MockHid::with_reports(vec![MockHid::report_1(23, &[3])]);
+        let mouse = HpMouse::from_device_with_profile(dev.clone(), &RESET);
+        assert!(mouse.factory_reset(&mut mouse.read()).is_err());
+        assert_eq!(dev.writes().len(), 1);
+
+        let mouse = HpMouse::from_device(MockHid::with_reports(Vec::new()));
+        assert_eq!(
+            mouse.send_factory_reset().unwrap_err().kind(),
+            std::io::ErrorKind::Unsupported
+        );
+    }
+
+    #[test]
+    fn test_dpi_stages() {
+        static STAGES: DeviceProfile = DeviceProfile {
+            name: "Stages",
+            dpi_stages: Some(DpiStages {
+                command: 8,
+                max_count: 3,
+                min_firmware: FirmwareVersion(2, 0, 0),
+            }),
+            ..HP_930
+        };
+        let dev = MockHid::with_reports(vec![mouse_report((800, 3000, 50), 0)]);
+        let mouse = HpMouse::from_device_with_profile(dev.clone(), &STAGES);
+        // Range unknown until the mouse packet is read
+        assert!(mouse.set_dpi_stages(&[800], false).is_err());
+        mouse.read().nth(1);
+
+        mouse.set_dpi_stages(&[800, 1600], false).unwrap();
+        let writes = dev.writes();
+        assert_eq!(writes.len(), 1);
+        assert_eq!(&writes[0][3..12], &[7, 0, 0, 8, 2, 0x20, 0x03, 0x40, 0x06]);
+
+        for stages in [&[800, 1600, 2400, 3000][..], &[], &[700], &[3050], &[825]] {
+            let err = mouse.set_dpi_stages(stages, false).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        }
+        assert_eq!(dev.writes().len(), 1);
+
+        let mouse = HpMouse::from_device(MockHid::with_reports(Vec::new()));
+        assert_eq!(
+            mouse.set_dpi_stages(&[800], false).unwrap_err().kind(),
+            std::io::ErrorKind::Unsupported
+        );
+    }
+}
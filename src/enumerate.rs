@@ -1,11 +1,7 @@
 use nix::poll::{poll, PollFd, PollFlags};
 use std::{io, os::unix::io::AsRawFd, path::PathBuf};
 
-use super::HpMouse;
-
-const HP_VENDOR_ID: u16 = 0x03F0;
-const BT_PRODUCT_ID: u16 = 0x524A;
-const USB_PRODUCT_ID: u16 = 0x544A;
+use super::{DeviceProfile, HpMouse};
 
 #[derive(Debug)]
 pub struct DeviceInfo {
@@ -13,14 +9,25 @@ pub struct DeviceInfo {
     pub product_id: u16,
     pub interface: Option<u8>,
     pub devnode: PathBuf,
+    pub profile: &'static DeviceProfile,
 }
 
 impl DeviceInfo {
     pub fn open(&self) -> io::Result<HpMouse> {
-        HpMouse::open_devnode(&self.devnode)
+        HpMouse::open_devnode_with_profile(&self.devnode, self.profile)
     }
 }
 
+/// Matching devices seen by `hidapi`, to open with `HidApiDevice::open`
+#[cfg(feature = "hidapi")]
+pub fn enumerate_hidapi(api: &hidapi::HidApi) -> impl Iterator<Item = &hidapi::DeviceInfo> {
+    api.device_list().filter(|info| {
+        // -1 if not USB
+        let interface = u8::try_from(info.interface_number()).ok();
+        DeviceProfile::for_device(info.vendor_id(), info.product_id(), interface).is_some()
+    })
+}
+
 fn parse_hid_id(id: &str) -> Option<(u16, u16)> {
     let mut iter = id.split(':');
     let _ = iter.next()?;
@@ -47,17 +54,14 @@ fn match_device(device: &udev::Device) -> Option<DeviceInfo> {
         .and_then(|x| parse_hid_id(x.to_str()?))?;
     let interface = get_interface_number(&device);
     let devnode = device.devnode()?;
-    match (vendor_id, product_id, interface) {
-        (HP_VENDOR_ID, USB_PRODUCT_ID, Some(1)) | (HP_VENDOR_ID, BT_PRODUCT_ID, _) => {
-            Some(DeviceInfo {
-                vendor_id,
-                product_id,
-                interface,
-                devnode: devnode.to_owned(),
-            })
-        }
-        _ => None,
-    }
+    let profile = DeviceProfile::for_device(vendor_id, product_id, interface)?;
+    Some(DeviceInfo {
+        vendor_id,
+        product_id,
+        interface,
+        devnode: devnode.to_owned(),
+        profile,
+    })
 }
 
 pub fn enumerate() -> io::Result<Vec<DeviceInfo>> {
@@ -1,14 +1,36 @@
 use bitvec::prelude::*;
+#[cfg(feature = "async")]
+use std::task::Poll;
 use std::{
-    io::ErrorKind,
+    collections::VecDeque,
+    fmt,
+    io::{self, ErrorKind},
     mem,
     num::NonZeroU8,
-    os::unix::io::{AsRawFd, RawFd},
+    os::unix::io::RawFd,
     str,
-    sync::Arc,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
 };
 
-use crate::{Button, Hid, HP_SIGNATURE};
+use crate::{Button, DeviceProfile, HidDevice, PressType, Recording};
+
+// Larger than any report
+const READ_BUF_LEN: usize = 4096;
+// Length of one full report: the report ID, header, and 16 bytes of payload,
+// as in the reports `HpMouse` writes. A read of a multiple of this holds
+// several reports back to back.
+const REPORT_LEN: usize = 21;
+// Longest packet to reassemble, other than the button list. The other known
+// packets are all well under this; the header's length field can claim up
+// to 1023.
+const MAX_INCOMING_LEN: usize = 512;
+// Longest button list (kind 14) to reassemble. With an entry of 4 +
+// `MAX_ACTION_LEN` bytes for each press type of seven buttons, a list could
+// be more than the 1023 bytes the header's length field can claim, so only
+// that bounds it.
+const MAX_BUTTONS_LEN: usize = 1023;
 
 fn u16_from_bytes(low: u8, high: u8) -> u16 {
     u16::from_le_bytes([low, high])
@@ -17,7 +39,6 @@ fn u16_from_bytes(low: u8, high: u8) -> u16 {
 #[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
 struct Header {
     signature: u16,
-    #[allow(unused)]
     composit_device: u8,
     length: usize,
     sequence: u8,
@@ -33,15 +54,35 @@ impl Header {
         })
     }
 
-    fn kind(&self) -> Option<u16> {
-        self.signature.checked_sub(HP_SIGNATURE)
+    fn kind(&self, profile: &DeviceProfile) -> Option<u16> {
+        self.signature.checked_sub(profile.signature)
     }
 }
 
-#[derive(Debug)]
+/// Firmware version as (major, minor, patch), ordered numerically by component
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct FirmwareVersion(pub u16, pub u16, pub u16);
+
+impl From<(u16, u16, u16)> for FirmwareVersion {
+    fn from((major, minor, patch): (u16, u16, u16)) -> Self {
+        Self(major, minor, patch)
+    }
+}
+
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+/// Serialized tagged with its kind, as `{"type": "battery", "level": 80, ...}`
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
 pub enum Event {
     Firmware {
-        version: (u16, u16, u16),
+        version: FirmwareVersion,
         device: String,
         serial: String,
     },
@@ -49,6 +90,8 @@ pub enum Event {
         low_level: u8,
         crit_level: u8,
         power_off_timeout: u8,
+        /// In units of `AUTO_REPORT_UNIT`; 0 if the device doesn't push
+        /// battery reports
         auto_report_delay: u8,
         level: u8,
     },
@@ -56,11 +99,7 @@ pub enum Event {
         total_buttons: u8,
         programmed_buttons: u8,
         host_id: u8,
-        support_long_press: bool,
-        support_double_press: bool,
-        support_down_up_press: bool,
-        support_simulate: bool,
-        support_program_stop: bool,
+        capabilities: ButtonsCapabilities,
         buttons: Vec<Button>,
     },
     Mouse {
@@ -79,29 +118,404 @@ pub enum Event {
         left_handed: bool,
         support_no_save_to_flash: bool,
     },
+    // Index of the active on-device profile, if `DeviceProfile::profile_slots`
+    ActiveProfile {
+        index: u8,
+    },
+    // Color of the status LED, if `DeviceProfile::led`
+    Led {
+        red: u8,
+        green: u8,
+        blue: u8,
+    },
+    // Factory reset finished, if `DeviceProfile::factory_reset`
+    FactoryReset,
+    // Status packet with a non-zero command byte, instead of the status
+    CommandError {
+        kind: u16,
+        code: u8,
+    },
+    // Packet kind not handled by this crate, passed through as-is
+    Unknown {
+        kind: u16,
+        data: Vec<u8>,
+    },
+    // Synthetic, yielded first by the `HpMouseEvents` iterator, once the
+    // device is open and before any report from it
+    Connected,
+    // Synthetic, emitted by `ReconnectingEvents` after reopening the device
+    Reconnected,
+}
+
+/// What the buttons support, from the flags in `Event::Buttons`. These are
+/// reported once for all buttons; no per-button capabilities are known in
+/// the protocol, so every button is assumed to support the same.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ButtonsCapabilities {
+    pub long_press: bool,
+    pub double_press: bool,
+    /// Separate actions for `PressType::Down` and `PressType::Up`
+    pub down_up_press: bool,
+    /// `HpMouse::exec_button`
+    pub simulate: bool,
+    pub program_stop: bool,
+}
+
+impl ButtonsCapabilities {
+    fn from_flags(flags: u8) -> Self {
+        let flags = flags.view_bits::<Lsb0>();
+        Self {
+            long_press: flags[0],
+            double_press: flags[1],
+            down_up_press: flags[2],
+            simulate: flags[3],
+            program_stop: flags[4],
+        }
+    }
+
+    /// Whether a binding for `press_type` can be programmed
+    pub fn supports(&self, press_type: PressType) -> bool {
+        match press_type {
+            PressType::Normal => true,
+            PressType::Long => self.long_press,
+            PressType::Double => self.double_press,
+            PressType::Down | PressType::Up => self.down_up_press,
+        }
+    }
+}
+
+/// Error reading events, classified by whether reading can continue
+#[derive(Debug)]
+pub enum ReadError {
+    /// Device is temporarily unavailable; retry after a while
+    Busy(io::Error),
+    /// Device was removed or can no longer be used; stop reading
+    Gone(io::Error),
+    /// Other read failure
+    Io(io::Error),
+    /// Malformed or unexpected report. The device is fine, and reading can
+    /// continue with the next report.
+    Protocol(ProtocolError),
+}
+
+/// Report 1 that can't be parsed, or doesn't continue the packet being
+/// reassembled. A packet that breaks off is dropped, and reassembly starts
+/// over with the next report of sequence 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProtocolError {
+    /// Report too short for a header
+    InvalidHeader,
+    /// Signature below the device's base, so of no packet kind
+    InvalidSignature(u16),
+    /// Header claims a packet longer than any known one
+    TooLong(usize),
+    /// Report out of order, such as after one was lost. `expected` is 0 when
+    /// no packet was started.
+    SequenceGap { expected: u8, found: u8 },
+    /// Sequence 0 before the previous packet was complete
+    UnexpectedStart,
+    /// Continuation of a different packet kind than was started
+    SignatureMismatch { expected: u16, found: u16 },
+    /// Continuation claiming a different total length
+    LengthMismatch { expected: usize, found: usize },
+    /// Continuation for a different composite device
+    DeviceMismatch { expected: u8, found: u8 },
+    /// Complete packet with a malformed payload
+    Payload(String),
+}
+
+impl ProtocolError {
+    // Whether the packet being reassembled can't be completed
+    fn breaks_packet(&self) -> bool {
+        matches!(
+            self,
+            Self::SequenceGap { .. }
+                | Self::UnexpectedStart
+                | Self::SignatureMismatch { .. }
+                | Self::LengthMismatch { .. }
+                | Self::DeviceMismatch { .. }
+        )
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => f.write_str("Invalid header"),
+            Self::InvalidSignature(signature) => {
+                write!(f, "Invalid header signature {:04X}", signature)
+            }
+            Self::TooLong(length) => write!(
+                f,
+                "Packet length {} more than the limit of {}",
+                length, MAX_INCOMING_LEN
+            ),
+            Self::SequenceGap { expected, found } => write!(
+                f,
+                "Unexpected packet sequence {}, expected {}",
+                found, expected
+            ),
+            Self::UnexpectedStart => f.write_str("Unexpected packet sequence 0"),
+            Self::SignatureMismatch { expected, found } => write!(
+                f,
+                "Non-matching signature. Expected: {:04X} Found: {:04X}",
+                expected, found
+            ),
+            Self::LengthMismatch { expected, found } => write!(
+                f,
+                "Non-matching length. Expected: {} Found: {}",
+                expected, found
+            ),
+            Self::DeviceMismatch { expected, found } => write!(
+                f,
+                "Non-matching composite device. Expected: {} Found: {}",
+                expected, found
+            ),
+            Self::Payload(err) => f.write_str(err),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl ReadError {
+    pub(crate) fn from_io(err: io::Error) -> Self {
+        match err.raw_os_error() {
+            Some(libc::ENODEV | libc::ENXIO | libc::ESHUTDOWN | libc::EPIPE | libc::EBADF) => {
+                return Self::Gone(err)
+            }
+            Some(libc::EBUSY | libc::EAGAIN | libc::ETIMEDOUT | libc::ENOMEM) => {
+                return Self::Busy(err)
+            }
+            _ => {}
+        }
+        match err.kind() {
+            ErrorKind::NotFound | ErrorKind::BrokenPipe => Self::Gone(err),
+            ErrorKind::TimedOut | ErrorKind::WouldBlock => Self::Busy(err),
+            _ => Self::Io(err),
+        }
+    }
+
+    /// Whether reading may succeed if retried later
+    pub fn is_busy(&self) -> bool {
+        matches!(self, Self::Busy(_))
+    }
+
+    /// Whether the device is gone, so reading should stop
+    pub fn is_gone(&self) -> bool {
+        matches!(self, Self::Gone(_))
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Busy(err) => write!(f, "device busy: {}", err),
+            Self::Gone(err) => write!(f, "device gone: {}", err),
+            Self::Io(err) => err.fmt(f),
+            Self::Protocol(err) => err.fmt(f),
+        }
+    }
+}
+
+/// Kept apart by kind: `Busy` is `ResourceBusy`, `Gone` is `NotConnected`,
+/// and `Protocol` is `InvalidData`
+impl From<ReadError> for io::Error {
+    fn from(err: ReadError) -> Self {
+        match err {
+            ReadError::Busy(err) => io::Error::new(ErrorKind::ResourceBusy, err),
+            ReadError::Gone(err) => io::Error::new(ErrorKind::NotConnected, err),
+            ReadError::Io(err) => err,
+            ReadError::Protocol(err) => io::Error::new(ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Busy(err) | Self::Gone(err) | Self::Io(err) => Some(err),
+            Self::Protocol(err) => Some(err),
+        }
+    }
+}
+
+/// Framing statistics, for telling whether reports are lost or corrupted
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Diagnostics {
+    pub reports: u64,
+    pub reassembly_errors: u64,
+    // Header fields of last report
+    pub sequence: u8,
+    pub length: usize,
+    pub composit_device: u8,
+}
+
+// Device info from events, shared with `HpMouse`
+#[derive(Debug, Default)]
+pub(crate) struct Reported {
+    pub firmware_version: Option<FirmwareVersion>,
+    pub support_no_save_to_flash: Option<bool>,
+    pub cut_off_max: Option<u8>,
+    // Minimum, maximum, and step
+    pub dpi_range: Option<(u16, u16, u16)>,
+    pub support_left_handed: Option<bool>,
+    pub auto_report_delay: Option<u8>,
+    pub buttons_capabilities: Option<ButtonsCapabilities>,
+    // Queries `HpMouse` sent whose reply hasn't been matched, and when
+    pub queries: Vec<(Query, Instant)>,
+}
+
+// Query whose reply `HpMouseEvents::is_unsolicited` matches
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Query {
+    Buttons,
+    Mouse,
+}
+
+// Logs a report if parsing it panics, while the guard is held. This runs
+// during unwinding, so it does nothing if panics abort.
+struct PanicLog<'a>(Option<&'a [u8]>);
+
+impl Drop for PanicLog<'_> {
+    fn drop(&mut self) {
+        if let Some(report) = self.0.filter(|_| thread::panicking()) {
+            log::error!("{}", panic_log_message(report));
+        }
+    }
+}
+
+fn panic_log_message(report: &[u8]) -> String {
+    format!(
+        "Panic parsing HID report of {} bytes:{}",
+        report.len(),
+        Hex(report)
+    )
+}
+
+// Bytes as ` 01 20 ff`, formatted only if the log line is enabled
+pub(crate) struct Hex<'a>(pub &'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, " {:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+// Non-zero command byte in a status packet; likely an error code from the
+// firmware
+fn command_error(kind: u16, code: u8) -> Option<Event> {
+    Some(Event::CommandError { kind, code })
 }
 
 pub struct HpMouseEvents {
-    dev: Arc<Hid>,
+    dev: Arc<dyn HidDevice>,
+    profile: &'static DeviceProfile,
+    reported: Arc<Mutex<Reported>>,
+    // Reused across reads, so events don't allocate
+    read_buf: Box<[u8]>,
     incoming: Vec<u8>,
     header: Header,
+    diagnostics: Diagnostics,
+    // `Event::Connected` not yet yielded
+    pending_connected: bool,
+    // Results of later reports from a read that held several
+    pending: VecDeque<Result<Event, ReadError>>,
+    log_panics: bool,
+    recording: Option<Recording>,
+    // Longest `next` waits for each read
+    timeout: Option<Duration>,
 }
 
 impl HpMouseEvents {
-    pub(crate) fn new(dev: Arc<Hid>) -> Self {
+    pub(crate) fn new(
+        dev: Arc<dyn HidDevice>,
+        profile: &'static DeviceProfile,
+        reported: Arc<Mutex<Reported>>,
+    ) -> Self {
         Self {
             dev,
+            profile,
+            reported,
+            read_buf: vec![0; READ_BUF_LEN].into_boxed_slice(),
             incoming: Vec::new(),
             header: Header::default(),
+            diagnostics: Diagnostics::default(),
+            pending_connected: true,
+            pending: VecDeque::new(),
+            log_panics: false,
+            recording: None,
+            timeout: None,
+        }
+    }
+
+    /// Don't yield `Event::Connected`, for a caller that already announced
+    /// the device in some other way
+    pub(crate) fn skip_connected(&mut self) {
+        self.pending_connected = false;
+    }
+
+    /// Whether `event` is an `Event::Buttons` or `Event::Mouse` the device
+    /// pushed, such as after being reconfigured from another host, rather
+    /// than a reply to `HpMouse::query_button` or `query_dpi`. Each query
+    /// matches the first such event within `VERIFY_TIMEOUT` of it.
+    ///
+    /// A `false` result consumes the query the event answers, so a second
+    /// call for the same event can return `true`. Call this exactly once for
+    /// every event read.
+    pub fn is_unsolicited(&self, event: &Event) -> bool {
+        let query = match event {
+            Event::Buttons { .. } => Query::Buttons,
+            Event::Mouse { .. } => Query::Mouse,
+            _ => return false,
+        };
+        let mut reported = self.reported.lock().unwrap();
+        reported
+            .queries
+            .retain(|(_, time)| time.elapsed() < crate::VERIFY_TIMEOUT);
+        match reported.queries.iter().position(|(x, _)| *x == query) {
+            Some(idx) => {
+                reported.queries.remove(idx);
+                false
+            }
+            None => true,
         }
     }
 
-    fn report_1_packet_1(&mut self, data: &[u8]) -> Option<Event> {
-        println!("Update {}", data.len());
+    /// Log the raw report as an error if parsing it panics, for bug reports
+    /// about packets the parser doesn't handle. The panic still propagates.
+    pub fn log_panics(&mut self, enable: bool) {
+        self.log_panics = enable;
+    }
+
+    /// Write every report read, and the event or error parsed from it, to
+    /// `recording`. `None` stops recording.
+    pub fn record(&mut self, recording: Option<Recording>) {
+        self.recording = recording;
+    }
+
+    /// Give up waiting for a report in `next` after `timeout`, returning
+    /// `Some(Err(ReadError::Busy(_)))` with `ErrorKind::TimedOut`, so a
+    /// caller can do other work and then keep reading. Applies to each read,
+    /// so a packet spanning several reports can take longer. `None` waits
+    /// indefinitely, as by default. See `next_timeout` for a single call.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    fn report_1_packet_1(&mut self, data: &[u8]) -> Result<Option<Event>, String> {
+        log::debug!("Update {}", data.len());
 
         if data.len() <= 3 {
             // Buffer too small
-            return None;
+            return Ok(None);
         }
 
         let firmware_version = u16_from_bytes(data[0], data[1]);
@@ -115,22 +529,37 @@ impl HpMouseEvents {
             let size = data[i] as usize;
             i += 1;
 
-            let mut item = Vec::with_capacity(size);
-            while i < data.len() && item.len() < size {
-                item.push(data[i]);
-                i += 1;
-            }
+            // Rather than a truncated device name or serial
+            let item = data.get(i..i + size).ok_or_else(|| {
+                format!(
+                    "Firmware item {} is {} bytes, but only {} remain",
+                    items.len() + 1,
+                    size,
+                    data.len() - i
+                )
+            })?;
             items.push(item);
+            i += size;
         }
 
-        let device = str::from_utf8(items.get(0)?).ok()?;
-        let serial = str::from_utf8(items.get(1)?).ok()?;
+        let strings = || {
+            let device = str::from_utf8(items.first()?).ok()?;
+            let serial = str::from_utf8(items.get(1)?).ok()?;
+            Some((device, serial))
+        };
+        let (device, serial) = match strings() {
+            Some(strings) => strings,
+            None => return Ok(None),
+        };
+
+        let version = FirmwareVersion(major_version, minor_version, patch_version);
+        self.reported.lock().unwrap().firmware_version = Some(version);
 
-        Some(Event::Firmware {
-            version: (major_version, minor_version, patch_version),
+        Ok(Some(Event::Firmware {
+            version,
             device: device.to_string(),
             serial: serial.to_string(),
-        })
+        }))
     }
 
     fn report_1_packet_6(&mut self, data: &[u8]) -> Option<Event> {
@@ -144,6 +573,7 @@ impl HpMouseEvents {
         let power_off_timeout = data[2];
         let auto_report_delay = data[3];
         let level = data[4];
+        self.reported.lock().unwrap().auto_report_delay = Some(auto_report_delay);
 
         Some(Event::Battery {
             low_level,
@@ -154,60 +584,68 @@ impl HpMouseEvents {
         })
     }
 
-    fn report_1_packet_14(&mut self, data: &[u8]) -> Option<Event> {
-        if data.get(0) != Some(&0) {
-            // Wrong command
-            return None;
+    fn report_1_packet_14(&mut self, data: &[u8]) -> Result<Option<Event>, String> {
+        match data.first() {
+            Some(0) => {}
+            Some(code) => return Ok(command_error(14, *code)),
+            None => return Ok(None),
         }
 
         if data.len() <= 4 {
             // Buffer too small
-            return None;
+            return Ok(None);
         }
 
         let total_buttons = data[1];
         let programmed_buttons = data[2];
         let host_id = data[3];
 
-        let flags = data[4].view_bits::<Lsb0>();
-        let support_long_press = flags[0];
-        let support_double_press = flags[1];
-        let support_down_up_press = flags[2];
-        let support_simulate = flags[3];
-        let support_program_stop = flags[4];
+        // Don't trust the count to size allocations or the loop below. Each
+        // button has an entry for each press type programmed.
+        if usize::from(programmed_buttons) > usize::from(total_buttons) * PressType::COUNT {
+            return Err(format!(
+                "Invalid button count {} of {}",
+                programmed_buttons, total_buttons
+            ));
+        }
+
+        let capabilities = ButtonsCapabilities::from_flags(data[4]);
 
         let mut buttons = Vec::with_capacity(programmed_buttons as usize);
         let mut i = 5;
         while buttons.len() < programmed_buttons as usize {
-            if let Some((button, count)) = Button::decode(&data[i..]) {
+            if let Some((button, count)) = data.get(i..).and_then(Button::decode) {
                 buttons.push(button);
                 i += count;
             } else {
-                break;
+                // Don't return a partial list if an action runs past the packet
+                return Err(format!(
+                    "Invalid or truncated button {} of {}",
+                    buttons.len() + 1,
+                    programmed_buttons
+                ));
             }
         }
 
         for button in buttons.iter() {
-            eprintln!("Action: {:?}", button.decode_action());
+            log::debug!("Action: {:?}", button.decode_action());
         }
+        self.reported.lock().unwrap().buttons_capabilities = Some(capabilities);
 
-        Some(Event::Buttons {
+        Ok(Some(Event::Buttons {
             total_buttons,
             programmed_buttons,
             host_id,
-            support_long_press,
-            support_double_press,
-            support_down_up_press,
-            support_simulate,
-            support_program_stop,
+            capabilities,
             buttons,
-        })
+        }))
     }
 
     fn report_1_packet_18(&mut self, data: &[u8]) -> Option<Event> {
-        if data.get(0) != Some(&0) {
-            // Wrong command
-            return None;
+        match data.first() {
+            Some(0) => {}
+            Some(code) => return command_error(18, *code),
+            None => return None,
         }
 
         if data.len() <= 14 {
@@ -233,6 +671,12 @@ impl HpMouseEvents {
         let support_left_handed = flags[0];
         let left_handed = flags[1];
         let support_no_save_to_flash = flags[2];
+        let mut reported = self.reported.lock().unwrap();
+        reported.support_no_save_to_flash = Some(support_no_save_to_flash);
+        reported.cut_off_max = Some(cut_off_max);
+        reported.dpi_range = Some((min_dpi, max_dpi, step_dpi));
+        reported.support_left_handed = Some(support_left_handed);
+        drop(reported);
 
         Some(Event::Mouse {
             max_dpi,
@@ -252,36 +696,152 @@ impl HpMouseEvents {
         })
     }
 
-    fn report_1(&mut self, data: &[u8]) -> Result<Option<Event>, String> {
-        let header = Header::new(data).ok_or_else(|| "Invalid header".to_string())?;
+    fn report_1_active_profile(&mut self, kind: u16, data: &[u8]) -> Result<Option<Event>, String> {
+        let slots = match &self.profile.profile_slots {
+            Some(slots) => slots,
+            None => return Ok(None),
+        };
+        // Older firmware may use the packet kind for something else
+        let firmware_version = self.reported.lock().unwrap().firmware_version;
+        if firmware_version.is_some_and(|version| version < slots.min_firmware) {
+            return Ok(Some(Event::Unknown {
+                kind,
+                data: data.to_vec(),
+            }));
+        }
+
+        match data.first() {
+            Some(0) => {}
+            Some(code) => return Ok(command_error(kind, *code)),
+            None => return Ok(None),
+        }
+
+        let index = match data.get(1) {
+            Some(index) => *index,
+            // Buffer too small
+            None => return Ok(None),
+        };
+        if index >= slots.count {
+            return Err(format!(
+                "Invalid profile index {} of {}",
+                index, slots.count
+            ));
+        }
 
-        let kind_opt = header.kind();
-        println!(
-            " signature {:04X} {:?} length {} sequence {}",
-            header.signature, kind_opt, header.length, header.sequence
+        Ok(Some(Event::ActiveProfile { index }))
+    }
+
+    fn report_1_factory_reset(&mut self, kind: u16, data: &[u8]) -> Option<Event> {
+        let factory_reset = self.profile.factory_reset.as_ref()?;
+        // Older firmware may use the packet kind for something else
+        let firmware_version = self.reported.lock().unwrap().firmware_version;
+        if firmware_version.is_some_and(|version| version < factory_reset.min_firmware) {
+            return Some(Event::Unknown {
+                kind,
+                data: data.to_vec(),
+            });
+        }
+
+        match data.first() {
+            Some(0) => Some(Event::FactoryReset),
+            Some(code) => command_error(kind, *code),
+            None => None, // Buffer too small
+        }
+    }
+
+    fn report_1_led(&mut self, kind: u16, data: &[u8]) -> Option<Event> {
+        let led = self.profile.led.as_ref()?;
+        // Older firmware may use the packet kind for something else
+        let firmware_version = self.reported.lock().unwrap().firmware_version;
+        if firmware_version.is_some_and(|version| version < led.min_firmware) {
+            return Some(Event::Unknown {
+                kind,
+                data: data.to_vec(),
+            });
+        }
+
+        match data {
+            [0, red, green, blue, ..] => Some(Event::Led {
+                red: *red,
+                green: *green,
+                blue: *blue,
+            }),
+            [0, ..] | [] => None, // Buffer too small
+            [code, ..] => command_error(kind, *code),
+        }
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(len = data.len()))
+    )]
+    fn report_1(&mut self, data: &[u8]) -> Result<Option<Event>, ProtocolError> {
+        let header = Header::new(data).ok_or(ProtocolError::InvalidHeader)?;
+        self.diagnostics.reports += 1;
+        self.diagnostics.sequence = header.sequence;
+        self.diagnostics.length = header.length;
+        self.diagnostics.composit_device = header.composit_device;
+
+        let kind_opt = header.kind(self.profile);
+        log::debug!(
+            "signature {:04X} {:?} length {} sequence {}",
+            header.signature,
+            kind_opt,
+            header.length,
+            header.sequence
         );
 
         // Ensure signature is valid and can be converted to a packet kind
-        let kind = kind_opt.ok_or_else(|| "Invalid header signature".to_string())?;
+        let kind = kind_opt.ok_or(ProtocolError::InvalidSignature(header.signature))?;
 
         // Insert new incoming packet if sequence is 0, verify there is no current one
         if header.sequence == 0 {
             if !self.incoming.is_empty() {
-                return Err("Unexpected packet sequence 0".to_string());
+                return Err(ProtocolError::UnexpectedStart);
+            }
+            let max_len = if kind == 14 {
+                MAX_BUTTONS_LEN
+            } else {
+                MAX_INCOMING_LEN
+            };
+            if header.length > max_len {
+                return Err(ProtocolError::TooLong(header.length));
             }
             self.header = header;
         // Get current incoming packet, verify that it exists
         } else {
             if self.incoming.is_empty() {
-                return Err(format!("Unexpected packet sequence {}", header.sequence));
+                return Err(ProtocolError::SequenceGap {
+                    expected: 0,
+                    found: header.sequence,
+                });
             }
-            self.header.sequence += 1;
-            if header != self.header {
-                return Err(format!(
-                    "Non-matching header. Expected: {:?} Found: {:?}",
-                    self.header, header
-                ));
+            let expected = self.header;
+            if header.signature != expected.signature {
+                return Err(ProtocolError::SignatureMismatch {
+                    expected: expected.signature,
+                    found: header.signature,
+                });
+            }
+            if header.composit_device != expected.composit_device {
+                return Err(ProtocolError::DeviceMismatch {
+                    expected: expected.composit_device,
+                    found: header.composit_device,
+                });
             }
+            if header.length != expected.length {
+                return Err(ProtocolError::LengthMismatch {
+                    expected: expected.length,
+                    found: header.length,
+                });
+            }
+            if header.sequence != expected.sequence + 1 {
+                return Err(ProtocolError::SequenceGap {
+                    expected: expected.sequence + 1,
+                    found: header.sequence,
+                });
+            }
+            self.header = header;
         }
 
         // Push back new data
@@ -291,24 +851,80 @@ impl HpMouseEvents {
         if self.incoming.len() >= header.length {
             let mut incoming = mem::take(&mut self.incoming);
             incoming.truncate(header.length);
-            return Ok(match kind {
-                1 => self.report_1_packet_1(&incoming),
+            if !self.profile.supports_packet(kind) {
+                return Ok(Some(Event::Unknown {
+                    kind,
+                    data: incoming,
+                }));
+            }
+            let profile_slots_kind = self.profile.profile_slots.as_ref().map(|x| x.packet_kind);
+            let led_kind = self.profile.led.as_ref().map(|x| x.packet_kind);
+            let factory_reset_kind = self.profile.factory_reset.as_ref().map(|x| x.packet_kind);
+            let event = match kind {
+                _ if Some(kind) == profile_slots_kind => self
+                    .report_1_active_profile(kind, &incoming)
+                    .map_err(ProtocolError::Payload)?,
+                _ if Some(kind) == led_kind => self.report_1_led(kind, &incoming),
+                _ if Some(kind) == factory_reset_kind => {
+                    self.report_1_factory_reset(kind, &incoming)
+                }
+                1 => self
+                    .report_1_packet_1(&incoming)
+                    .map_err(ProtocolError::Payload)?,
                 6 => self.report_1_packet_6(&incoming),
-                14 => self.report_1_packet_14(&incoming),
+                14 => self
+                    .report_1_packet_14(&incoming)
+                    .map_err(ProtocolError::Payload)?,
                 18 => self.report_1_packet_18(&incoming),
-                _ => None,
-            });
+                _ => {
+                    return Ok(Some(Event::Unknown {
+                        kind,
+                        data: incoming,
+                    }))
+                }
+            };
+            // Keep the allocation for the next packet
+            incoming.clear();
+            self.incoming = incoming;
+            return Ok(event);
         }
 
         // No full packet yet
         Ok(None)
     }
 
-    pub fn read(&mut self) -> Result<ReadRes, String> {
-        let mut buf = [0; 4096];
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(len = tracing::field::Empty))
+    )]
+    pub fn read(&mut self) -> Result<ReadRes, ReadError> {
+        if let Some(res) = self.pending.pop_front() {
+            return res.map(ReadRes::Packet);
+        }
+        // Taken while reading, since `report_1` also borrows `self`
+        let mut buf = mem::take(&mut self.read_buf);
+        let res = self.read_into(&mut buf);
+        self.read_buf = buf;
+        res
+    }
+
+    // Kind of the packet in `report`, if it is report 1 with a header for
+    // this device
+    fn report_1_kind(&self, report: &[u8]) -> Option<u16> {
+        if report.first() != Some(&1) {
+            return None;
+        }
+        Header::new(&report[1..])?.kind(self.profile)
+    }
+
+    // Whether `report` is part of a firmware packet, which has the serial
+    fn is_firmware(&self, report: &[u8]) -> bool {
+        self.report_1_kind(report) == Some(1)
+    }
 
+    fn read_into(&mut self, buf: &mut [u8]) -> Result<ReadRes, ReadError> {
         let len = loop {
-            match self.dev.read(&mut buf) {
+            match self.dev.read(buf) {
                 Ok(0) => {
                     return Ok(ReadRes::EOF);
                 }
@@ -319,56 +935,523 @@ impl HpMouseEvents {
                     if err.raw_os_error() == Some(libc::EIO) {
                         // Error when device is disconnected
                         return Ok(ReadRes::EOF);
+                    } else if err.kind() == ErrorKind::WouldBlock {
+                        // Device opened non-blocking, and no data is available
+                        return Ok(ReadRes::WouldBlock);
                     } else if err.kind() != ErrorKind::Interrupted {
-                        return Err(err.to_string());
+                        return Err(ReadError::from_io(err));
                     }
                 }
             }
         };
 
-        eprintln!("HID read {}", len);
-        for i in 0..len {
-            eprint!(" {:02x}", buf[i]);
-        }
-        eprintln!();
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("len", &len);
 
-        match buf[0] {
-            1 => {
-                if let Some(packet) = self.report_1(&buf[1..len])? {
-                    return Ok(ReadRes::Packet(packet));
-                }
+        log::trace!("HID read {}:{}", len, Hex(&buf[..len]));
+
+        // Usually one report, but a backend may return several full-length
+        // ones at once. Only split where each part starts like a report, so
+        // a single long report isn't cut up.
+        let concatenated = len > REPORT_LEN
+            && len % REPORT_LEN == 0
+            && buf[..len]
+                .chunks(REPORT_LEN)
+                .all(|report| self.report_1_kind(report).is_some());
+        let report_len = if concatenated { REPORT_LEN } else { len };
+        for report in buf[..len].chunks(report_len) {
+            let _panic_log = PanicLog(Some(report).filter(|_| self.log_panics));
+            if let Some(recording) = &self.recording {
+                recording.report(
+                    report,
+                    recording.redacts_serial() && self.is_firmware(report),
+                );
             }
-            _ => {}
+            let res = match report[0] {
+                1 => match self.report_1(&report[1..]) {
+                    Ok(Some(packet)) => Ok(packet),
+                    Ok(None) => continue,
+                    Err(err) => {
+                        self.diagnostics.reassembly_errors += 1;
+                        if err.breaks_packet() {
+                            // Start over with the next packet
+                            self.incoming.clear();
+                            self.header = Header::default();
+                        }
+                        Err(ReadError::Protocol(err))
+                    }
+                },
+                _ => continue,
+            };
+            if let Some(recording) = &self.recording {
+                recording.result(&res);
+            }
+            self.pending.push_back(res);
+        }
+        match self.pending.pop_front() {
+            Some(res) => res.map(ReadRes::Packet),
+            None => Ok(ReadRes::Continue),
         }
-        Ok(ReadRes::Continue)
     }
 }
 
 pub enum ReadRes {
     Packet(Event),
     Continue,
+    WouldBlock,
     EOF,
 }
 
+#[cfg(feature = "async")]
+impl HpMouseEvents {
+    pub(crate) fn device(&self) -> &Arc<dyn HidDevice> {
+        &self.dev
+    }
+
+    /// Like `next`, but `Poll::Pending` instead of blocking when nothing can
+    /// be read yet
+    pub(crate) fn next_ready(&mut self) -> Poll<Option<Result<Event, ReadError>>> {
+        if mem::take(&mut self.pending_connected) {
+            return Poll::Ready(Some(Ok(Event::Connected)));
+        }
+
+        loop {
+            match self.poll(0) {
+                Ok(false) => return Poll::Pending,
+                Ok(true) => {}
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Poll::Ready(Some(Err(ReadError::from_io(err)))),
+            }
+            return Poll::Ready(match self.read() {
+                Ok(ReadRes::Continue) | Ok(ReadRes::WouldBlock) => continue,
+                Ok(ReadRes::Packet(event)) => Some(Ok(event)),
+                Ok(ReadRes::EOF) => None,
+                Err(err) => Some(Err(err)),
+            });
+        }
+    }
+}
+
+// `timeout` for `HidDevice::poll`
+fn poll_millis(timeout: Duration) -> i32 {
+    timeout.as_millis().try_into().unwrap_or(i32::MAX)
+}
+
+// How long `HpMouseEvents::next_waiting` waits for a report
+#[derive(Clone, Copy)]
+enum Wait {
+    Forever,
+    // Up to this long for each read, as set with `set_timeout`
+    PerRead(Duration),
+    Until(Instant),
+}
+
 impl Iterator for HpMouseEvents {
-    type Item = Result<Event, String>;
+    type Item = Result<Event, ReadError>;
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     fn next(&mut self) -> Option<Self::Item> {
+        let wait = match self.timeout {
+            Some(timeout) => Wait::PerRead(timeout),
+            None => Wait::Forever,
+        };
+        self.next_waiting(wait).unwrap_or_else(|| {
+            let err = io::Error::from(ErrorKind::TimedOut);
+            Some(Err(ReadError::from_io(err)))
+        })
+    }
+}
+
+impl HpMouseEvents {
+    // `next`, or `None` if `wait` runs out first
+    fn next_waiting(&mut self, wait: Wait) -> Option<Option<Result<Event, ReadError>>> {
+        if mem::take(&mut self.pending_connected) {
+            return Some(Some(Ok(Event::Connected)));
+        }
+
         loop {
-            return match self.read() {
+            let timeout = match wait {
+                Wait::Forever => None,
+                Wait::PerRead(timeout) => Some(timeout),
+                Wait::Until(deadline) => Some(deadline.saturating_duration_since(Instant::now())),
+            };
+            if let Some(timeout) = timeout {
+                match self.poll(poll_millis(timeout)) {
+                    Ok(false) => return None,
+                    Ok(true) => {}
+                    Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                    Err(err) => return Some(Some(Err(ReadError::from_io(err)))),
+                }
+            }
+            return Some(match self.read() {
                 Ok(ReadRes::Continue) => {
                     continue;
                 }
+                Ok(ReadRes::WouldBlock) => {
+                    // Wait until readable, so a non-blocking device doesn't spin
+                    if timeout.is_none() {
+                        let _ = self.poll(-1);
+                    }
+                    continue;
+                }
                 Ok(ReadRes::Packet(event)) => Some(Ok(event)),
                 Ok(ReadRes::EOF) => None,
                 Err(err) => Some(Err(err)),
-            };
+            });
+        }
+    }
+
+    /// File descriptor to wait on for input, such as in an event loop, or
+    /// `None` for a backend without one. Events already read ahead are
+    /// returned without the descriptor becoming readable, so check `poll(0)`
+    /// first.
+    pub fn raw_fd(&self) -> Option<RawFd> {
+        self.dev.raw_fd()
+    }
+
+    /// Wait up to `timeout` milliseconds, or indefinitely if negative, for
+    /// `read` to have input. Returns `false` on timeout.
+    pub fn poll(&self, timeout: i32) -> io::Result<bool> {
+        if !self.pending.is_empty() {
+            return Ok(true);
         }
+        self.dev.poll(timeout)
+    }
+
+    /// Like `next`, but returns `None` if no event arrives within `timeout`,
+    /// so a caller can do other work between events. The timeout is for the
+    /// whole call, however many reports it reads, and replaces any set with
+    /// `set_timeout`. Reading can continue after a timeout. The end of the
+    /// stream is `Some(Err(ReadError::Gone(_)))`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<Result<Event, ReadError>> {
+        let res = self.next_waiting(Wait::Until(Instant::now() + timeout))?;
+        Some(res.unwrap_or_else(|| {
+            let err = io::Error::new(ErrorKind::UnexpectedEof, "end of events");
+            Err(ReadError::Gone(err))
+        }))
     }
 }
 
-impl AsRawFd for HpMouseEvents {
-    fn as_raw_fd(&self) -> RawFd {
-        self.dev.as_raw_fd()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HpMouse, MockHid, HP_930};
+
+    fn read_packet(report: Vec<u8>) -> Result<ReadRes, ReadError> {
+        HpMouse::from_device(MockHid::with_reports(vec![report]))
+            .read()
+            .read()
+    }
+
+    #[test]
+    fn test_version_order() {
+        assert!(FirmwareVersion(1, 2, 0) < FirmwareVersion(1, 10, 0));
+        assert!(FirmwareVersion(1, 10, 0) < FirmwareVersion(2, 0, 0));
+        assert_eq!(FirmwareVersion(1, 10, 3).to_string(), "1.10.3");
+    }
+
+    #[test]
+    fn test_require_firmware() {
+        // 1.10.3, device "930", serial "ABC"
+        let report = MockHid::report_1(
+            1,
+            &[0x4F, 0x04, 0, 0, 3, b'9', b'3', b'0', 3, b'A', b'B', b'C'],
+        );
+        let mouse = HpMouse::from_device(MockHid::with_reports(vec![report]));
+        // Unknown until the firmware packet is read
+        assert!(mouse.require_firmware(FirmwareVersion(9, 0, 0)).is_ok());
+        mouse.read().nth(1);
+        assert_eq!(mouse.firmware_version(), Some(FirmwareVersion(1, 10, 3)));
+        assert!(mouse.require_firmware(FirmwareVersion(1, 2, 0)).is_ok());
+        assert!(mouse.require_firmware(FirmwareVersion(1, 11, 0)).is_err());
+    }
+
+    fn buttons_payload(last_size: u8, last_action: &[u8]) -> Vec<u8> {
+        let mut payload = vec![0, 7, 2, 0, 0b11111];
+        payload.extend_from_slice(&[0, 0, 0, 1, 0]);
+        payload.extend_from_slice(&[1, 0, 0, last_size]);
+        payload.extend_from_slice(last_action);
+        payload
+    }
+
+    #[test]
+    fn test_firmware_item_overrun() {
+        // Serial claims 5 bytes, with 3 left
+        let report = MockHid::report_1(
+            1,
+            &[0x4F, 0x04, 0, 0, 3, b'9', b'3', b'0', 5, b'A', b'B', b'C'],
+        );
+        assert!(matches!(read_packet(report), Err(ReadError::Protocol(_))));
+
+        let report = MockHid::report_1(
+            1,
+            &[0x4F, 0x04, 0, 0, 3, b'9', b'3', b'0', 3, b'A', b'B', b'C'],
+        );
+        match read_packet(report) {
+            Ok(ReadRes::Packet(Event::Firmware { device, serial, .. })) => {
+                assert_eq!(device, "930");
+                assert_eq!(serial, "ABC");
+            }
+            _ => panic!("Expected firmware event"),
+        }
+    }
+
+    #[test]
+    fn test_command_error() {
+        for kind in [14, 18] {
+            let report = MockHid::report_1(kind, &[3, 0, 0, 0, 0]);
+            match read_packet(report) {
+                Ok(ReadRes::Packet(Event::CommandError { kind: x, code: 3 })) => {
+                    assert_eq!(x, kind);
+                }
+                _ => panic!("Expected command error"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_buttons_action_at_boundary() {
+        let report = MockHid::report_1(14, &buttons_payload(3, &[1, 2, 3]));
+        match read_packet(report) {
+            Ok(ReadRes::Packet(Event::Buttons {
+                buttons,
+                capabilities,
+                ..
+            })) => {
+                assert_eq!(buttons.len(), 2);
+                assert_eq!(buttons[1].action, &[1, 2, 3]);
+                assert!(capabilities.supports(PressType::Down));
+                assert!(capabilities.program_stop);
+            }
+            _ => panic!("Expected buttons event"),
+        }
+    }
+
+    #[test]
+    fn test_event_json() {
+        let report = MockHid::report_1(6, &[10, 5, 0, 6, 80]);
+        let event = match read_packet(report) {
+            Ok(ReadRes::Packet(event)) => event,
+            _ => panic!("Expected battery event"),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "battery");
+        assert_eq!(json["level"], 80);
+
+        let report = MockHid::report_1(14, &buttons_payload(3, &[1, 2, 3]));
+        let event = match read_packet(report) {
+            Ok(ReadRes::Packet(event)) => event,
+            _ => panic!("Expected buttons event"),
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        match serde_json::from_str(&json).unwrap() {
+            Event::Buttons {
+                buttons,
+                capabilities,
+                ..
+            } => {
+                assert_eq!(buttons[1].action, &[1, 2, 3]);
+                assert!(capabilities.program_stop);
+            }
+            event => panic!("Expected buttons event, got {:?}", event),
+        }
+        assert!(json.contains(r#""press_type":"normal""#));
+    }
+
+    #[test]
+    fn test_panic_log() {
+        assert_eq!(
+            panic_log_message(&[1, 0x20, 0xff]),
+            "Panic parsing HID report of 3 bytes: 01 20 ff"
+        );
+        // The panic still reaches the caller
+        let res = std::panic::catch_unwind(|| {
+            let _panic_log = PanicLog(Some(&[1, 2, 3]));
+            panic!("parser bug");
+        });
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_length_too_large() {
+        // Length 1000, sequence 0
+        let mut long = MockHid::report_1(6, &[10, 5, 0, 6, 80]);
+        long[3] = 0xE8;
+        long[4] = 0x03;
+        let battery = MockHid::report_1(6, &[10, 5, 0, 6, 80]);
+        let mut events = HpMouse::from_device(MockHid::with_reports(vec![long, battery])).read();
+        assert!(matches!(events.read(), Err(ReadError::Protocol(_))));
+        // Nothing is kept waiting for the rest, so the next packet is read
+        assert!(matches!(
+            events.read(),
+            Ok(ReadRes::Packet(Event::Battery { level: 80, .. }))
+        ));
+    }
+
+    // Report `sequence` of a packet of `len` bytes
+    fn fragment(kind: u16, len: u16, sequence: u8, payload: &[u8]) -> Vec<u8> {
+        let mut report = MockHid::report_1(kind, payload);
+        report[3..5].copy_from_slice(&(len | u16::from(sequence) << 10).to_le_bytes());
+        report
+    }
+
+    #[test]
+    fn test_resync() {
+        // Firmware packet in two reports
+        let firmware = [0x4F, 0x04, 0, 0, 3, b'9', b'3', b'0', 3, b'A', b'B', b'C'];
+        let start = fragment(1, 12, 0, &firmware[..6]);
+        let end = fragment(1, 12, 1, &firmware[6..]);
+        let battery = MockHid::report_1(6, &[10, 5, 0, 6, 80]);
+
+        let cases: [(Vec<u8>, ProtocolError); 5] = [
+            // Start of the next packet was lost
+            (
+                fragment(1, 12, 2, &firmware[6..]),
+                ProtocolError::SequenceGap {
+                    expected: 1,
+                    found: 2,
+                },
+            ),
+            (
+                fragment(6, 12, 1, &firmware[6..]),
+                ProtocolError::SignatureMismatch {
+                    expected: HP_930.signature + 1,
+                    found: HP_930.signature + 6,
+                },
+            ),
+            (
+                fragment(1, 13, 1, &firmware[6..]),
+                ProtocolError::LengthMismatch {
+                    expected: 12,
+                    found: 13,
+                },
+            ),
+            (battery.clone(), ProtocolError::UnexpectedStart),
+            (
+                end.clone(),
+                ProtocolError::SequenceGap {
+                    expected: 0,
+                    found: 1,
+                },
+            ),
+        ];
+        for (i, (corrupt, err)) in cases.into_iter().enumerate() {
+            let mut reports = vec![start.clone(), corrupt];
+            if i == 4 {
+                // Continuation without a start
+                reports.remove(0);
+            }
+            reports.extend([end.clone(), battery.clone(), start.clone(), end.clone()]);
+            let events = HpMouse::from_device(MockHid::with_reports(reports)).read();
+            let res: Vec<_> = events.skip(1).collect();
+            // The rest of the broken packet is rejected too, then the stream
+            // recovers
+            match res.as_slice() {
+                [Err(ReadError::Protocol(x)), Err(ReadError::Protocol(_)), Ok(Event::Battery { .. }), Ok(Event::Firmware { .. })] =>
+                {
+                    assert_eq!(x, &err, "case {}", i)
+                }
+                _ => panic!("case {}: {:?}", i, res),
+            }
+        }
+    }
+
+    #[test]
+    fn test_truncated_packets() {
+        let firmware = [0x4F, 0x04, 0, 0, 3, b'9', b'3', b'0', 3, b'A', b'B', b'C'];
+        let mut mouse_info = [0; 15];
+        mouse_info[14] = 0b101;
+        let packets: [(u16, &[u8]); 4] = [
+            (1, &firmware),
+            (6, &[10, 5, 0, 6, 80]),
+            (14, &buttons_payload(3, &[1, 2, 3])),
+            (18, &mouse_info),
+        ];
+        for (kind, packet) in packets {
+            assert!(matches!(
+                read_packet(MockHid::report_1(kind, packet)),
+                Ok(ReadRes::Packet(_))
+            ));
+            // Ignored or rejected at every shorter length, without panicking
+            for len in 0..packet.len() {
+                let res = read_packet(MockHid::report_1(kind, &packet[..len]));
+                assert!(
+                    !matches!(
+                        res,
+                        Ok(ReadRes::Packet(
+                            Event::Firmware { .. }
+                                | Event::Battery { .. }
+                                | Event::Buttons { .. }
+                                | Event::Mouse { .. }
+                        ))
+                    ),
+                    "kind {} length {}",
+                    kind,
+                    len
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_buttons_action_truncated() {
+        let report = MockHid::report_1(14, &buttons_payload(4, &[1, 2, 3]));
+        assert!(matches!(read_packet(report), Err(ReadError::Protocol(_))));
+    }
+
+    #[test]
+    fn test_buttons_count_malformed() {
+        for count in [8, 0x80, 0xFF] {
+            let mut payload = buttons_payload(3, &[1, 2, 3]);
+            payload[2] = count;
+            let report = MockHid::report_1(14, &payload);
+            assert!(read_packet(report).is_err());
+        }
+    }
+
+    #[test]
+    fn test_buttons_down_up() {
+        // Normal, Down, and Up entries for each of seven buttons, as
+        // `binding_buttons` writes with `clear_down_up`
+        let mut payload = vec![0, 7, 21, 0, 0b11111];
+        for id in 0..7 {
+            for press_type in [0, 3, 4] {
+                payload.extend_from_slice(&[id, 0, press_type, 0]);
+            }
+        }
+        let len = payload.len() as u16;
+        let reports = payload
+            .chunks(16)
+            .enumerate()
+            .map(|(sequence, chunk)| fragment(14, len, sequence as u8, chunk))
+            .collect();
+        let mut events = HpMouse::from_device(MockHid::with_reports(reports)).read();
+        match events.nth(1) {
+            Some(Ok(Event::Buttons { buttons, .. })) => {
+                assert_eq!(buttons.len(), 21);
+                assert_eq!(buttons[20].id, 6);
+                assert_eq!(buttons[20].press_type, PressType::Up);
+            }
+            res => panic!("Expected buttons event, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_read_error_kind() {
+        let from_errno = |errno| ReadError::from_io(io::Error::from_raw_os_error(errno));
+        assert!(from_errno(libc::ENODEV).is_gone());
+        assert!(from_errno(libc::EBUSY).is_busy());
+        assert!(from_errno(libc::ETIMEDOUT).is_busy());
+        assert!(matches!(from_errno(libc::EINVAL), ReadError::Io(_)));
+        let err = ReadError::from_io(io::Error::from(ErrorKind::BrokenPipe));
+        assert!(err.is_gone());
+
+        let kind = |err: ReadError| io::Error::from(err).kind();
+        assert_eq!(kind(from_errno(libc::ENODEV)), ErrorKind::NotConnected);
+        assert_eq!(kind(from_errno(libc::EBUSY)), ErrorKind::ResourceBusy);
+        assert_eq!(kind(from_errno(libc::EINVAL)), ErrorKind::InvalidInput);
+        let err = ReadError::Protocol(ProtocolError::InvalidHeader);
+        assert_eq!(kind(err), ErrorKind::InvalidData);
     }
 }
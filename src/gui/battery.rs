@@ -0,0 +1,102 @@
+/// Battery state, in increasing severity
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BatteryAlert {
+    #[default]
+    Normal,
+    Low,
+    Critical,
+}
+
+impl BatteryAlert {
+    pub fn for_level(level: u8, low_level: u8, crit_level: u8) -> Self {
+        if level <= crit_level {
+            Self::Critical
+        } else if level <= low_level {
+            Self::Low
+        } else {
+            Self::Normal
+        }
+    }
+
+    pub fn icon_name(self) -> &'static str {
+        match self {
+            Self::Normal => "battery-good-symbolic",
+            Self::Low => "battery-low-symbolic",
+            Self::Critical => "battery-caution-symbolic",
+        }
+    }
+}
+
+/// Tracks the last state notified, so each threshold crossing is only
+/// notified once instead of on every battery report
+#[derive(Default)]
+pub struct BatteryAlerts {
+    notified: BatteryAlert,
+}
+
+impl BatteryAlerts {
+    /// Returns an alert if `level` has fallen to a more severe state than
+    /// last notified. Recovering, by charging, re-arms the lower thresholds.
+    pub fn update(&mut self, level: u8, low_level: u8, crit_level: u8) -> Option<BatteryAlert> {
+        let alert = BatteryAlert::for_level(level, low_level, crit_level);
+        let notify = alert > self.notified;
+        self.notified = alert;
+        if notify {
+            Some(alert)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(feature = "notifications")]
+pub fn notify(alert: BatteryAlert, level: u8) {
+    use notify_rust::{Notification, Urgency};
+
+    use crate::i18n::gettext;
+
+    let (summary, urgency) = match alert {
+        BatteryAlert::Normal => return,
+        BatteryAlert::Low => (N_!("Mouse Battery Low"), Urgency::Normal),
+        BatteryAlert::Critical => (N_!("Mouse Battery Critically Low"), Urgency::Critical),
+    };
+    let res = Notification::new()
+        .appname(&gettext("Mouse Configurator"))
+        .summary(&gettext(summary))
+        .body(&format!("{}% {}", level, gettext("remaining")))
+        .icon("battery-caution-symbolic")
+        .urgency(urgency)
+        .show();
+    if let Err(err) = res {
+        eprintln!("Failed to show battery notification: {}", err);
+    }
+}
+
+#[cfg(not(feature = "notifications"))]
+pub fn notify(_alert: BatteryAlert, _level: u8) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notify_once_per_crossing() {
+        let mut alerts = BatteryAlerts::default();
+        assert_eq!(alerts.update(50, 20, 5), None);
+        assert_eq!(alerts.update(20, 20, 5), Some(BatteryAlert::Low));
+        assert_eq!(alerts.update(15, 20, 5), None);
+        assert_eq!(alerts.update(5, 20, 5), Some(BatteryAlert::Critical));
+        assert_eq!(alerts.update(4, 20, 5), None);
+        // Charged, then drained again
+        assert_eq!(alerts.update(90, 20, 5), None);
+        assert_eq!(alerts.update(3, 20, 5), Some(BatteryAlert::Critical));
+    }
+
+    #[test]
+    fn test_icon_for_level() {
+        let icon = |level| BatteryAlert::for_level(level, 20, 5).icon_name();
+        assert_eq!(icon(80), "battery-good-symbolic");
+        assert_eq!(icon(20), "battery-low-symbolic");
+        assert_eq!(icon(5), "battery-caution-symbolic");
+    }
+}
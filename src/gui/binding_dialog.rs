@@ -1,25 +1,83 @@
-use gtk4::{glib, pango, prelude::*};
+use gtk4::{accessible::Property, glib, prelude::*};
 use relm4::{send, view, ComponentUpdate, Model, Sender, Widgets};
 use std::{cell::Cell, collections::HashMap, ptr, rc::Rc};
 
 use crate::{
-    bindings::{Category, Entry, HardwareButton, BINDINGS},
-    profile::Binding,
+    bindings::{
+        delay, describe_op, held_key_binding, repeat, shortcut_accelerator, shortcut_binding,
+        BindingRepr, Category, Entry, HardwareButton, MediaKeys, BINDINGS, SHORTCUT_KEYS,
+    },
+    command_binding,
+    config::Binding,
+    i18n::gettext,
+    keycode::*,
     util, AppMsg,
 };
+use mouse_configurator::{button::encode_action, Op, MAX_ACTION_LEN};
+
+const MAX_REPEAT: u8 = 20;
+
+fn too_long(sequence: &[Op], count: u8) -> bool {
+    encode_action(&repeat(sequence, count)).len() > MAX_ACTION_LEN
+}
 
 pub enum BindingDialogMsg {
-    Show(HardwareButton),
+    // With whether the device supports separate down and up actions, whether
+    // the button is enabled, and how media controls are sent
+    Show(HardwareButton, Option<Binding>, bool, bool, MediaKeys),
     #[allow(unused)]
     Hide,
     SelectCategory(Option<&'static Category>),
     Selected(&'static Entry),
+    ResetBinding,
+    CopyToButtons,
+    SetEnabled(bool),
+    EditSequence,
+    AddStep(Vec<Op>),
+    RemoveStep(usize),
+    MoveStep(usize, bool),
+    SetRepeat(u8),
+    SaveSequence,
+    EditCommand,
+    SaveCommand(String),
+    EditShortcut,
+    SetShortcutModifier(i8, bool),
+    SetShortcutKey(Option<i8>),
+    SaveShortcut,
+    #[cfg(feature = "test-binding")]
+    TestShortcut,
+    EditSingleKey,
+    SetSingleKey(Option<i8>),
+    SetSingleKeyHeld(bool),
+    SaveSingleKey,
+    EditDownUp,
+    SaveDownUp(Vec<Op>, Vec<Op>),
 }
 
 pub struct BindingDialogModel {
     button_id: HardwareButton,
+    enabled: bool,
     category: Option<&'static Category>,
     shown: bool,
+    editing_sequence: bool,
+    sequence: Vec<Op>,
+    sequence_changed: bool,
+    sequence_full: bool,
+    repeat: u8,
+    editing_command: bool,
+    command: String,
+    command_changed: bool,
+    editing_shortcut: bool,
+    shortcut_mods: i8,
+    shortcut_key: Option<i8>,
+    shortcut_changed: bool,
+    editing_single_key: bool,
+    single_key: Option<i8>,
+    // Hold the key down while the button is, so it auto-repeats
+    single_key_held: bool,
+    support_down_up: bool,
+    editing_down_up: bool,
+    media_keys: MediaKeys,
 }
 
 impl Model for BindingDialogModel {
@@ -32,8 +90,27 @@ impl ComponentUpdate<super::AppModel> for BindingDialogModel {
     fn init_model(_parent_model: &super::AppModel) -> Self {
         BindingDialogModel {
             button_id: HardwareButton::Right,
+            enabled: true,
             category: None,
             shown: false,
+            editing_sequence: false,
+            sequence: Vec::new(),
+            sequence_changed: false,
+            sequence_full: false,
+            repeat: 1,
+            editing_command: false,
+            command: String::new(),
+            command_changed: false,
+            editing_shortcut: false,
+            shortcut_mods: 0,
+            shortcut_key: None,
+            shortcut_changed: false,
+            editing_single_key: false,
+            single_key: None,
+            single_key_held: false,
+            support_down_up: false,
+            editing_down_up: false,
+            media_keys: MediaKeys::default(),
         }
     }
 
@@ -44,10 +121,39 @@ impl ComponentUpdate<super::AppModel> for BindingDialogModel {
         _sender: Sender<BindingDialogMsg>,
         parent_sender: Sender<AppMsg>,
     ) {
+        self.sequence_changed = false;
+        self.command_changed = false;
+        self.shortcut_changed = false;
+
         match msg {
-            BindingDialogMsg::Show(button_id) => {
+            BindingDialogMsg::Show(button_id, binding, support_down_up, enabled, media_keys) => {
                 self.button_id = button_id;
+                self.enabled = enabled;
                 self.category = None;
+                (self.sequence, self.repeat) = match &binding {
+                    Some(Binding::Custom(ops)) => (ops.clone(), 1),
+                    Some(Binding::Repeat { count, ops }) => (ops.clone(), *count),
+                    _ => (Vec::new(), 1),
+                };
+                self.command = match &binding {
+                    Some(Binding::Command { command }) => command.clone(),
+                    _ => String::new(),
+                };
+                self.editing_sequence = !self.sequence.is_empty();
+                self.editing_command = !self.command.is_empty();
+                self.sequence_changed = true;
+                self.command_changed = true;
+                self.editing_shortcut = false;
+                self.shortcut_mods = 0;
+                self.shortcut_key = None;
+                self.shortcut_changed = true;
+                self.editing_single_key = false;
+                self.single_key = None;
+                self.single_key_held = false;
+                self.support_down_up = support_down_up;
+                self.media_keys = media_keys;
+                self.editing_down_up = matches!(binding, Some(Binding::DownUp { .. }));
+                self.sequence_full = false;
                 self.shown = true;
             }
             BindingDialogMsg::Hide => {
@@ -55,6 +161,11 @@ impl ComponentUpdate<super::AppModel> for BindingDialogModel {
             }
             BindingDialogMsg::SelectCategory(category) => {
                 self.category = category;
+                self.editing_sequence = false;
+                self.editing_command = false;
+                self.editing_shortcut = false;
+                self.editing_single_key = false;
+                self.editing_down_up = false;
             }
             BindingDialogMsg::Selected(entry) => {
                 send!(
@@ -63,6 +174,135 @@ impl ComponentUpdate<super::AppModel> for BindingDialogModel {
                 );
                 self.shown = false;
             }
+            BindingDialogMsg::ResetBinding => {
+                send!(parent_sender, AppMsg::ResetBinding(self.button_id));
+                self.shown = false;
+            }
+            BindingDialogMsg::CopyToButtons => {
+                send!(parent_sender, AppMsg::ShowCopyBinding(self.button_id));
+                self.shown = false;
+            }
+            BindingDialogMsg::SetEnabled(enabled) => {
+                if enabled != self.enabled {
+                    self.enabled = enabled;
+                    send!(
+                        parent_sender,
+                        AppMsg::SetButtonEnabled(self.button_id, enabled)
+                    );
+                }
+            }
+            BindingDialogMsg::EditSequence => {
+                self.editing_sequence = true;
+            }
+            BindingDialogMsg::AddStep(ops) => {
+                let mut sequence = self.sequence.clone();
+                sequence.extend(ops);
+                self.sequence_full = too_long(&sequence, self.repeat);
+                if !self.sequence_full {
+                    self.sequence = sequence;
+                    self.sequence_changed = true;
+                }
+            }
+            BindingDialogMsg::RemoveStep(idx) => {
+                if idx < self.sequence.len() {
+                    self.sequence.remove(idx);
+                    self.sequence_changed = true;
+                    self.sequence_full = too_long(&self.sequence, self.repeat);
+                }
+            }
+            BindingDialogMsg::MoveStep(idx, up) => {
+                let other = if up {
+                    idx.checked_sub(1)
+                } else {
+                    Some(idx + 1)
+                };
+                if let Some(other) = other.filter(|x| *x < self.sequence.len()) {
+                    self.sequence.swap(idx, other);
+                    self.sequence_changed = true;
+                }
+            }
+            BindingDialogMsg::SetRepeat(repeat) => {
+                self.repeat = repeat.clamp(1, MAX_REPEAT);
+                self.sequence_full = too_long(&self.sequence, self.repeat);
+            }
+            BindingDialogMsg::SaveSequence => {
+                if !self.sequence.is_empty() && !self.sequence_full {
+                    let binding = Binding::repeat(self.sequence.clone(), self.repeat);
+                    send!(parent_sender, AppMsg::SetBinding(self.button_id, binding));
+                    self.shown = false;
+                }
+            }
+            BindingDialogMsg::EditCommand => {
+                self.editing_command = true;
+            }
+            BindingDialogMsg::SaveCommand(command) => {
+                let command = command.trim();
+                if !command.is_empty() {
+                    let binding = Binding::Command {
+                        command: command.to_string(),
+                    };
+                    send!(parent_sender, AppMsg::SetBinding(self.button_id, binding));
+                    self.shown = false;
+                }
+            }
+            BindingDialogMsg::EditShortcut => {
+                self.editing_shortcut = true;
+            }
+            BindingDialogMsg::SetShortcutModifier(mod_, active) => {
+                if active {
+                    self.shortcut_mods |= mod_;
+                } else {
+                    self.shortcut_mods &= !mod_;
+                }
+            }
+            BindingDialogMsg::SetShortcutKey(key) => {
+                self.shortcut_key = key;
+            }
+            BindingDialogMsg::SaveShortcut => {
+                // A shortcut needs a key; modifiers alone aren't enough
+                if let Some(key) = self.shortcut_key {
+                    let ops = shortcut_binding(self.shortcut_mods, key);
+                    let binding = BindingRepr::from_ops(&ops).into();
+                    send!(parent_sender, AppMsg::SetBinding(self.button_id, binding));
+                    self.shown = false;
+                }
+            }
+            #[cfg(feature = "test-binding")]
+            BindingDialogMsg::TestShortcut => {
+                if let Some(key) = self.shortcut_key {
+                    crate::uinput::test(&shortcut_binding(self.shortcut_mods, key));
+                }
+            }
+            BindingDialogMsg::EditSingleKey => {
+                self.editing_single_key = true;
+            }
+            BindingDialogMsg::SetSingleKey(key) => {
+                self.single_key = key.filter(|x| key_name(*x).is_some());
+            }
+            BindingDialogMsg::SetSingleKeyHeld(held) => {
+                self.single_key_held = held;
+            }
+            BindingDialogMsg::SaveSingleKey => {
+                if let Some(key) = self.single_key {
+                    // Holding needs separate down and up actions
+                    let binding = if self.single_key_held && self.support_down_up {
+                        let (down, up) = held_key_binding(key);
+                        Binding::DownUp { down, up }
+                    } else {
+                        BindingRepr::from_ops(&shortcut_binding(0, key)).into()
+                    };
+                    send!(parent_sender, AppMsg::SetBinding(self.button_id, binding));
+                    self.shown = false;
+                }
+            }
+            BindingDialogMsg::EditDownUp => {
+                self.editing_down_up = true;
+            }
+            BindingDialogMsg::SaveDownUp(down, up) => {
+                let binding = Binding::DownUp { down, up };
+                send!(parent_sender, AppMsg::SetBinding(self.button_id, binding));
+                self.shown = false;
+            }
         }
     }
 }
@@ -76,16 +316,44 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
             set_modal: true,
             set_hide_on_close: true,
             set_visible: watch!(model.shown),
-            set_title: Some("Set Binding"),
+            set_title: Some(&gettext("Set Binding")),
             set_titlebar = Some(&gtk4::HeaderBar) {
                 pack_start = &gtk4::Button {
                     add_css_class: "flat",
-                    set_visible: watch!(model.category.is_some()),
+                    set_visible: watch!(model.category.is_some() || model.editing_sequence || model.editing_command || model.editing_shortcut || model.editing_single_key || model.editing_down_up),
                     set_icon_name: "go-previous-symbolic",
+                    update_property: &[Property::Label(&gettext("Back"))],
                     connect_clicked(sender) => move |_| {
                         send!(sender, BindingDialogMsg::SelectCategory(None));
                     }
                 },
+                pack_start = &gtk4::Button {
+                    add_css_class: "flat",
+                    set_visible: watch!(model.category.is_none() && !(model.editing_sequence || model.editing_command || model.editing_shortcut || model.editing_single_key || model.editing_down_up)),
+                    set_icon_name: "edit-copy-symbolic",
+                    set_tooltip_text: Some(&gettext("Copy to Other Buttons")),
+                    update_property: &[Property::Label(&gettext("Copy to Other Buttons"))],
+                    connect_clicked(sender) => move |_| {
+                        send!(sender, BindingDialogMsg::CopyToButtons);
+                    }
+                },
+                pack_end = &gtk4::Button {
+                    set_label: &gettext("Reset to Default"),
+                    connect_clicked(sender) => move |_| {
+                        send!(sender, BindingDialogMsg::ResetBinding);
+                    }
+                },
+                // Disabling keeps the binding, to restore on enabling
+                pack_end = &gtk4::Switch {
+                    set_valign: gtk4::Align::Center,
+                    set_tooltip_text: Some(&gettext("Enabled")),
+                    update_property: &[Property::Label(&gettext("Enabled"))],
+                    set_active: watch!(model.enabled),
+                    connect_state_set(sender) => move |_, active| {
+                        send!(sender, BindingDialogMsg::SetEnabled(active));
+                        gtk4::Inhibit(false)
+                    }
+                },
             },
             set_child = Some(&gtk4::ScrolledWindow) {
                 set_hscrollbar_policy: gtk4::PolicyType::Never,
@@ -103,18 +371,27 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
                         add_css_class: "frame",
                         set_header_func: util::header_func,
                         connect_row_activated(sender) => move |_, row| {
-                            let category = categories[row.index() as usize];
-                            send!(sender, BindingDialogMsg::SelectCategory(Some(category)));
+                            let idx = row.index() as usize;
+                            if let Some(category) = categories.get(idx) {
+                                send!(sender, BindingDialogMsg::SelectCategory(Some(category)));
+                            } else if idx == categories.len() {
+                                send!(sender, BindingDialogMsg::EditShortcut);
+                            } else if idx == categories.len() + 1 {
+                                send!(sender, BindingDialogMsg::EditSingleKey);
+                            } else if idx == categories.len() + 2 {
+                                send!(sender, BindingDialogMsg::EditSequence);
+                            } else if idx == categories.len() + 3 {
+                                send!(sender, BindingDialogMsg::EditCommand);
+                            } else {
+                                send!(sender, BindingDialogMsg::EditDownUp);
+                            }
                         },
                     },
                     add_child: binding_vbox = &gtk4::Box {
                         set_orientation: gtk4::Orientation::Vertical,
                         set_spacing: 6,
-                        append = &gtk4::Label {
-                            set_label: watch! { model.category.map_or("", |x| &x.label) }, // XXX translate
-                            set_attributes = Some(&pango::AttrList) {
-                                insert: pango::AttrInt::new_weight(pango::Weight::Bold)
-                            },
+                        append = &util::heading("") -> gtk4::Label {
+                            set_label: watch! { &model.category.map_or_else(String::new, |x| x.label()) },
                         },
                         append: binding_list_box = &gtk4::ListBox {
                             set_hexpand: true,
@@ -124,12 +401,229 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
                                 let row_category = rows[row.index() as usize].0;
                                 ptr::eq(row_category, category.get())
                             },
-                            connect_row_activated(rows) => move |_, row| {
+                            connect_row_activated(sender, rows) => move |_, row| {
                                 let entry = rows[row.index() as usize].1;
                                 send!(sender, BindingDialogMsg::Selected(entry));
 
                             },
                         },
+                    },
+                    add_child: shortcut_vbox = &gtk4::Box {
+                        set_orientation: gtk4::Orientation::Vertical,
+                        set_spacing: 6,
+                        append = &util::heading(&gettext("Keyboard Shortcut")) -> gtk4::Label {},
+                        append = &gtk4::Box {
+                            set_orientation: gtk4::Orientation::Horizontal,
+                            set_spacing: 6,
+                            append = &gtk4::CheckButton {
+                                set_label: Some("Ctrl"),
+                                set_active: watch!(model.shortcut_mods & MOD_Ctrl != 0),
+                                connect_toggled(sender) => move |button| {
+                                    send!(sender, BindingDialogMsg::SetShortcutModifier(MOD_Ctrl, button.is_active()));
+                                },
+                            },
+                            append = &gtk4::CheckButton {
+                                set_label: Some("Alt"),
+                                set_active: watch!(model.shortcut_mods & MOD_Alt != 0),
+                                connect_toggled(sender) => move |button| {
+                                    send!(sender, BindingDialogMsg::SetShortcutModifier(MOD_Alt, button.is_active()));
+                                },
+                            },
+                            append = &gtk4::CheckButton {
+                                set_label: Some("Shift"),
+                                set_active: watch!(model.shortcut_mods & MOD_Shift != 0),
+                                connect_toggled(sender) => move |button| {
+                                    send!(sender, BindingDialogMsg::SetShortcutModifier(MOD_Shift, button.is_active()));
+                                },
+                            },
+                            append = &gtk4::CheckButton {
+                                set_label: Some("Super"),
+                                set_active: watch!(model.shortcut_mods & MOD_Super != 0),
+                                connect_toggled(sender) => move |button| {
+                                    send!(sender, BindingDialogMsg::SetShortcutModifier(MOD_Super, button.is_active()));
+                                },
+                            },
+                        },
+                        append: shortcut_key_dropdown = &gtk4::DropDown {
+                            set_model: Some(&gtk4::StringList::new(&shortcut_key_labels)),
+                            connect_selected_notify(sender) => move |dropdown| {
+                                let key = SHORTCUT_KEYS.get(dropdown.selected() as usize).map(|(_, key)| *key);
+                                send!(sender, BindingDialogMsg::SetShortcutKey(key));
+                            },
+                        },
+                        append = &gtk4::Label {
+                            add_css_class: "title-4",
+                            set_label: watch! { &shortcut_accelerator(model.shortcut_mods, model.shortcut_key) },
+                        },
+                        append: shortcut_buttons = &gtk4::Box {
+                            set_orientation: gtk4::Orientation::Horizontal,
+                            set_spacing: 6,
+                            set_homogeneous: true,
+                            set_sensitive: watch!(model.shortcut_key.is_some()),
+                            append = &gtk4::Button {
+                                set_label: &gettext("Save"),
+                                add_css_class: "suggested-action",
+                                connect_clicked(sender) => move |_| {
+                                    send!(sender, BindingDialogMsg::SaveShortcut);
+                                }
+                            },
+                        },
+                    },
+                    add_child: single_key_vbox = &gtk4::Box {
+                        set_orientation: gtk4::Orientation::Vertical,
+                        set_spacing: 6,
+                        append = &util::heading(&gettext("Single Key")) -> gtk4::Label {},
+                        append: single_key_dropdown = &gtk4::DropDown {
+                            set_model: Some(&gtk4::StringList::new(&single_key_labels)),
+                            set_expression: Some(&gtk4::PropertyExpression::new(
+                                gtk4::StringObject::static_type(),
+                                None::<&gtk4::Expression>,
+                                "string",
+                            )),
+                            set_enable_search: true,
+                            connect_selected_notify(sender, single_keys) => move |dropdown| {
+                                let key = single_keys.get(dropdown.selected() as usize).copied();
+                                send!(sender, BindingDialogMsg::SetSingleKey(key));
+                            },
+                        },
+                        append = &gtk4::CheckButton {
+                            set_label: Some(&gettext("Repeat while held")),
+                            set_sensitive: watch!(model.support_down_up),
+                            set_active: watch!(model.single_key_held && model.support_down_up),
+                            connect_toggled(sender) => move |button| {
+                                send!(sender, BindingDialogMsg::SetSingleKeyHeld(button.is_active()));
+                            },
+                        },
+                        append = &gtk4::Button {
+                            set_label: &gettext("Save"),
+                            add_css_class: "suggested-action",
+                            set_sensitive: watch!(model.single_key.is_some()),
+                            connect_clicked(sender) => move |_| {
+                                send!(sender, BindingDialogMsg::SaveSingleKey);
+                            }
+                        },
+                    },
+                    add_child: sequence_vbox = &gtk4::Box {
+                        set_orientation: gtk4::Orientation::Vertical,
+                        set_spacing: 6,
+                        append = &util::heading(&gettext("Custom Sequence")) -> gtk4::Label {},
+                        append: sequence_list_box = &gtk4::ListBox {
+                            set_hexpand: true,
+                            add_css_class: "frame",
+                            set_header_func: util::header_func,
+                        },
+                        append = &gtk4::Label {
+                            set_visible: watch!(model.sequence_full),
+                            set_label: &format!(
+                                "{} ({}: {} bytes)",
+                                gettext("Sequence is too long to store on the mouse."),
+                                gettext("Limit"),
+                                MAX_ACTION_LEN
+                            ),
+                        },
+                        append = &gtk4::Box {
+                            set_orientation: gtk4::Orientation::Horizontal,
+                            set_spacing: 6,
+                            append: step_dropdown = &gtk4::DropDown {
+                                set_hexpand: true,
+                                set_model: Some(&gtk4::StringList::new(&step_labels)),
+                            },
+                            append: add_step_button = &gtk4::Button {
+                                set_label: &gettext("Add"),
+                            },
+                        },
+                        append = &gtk4::Box {
+                            set_orientation: gtk4::Orientation::Horizontal,
+                            set_spacing: 6,
+                            append: delay_spin_button = &gtk4::SpinButton {
+                                set_hexpand: true,
+                                set_adjustment: &gtk4::Adjustment::new(100., 10., 5000., 10., 100., 0.),
+                            },
+                            append = &gtk4::Label {
+                                set_label: &gettext("ms"),
+                            },
+                            append: add_delay_button = &gtk4::Button {
+                                set_label: &gettext("Add Delay"),
+                            },
+                        },
+                        append = &gtk4::Box {
+                            set_orientation: gtk4::Orientation::Horizontal,
+                            set_spacing: 6,
+                            append = &gtk4::Label {
+                                set_label: &gettext("Repeat"),
+                            },
+                            append: repeat_spin_button = &gtk4::SpinButton {
+                                set_hexpand: true,
+                                set_adjustment: &gtk4::Adjustment::new(1., 1., MAX_REPEAT.into(), 1., 5., 0.),
+                                connect_value_changed(sender) => move |spin_button| {
+                                    send!(sender, BindingDialogMsg::SetRepeat(spin_button.value_as_int() as u8));
+                                },
+                            },
+                            append = &gtk4::Label {
+                                set_label: &gettext("times"),
+                            },
+                        },
+                        append = &gtk4::Button {
+                            set_label: &gettext("Save"),
+                            add_css_class: "suggested-action",
+                            set_sensitive: watch!(!model.sequence.is_empty() && !model.sequence_full),
+                            connect_clicked(sender) => move |_| {
+                                send!(sender, BindingDialogMsg::SaveSequence);
+                            }
+                        },
+                    },
+                    add_child: command_vbox = &gtk4::Box {
+                        set_orientation: gtk4::Orientation::Vertical,
+                        set_spacing: 6,
+                        append = &util::heading(&gettext("Run Command")) -> gtk4::Label {},
+                        append: command_entry = &gtk4::Entry {
+                            set_placeholder_text: Some(&gettext("Command")),
+                        },
+                        append = &gtk4::Label {
+                            set_label: &gettext("The command runs as your user whenever the button is pressed. Only enter commands you trust. Works only in GNOME, through a custom keyboard shortcut; in other desktops the button sends Ctrl+Alt+Super and a digit instead."),
+                            set_wrap: true,
+                            set_max_width_chars: 40,
+                        },
+                        append: save_command_button = &gtk4::Button {
+                            set_label: &gettext("Save"),
+                            add_css_class: "suggested-action",
+                        },
+                    },
+                    add_child: down_up_vbox = &gtk4::Box {
+                        set_orientation: gtk4::Orientation::Vertical,
+                        set_spacing: 6,
+                        append = &util::heading(&gettext("Press and Release")) -> gtk4::Label {},
+                        append = &gtk4::Label {
+                            set_label: &gettext("Separate actions when the button goes down and when it comes back up."),
+                            set_wrap: true,
+                            set_max_width_chars: 40,
+                        },
+                        append = &gtk4::Box {
+                            set_orientation: gtk4::Orientation::Horizontal,
+                            set_spacing: 6,
+                            append = &gtk4::Label {
+                                set_label: &gettext("Press"),
+                            },
+                            append: down_dropdown = &gtk4::DropDown {
+                                set_hexpand: true,
+                                set_model: Some(&gtk4::StringList::new(&step_labels)),
+                            },
+                        },
+                        append = &gtk4::Box {
+                            set_orientation: gtk4::Orientation::Horizontal,
+                            set_spacing: 6,
+                            append = &gtk4::Label {
+                                set_label: &gettext("Release"),
+                            },
+                            append: up_dropdown = &gtk4::DropDown {
+                                set_hexpand: true,
+                                set_model: Some(&gtk4::StringList::new(&step_labels)),
+                            },
+                        },
+                        append: save_down_up_button = &gtk4::Button {
+                            set_label: &gettext("Save"),
+                            add_css_class: "suggested-action",
+                        },
                     }
                 }
             }
@@ -138,6 +632,10 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
 
     additional_fields! {
         category: Rc<Cell<&'static Category>>,
+        down_up_row: gtk4::ListBoxRow,
+        // Rows of presets with another form, shown for the one `media_keys`
+        // selects
+        media_rows: Vec<(gtk4::ListBoxRow, &'static Entry)>,
     }
 
     fn pre_init() {
@@ -151,16 +649,39 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
         }
         let rows = Rc::new(rows);
 
+        // `Op::Kill` ends the action, so it can't be a step of a sequence
+        let step_entries: Vec<&'static Entry> = rows
+            .iter()
+            .map(|(_, entry)| *entry)
+            .filter(|entry| !entry.binding.contains(&Op::Kill))
+            .collect();
+        let step_entries = Rc::new(step_entries);
+        let step_labels: Vec<String> = step_entries.iter().map(|entry| entry.label()).collect();
+        let step_labels: Vec<&str> = step_labels.iter().map(String::as_str).collect();
+
+        let shortcut_key_labels: Vec<&str> = SHORTCUT_KEYS.iter().map(|(name, _)| *name).collect();
+
+        // Every keyboard page key, searchable by name
+        let (single_key_labels, single_keys): (Vec<&str>, Vec<i8>) = KEYCODES
+            .iter()
+            .filter_map(|(_, name, usage)| match usage {
+                Usage::Key(key) => Some((*name, *key)),
+                _ => None,
+            })
+            .unzip();
+
         let category = Rc::new(Cell::new(&BINDINGS[0]));
     }
 
     fn post_init() {
+        let mut media_rows = Vec::new();
         for category in &*BINDINGS {
+            let category_label = category.label();
             let mut rows = HashMap::<gtk4::ListBoxRow, &'static Entry>::new();
 
             view! {
                 category_row = gtk4::ListBoxRow {
-                    set_selectable: false,
+                    update_property: &[Property::Label(&category_label)],
                     set_child: hbox = Some(&gtk4::Box) {
                         set_margin_top: 6,
                         set_margin_bottom: 6,
@@ -169,7 +690,7 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
                         set_spacing: 12,
                         set_orientation: gtk4::Orientation::Horizontal,
                         append = &gtk4::Label {
-                            set_label: category.label, // TODO Translate?
+                            set_label: &category_label,
                         },
                         append = &gtk4::Image {
                             set_hexpand: true,
@@ -182,9 +703,9 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
             category_list_box.append(&category_row);
 
             for entry in &category.entries {
+                let entry_label = entry.label();
                 view! {
                     row = gtk4::ListBoxRow {
-                        set_selectable: false,
                         set_child: hbox = Some(&gtk4::Box) {
                             set_margin_top: 6,
                             set_margin_bottom: 6,
@@ -193,11 +714,16 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
                             set_spacing: 12,
                             set_orientation: gtk4::Orientation::Horizontal,
                             append = &gtk4::Label {
-                                set_label: entry.label, // TODO Translate?
+                                set_label: &entry_label,
                             }
                         }
                     }
                 }
+                let accessible_label = match entry.keybind {
+                    Some(keybind) => format!("{} ({})", entry_label, keybind),
+                    None => entry_label,
+                };
+                row.update_property(&[Property::Label(&accessible_label)]);
                 if let Some(keybind) = entry.keybind {
                     view! {
                         keybind_label = gtk4::Label {
@@ -208,11 +734,193 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
                     }
                     hbox.append(&keybind_label);
                 }
+                #[cfg(feature = "test-binding")]
+                if crate::uinput::can_test(&entry.binding) {
+                    let test_button = gtk4::Button::with_label(&gettext("Test"));
+                    test_button.set_hexpand(entry.keybind.is_none());
+                    test_button.set_halign(gtk4::Align::End);
+                    test_button.set_valign(gtk4::Align::Center);
+                    test_button.connect_clicked(move |_| {
+                        crate::uinput::test(&crate::bindings::device_ops(&entry.binding))
+                    });
+                    hbox.append(&test_button);
+                }
                 binding_list_box.append(&row);
+                if [MediaKeys::Consumer, MediaKeys::Keyboard]
+                    .iter()
+                    .any(|x| entry.id.with_media_keys(*x) != entry.id)
+                {
+                    media_rows.push((row.clone(), entry));
+                }
                 rows.insert(row, entry);
             }
         }
 
+        #[cfg(feature = "test-binding")]
+        {
+            let test_button = gtk4::Button::with_label(&gettext("Test"));
+            test_button.connect_clicked(glib::clone!(@strong sender => move |_| {
+                send!(sender, BindingDialogMsg::TestShortcut);
+            }));
+            shortcut_buttons.prepend(&test_button);
+        }
+
+        // Rows after the categories open the shortcut, single key, sequence, and
+        // command editors
+        view! {
+            shortcut_row = gtk4::ListBoxRow {
+                update_property: &[Property::Label(&gettext("Keyboard Shortcut"))],
+                set_child = Some(&gtk4::Box) {
+                    set_margin_top: 6,
+                    set_margin_bottom: 6,
+                    set_margin_start: 6,
+                    set_margin_end: 6,
+                    set_spacing: 12,
+                    set_orientation: gtk4::Orientation::Horizontal,
+                    append = &gtk4::Label {
+                        set_label: &gettext("Keyboard Shortcut"),
+                    },
+                    append = &gtk4::Image {
+                        set_hexpand: true,
+                        set_halign: gtk4::Align::End,
+                        set_icon_name: Some("go-next-symbolic"),
+                    }
+                }
+            }
+        }
+        category_list_box.append(&shortcut_row);
+
+        view! {
+            single_key_row = gtk4::ListBoxRow {
+                update_property: &[Property::Label(&gettext("Single Key"))],
+                set_child = Some(&gtk4::Box) {
+                    set_margin_top: 6,
+                    set_margin_bottom: 6,
+                    set_margin_start: 6,
+                    set_margin_end: 6,
+                    set_spacing: 12,
+                    set_orientation: gtk4::Orientation::Horizontal,
+                    append = &gtk4::Label {
+                        set_label: &gettext("Single Key"),
+                    },
+                    append = &gtk4::Image {
+                        set_hexpand: true,
+                        set_halign: gtk4::Align::End,
+                        set_icon_name: Some("go-next-symbolic"),
+                    }
+                }
+            }
+        }
+        category_list_box.append(&single_key_row);
+
+        view! {
+            sequence_row = gtk4::ListBoxRow {
+                update_property: &[Property::Label(&gettext("Custom Sequence"))],
+                set_child = Some(&gtk4::Box) {
+                    set_margin_top: 6,
+                    set_margin_bottom: 6,
+                    set_margin_start: 6,
+                    set_margin_end: 6,
+                    set_spacing: 12,
+                    set_orientation: gtk4::Orientation::Horizontal,
+                    append = &gtk4::Label {
+                        set_label: &gettext("Custom Sequence"),
+                    },
+                    append = &gtk4::Image {
+                        set_hexpand: true,
+                        set_halign: gtk4::Align::End,
+                        set_icon_name: Some("go-next-symbolic"),
+                    }
+                }
+            }
+        }
+        category_list_box.append(&sequence_row);
+
+        view! {
+            command_row = gtk4::ListBoxRow {
+                update_property: &[Property::Label(&gettext("Run Command"))],
+                set_child = Some(&gtk4::Box) {
+                    set_margin_top: 6,
+                    set_margin_bottom: 6,
+                    set_margin_start: 6,
+                    set_margin_end: 6,
+                    set_spacing: 12,
+                    set_orientation: gtk4::Orientation::Horizontal,
+                    append = &gtk4::Label {
+                        set_label: &gettext("Run Command"),
+                    },
+                    append = &gtk4::Image {
+                        set_hexpand: true,
+                        set_halign: gtk4::Align::End,
+                        set_icon_name: Some("go-next-symbolic"),
+                    }
+                }
+            }
+        }
+        category_list_box.append(&command_row);
+        if !command_binding::available() {
+            command_row.set_sensitive(false);
+            command_row
+                .set_tooltip_text(Some(&gettext("Requires GNOME custom keyboard shortcuts")));
+        }
+
+        view! {
+            down_up_row = gtk4::ListBoxRow {
+                update_property: &[Property::Label(&gettext("Press and Release"))],
+                set_child = Some(&gtk4::Box) {
+                    set_margin_top: 6,
+                    set_margin_bottom: 6,
+                    set_margin_start: 6,
+                    set_margin_end: 6,
+                    set_spacing: 12,
+                    set_orientation: gtk4::Orientation::Horizontal,
+                    append = &gtk4::Label {
+                        set_label: &gettext("Press and Release"),
+                    },
+                    append = &gtk4::Image {
+                        set_hexpand: true,
+                        set_halign: gtk4::Align::End,
+                        set_icon_name: Some("go-next-symbolic"),
+                    }
+                }
+            }
+        }
+        category_list_box.append(&down_up_row);
+
+        save_down_up_button.connect_clicked(
+            glib::clone!(@strong sender, @strong step_entries, @strong down_dropdown, @strong up_dropdown => move |_| {
+                let entries = (
+                    step_entries.get(down_dropdown.selected() as usize),
+                    step_entries.get(up_dropdown.selected() as usize),
+                );
+                if let (Some(down), Some(up)) = entries {
+                    send!(sender, BindingDialogMsg::SaveDownUp(down.binding.clone(), up.binding.clone()));
+                }
+            }),
+        );
+
+        let save_command = glib::clone!(@strong sender, @strong command_entry => move || {
+            let command = command_entry.text().to_string();
+            send!(sender, BindingDialogMsg::SaveCommand(command));
+        });
+        save_command_button
+            .connect_clicked(glib::clone!(@strong save_command => move |_| save_command()));
+        command_entry.connect_activate(move |_| save_command());
+
+        add_step_button.connect_clicked(
+            glib::clone!(@strong sender, @strong step_dropdown => move |_| {
+                if let Some(entry) = step_entries.get(step_dropdown.selected() as usize) {
+                    send!(sender, BindingDialogMsg::AddStep(entry.binding.clone()));
+                }
+            }),
+        );
+        add_delay_button.connect_clicked(
+            glib::clone!(@strong sender, @strong delay_spin_button => move |_| {
+                let ms = delay_spin_button.value_as_int() as i16;
+                send!(sender, BindingDialogMsg::AddStep(vec![delay(ms)]));
+            }),
+        );
+
         // Avoid transition on reopening
         dialog.connect_visible_notify(
             glib::clone!(@strong stack, @strong category_list_box => move |dialog| {
@@ -226,14 +934,102 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
     }
 
     fn post_view() {
-        if let Some(category) = model.category.as_ref() {
-            self.stack.set_visible_child(&self.binding_vbox);
+        if model.command_changed {
+            self.command_entry.set_text(&model.command);
+        }
+        if model.shortcut_changed {
+            self.shortcut_key_dropdown
+                .set_selected(gtk4::INVALID_LIST_POSITION);
+            self.single_key_dropdown
+                .set_selected(gtk4::INVALID_LIST_POSITION);
+        }
+        for (row, entry) in &self.media_rows {
+            row.set_visible(entry.id.with_media_keys(model.media_keys) == entry.id);
+        }
+        // Only offered if the device supports it, but kept for editing
+        self.down_up_row
+            .set_visible(model.support_down_up || model.editing_down_up);
+
+        let page: gtk4::Widget = if model.editing_command {
+            self.command_vbox.clone().upcast()
+        } else if model.editing_shortcut {
+            self.shortcut_vbox.clone().upcast()
+        } else if model.editing_single_key {
+            self.single_key_vbox.clone().upcast()
+        } else if model.editing_down_up {
+            self.down_up_vbox.clone().upcast()
+        } else if model.editing_sequence {
+            self.sequence_vbox.clone().upcast()
+        } else if let Some(category) = model.category.as_ref() {
             if !ptr::eq(self.category.get(), *category) {
                 self.category.set(*category);
                 self.binding_list_box.invalidate_filter();
             }
+            self.binding_vbox.clone().upcast()
         } else {
-            self.stack.set_visible_child(&self.category_list_box);
+            self.category_list_box.clone().upcast()
+        };
+        if self.stack.visible_child().as_ref() != Some(&page) {
+            self.stack.set_visible_child(&page);
+            // Move focus to the first row, so Up/Down and Enter work from here
+            page.child_focus(gtk4::DirectionType::TabForward);
+        }
+
+        if model.sequence_changed {
+            self.repeat_spin_button.set_value(model.repeat.into());
+            while let Some(row) = self.sequence_list_box.first_child() {
+                self.sequence_list_box.remove(&row);
+            }
+
+            let len = model.sequence.len();
+            for (i, op) in model.sequence.iter().enumerate() {
+                view! {
+                    row = gtk4::ListBoxRow {
+                        set_selectable: false,
+                        set_activatable: false,
+                        set_child = Some(&gtk4::Box) {
+                            set_margin_top: 6,
+                            set_margin_bottom: 6,
+                            set_margin_start: 6,
+                            set_margin_end: 6,
+                            set_spacing: 6,
+                            set_orientation: gtk4::Orientation::Horizontal,
+                            append = &gtk4::Label {
+                                set_label: &describe_op(op),
+                                set_hexpand: true,
+                                set_halign: gtk4::Align::Start,
+                            },
+                            append = &gtk4::Button {
+                                add_css_class: "flat",
+                                set_icon_name: "go-up-symbolic",
+                                update_property: &[Property::Label(&gettext("Move Up"))],
+                                set_sensitive: i > 0,
+                                connect_clicked(sender) => move |_| {
+                                    send!(sender, BindingDialogMsg::MoveStep(i, true));
+                                }
+                            },
+                            append = &gtk4::Button {
+                                add_css_class: "flat",
+                                set_icon_name: "go-down-symbolic",
+                                update_property: &[Property::Label(&gettext("Move Down"))],
+                                set_sensitive: i + 1 < len,
+                                connect_clicked(sender) => move |_| {
+                                    send!(sender, BindingDialogMsg::MoveStep(i, false));
+                                }
+                            },
+                            append = &gtk4::Button {
+                                add_css_class: "flat",
+                                set_icon_name: "list-remove-symbolic",
+                                update_property: &[Property::Label(&gettext("Remove"))],
+                                connect_clicked(sender) => move |_| {
+                                    send!(sender, BindingDialogMsg::RemoveStep(i));
+                                }
+                            }
+                        }
+                    }
+                }
+                self.sequence_list_box.append(&row);
+            }
         }
     }
 }
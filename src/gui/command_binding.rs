@@ -0,0 +1,103 @@
+//! Bindings that run a shell command, in GNOME only. The mouse can only send
+//! input events, so the button is programmed with a reserved key
+//! combination, and a GNOME custom keyboard shortcut for that combination
+//! runs the command. The shortcut is handled by gnome-settings-daemon, so it
+//! keeps working when this program isn't running, including after
+//! `hp-mouse-daemon` applies the configuration. Nothing else handles the
+//! combination, so in other desktops the button only sends it.
+//!
+//! The command runs with the user's privileges, whenever the button is
+//! pressed, on any host the mouse is paired with that has the shortcut. It is
+//! stored in plain text in the config file and in GNOME's settings. Imported
+//! configurations have command bindings stripped, so one can only be created
+//! explicitly in the binding dialog.
+
+use gtk4::{gio, prelude::*};
+
+use crate::bindings::HardwareButton;
+
+const MEDIA_KEYS_SCHEMA: &str = "org.gnome.settings-daemon.plugins.media-keys";
+const CUSTOM_KEYBINDING_SCHEMA: &str =
+    "org.gnome.settings-daemon.plugins.media-keys.custom-keybinding";
+const CUSTOM_KEYBINDING_PATH: &str =
+    "/org/gnome/settings-daemon/plugins/media-keys/custom-keybindings";
+
+fn accelerator(button: HardwareButton) -> String {
+    format!("<Primary><Alt><Super>{}", button as u8 + 1)
+}
+
+fn keybinding_path(button: HardwareButton) -> String {
+    format!(
+        "{}/mouse-configurator-{}/",
+        CUSTOM_KEYBINDING_PATH, button as u8
+    )
+}
+
+// `gio::Settings::new` aborts if the schema isn't installed
+fn media_keys_settings() -> Option<gio::Settings> {
+    let source = gio::SettingsSchemaSource::default()?;
+    source.lookup(MEDIA_KEYS_SCHEMA, true)?;
+    source.lookup(CUSTOM_KEYBINDING_SCHEMA, true)?;
+    Some(gio::Settings::new(MEDIA_KEYS_SCHEMA))
+}
+
+/// Whether GNOME custom shortcuts are installed, so a command binding can
+/// work
+pub fn available() -> bool {
+    media_keys_settings().is_some()
+}
+
+fn set_keybinding_listed(settings: &gio::Settings, path: &str, listed: bool) {
+    let mut paths: Vec<String> = settings
+        .strv("custom-keybindings")
+        .iter()
+        .map(|x| x.to_string())
+        .filter(|x| x != path)
+        .collect();
+    if listed {
+        paths.push(path.to_string());
+    }
+    let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+    if let Err(err) = settings.set_strv("custom-keybindings", &paths) {
+        eprintln!("Failed to set custom keybindings: {}", err);
+    }
+}
+
+pub fn register(button: HardwareButton, command: &str) {
+    let settings = match media_keys_settings() {
+        Some(settings) => settings,
+        None => {
+            eprintln!("GNOME custom shortcuts unavailable; command binding will do nothing");
+            return;
+        }
+    };
+
+    let path = keybinding_path(button);
+    let keybinding = gio::Settings::with_path(CUSTOM_KEYBINDING_SCHEMA, &path);
+    let res = keybinding
+        .set_string("name", &format!("Mouse Configurator: {:?} Button", button))
+        .and_then(|_| keybinding.set_string("command", command))
+        .and_then(|_| keybinding.set_string("binding", &accelerator(button)));
+    if let Err(err) = res {
+        eprintln!("Failed to set custom shortcut: {}", err);
+        return;
+    }
+    set_keybinding_listed(&settings, &path, true);
+}
+
+pub fn unregister(button: HardwareButton) {
+    if let Some(settings) = media_keys_settings() {
+        let path = keybinding_path(button);
+        if settings
+            .strv("custom-keybindings")
+            .iter()
+            .any(|x| x.as_str() == path)
+        {
+            let keybinding = gio::Settings::with_path(CUSTOM_KEYBINDING_SCHEMA, &path);
+            for key in ["name", "command", "binding"] {
+                keybinding.reset(key);
+            }
+            set_keybinding_listed(&settings, &path, false);
+        }
+    }
+}
@@ -16,7 +16,7 @@ use std::{
     io::{self, IoSlice, IoSliceMut},
     os::unix::{
         ffi::OsStrExt,
-        io::{AsRawFd, FromRawFd, RawFd},
+        io::{FromRawFd, RawFd},
     },
     path::{Path, PathBuf},
     process::{Command, Stdio},
@@ -119,8 +119,13 @@ pub fn device_monitor_process() {
     for device_info in current_devices.into_iter().chain(monitor_devices) {
         match device_info.open() {
             Ok(device) => {
+                // Opened through hidraw, which always has one
+                let fd = match device.raw_fd() {
+                    Some(fd) => fd,
+                    None => continue,
+                };
                 let path = device_info.devnode.as_os_str().as_bytes();
-                let fds = &[device.as_raw_fd()];
+                let fds = &[fd];
                 let iov = &[IoSlice::new(path)];
                 let cmsgs = &[ControlMessage::ScmRights(fds)];
                 loop {
@@ -2,7 +2,8 @@ use gtk4::prelude::*;
 use relm4::view;
 use std::path::PathBuf;
 
-use crate::util;
+use crate::{bindings::HardwareButton, i18n::gettext, util};
+use mouse_configurator::FirmwareVersion;
 
 pub fn show_about_dialog(main_window: &gtk4::ApplicationWindow) {
     gtk4::AboutDialog::builder()
@@ -16,7 +17,7 @@ pub fn show_about_dialog(main_window: &gtk4::ApplicationWindow) {
         .show()
 }
 
-fn device_to_model(device: &str) -> &str {
+pub fn device_to_model(device: &str) -> &str {
     if device == "Brain" {
         "HP 930 series Creator Wireless Mouse"
     } else {
@@ -24,17 +25,26 @@ fn device_to_model(device: &str) -> &str {
     }
 }
 
+pub fn connection_label(connected: bool) -> String {
+    if connected {
+        gettext("Connected")
+    } else {
+        gettext("Not Connected")
+    }
+}
+
 pub fn show_info_dialog(
     main_window: &gtk4::ApplicationWindow,
     device: &str,
     serial: &str,
-    firmware_version: Option<(u16, u16, u16)>,
+    firmware_version: Option<FirmwareVersion>,
+    connected: bool,
 ) {
     view! {
         dialog = gtk4::Dialog {
             set_transient_for: Some(main_window),
             set_modal: true,
-            set_title: Some("About This Mouse"),
+            set_title: Some(&gettext("About This Mouse")),
             set_child = Some(&gtk4::ListBox) {
                 set_margin_start: 12,
                 set_margin_end: 12,
@@ -53,7 +63,7 @@ pub fn show_info_dialog(
                         set_spacing: 12,
                         set_orientation: gtk4::Orientation::Horizontal,
                         append = &gtk4::Label {
-                            set_label: "Model"
+                            set_label: &gettext("Model")
                         },
                         append = &gtk4::Label {
                             set_label: device_to_model(device),
@@ -62,6 +72,26 @@ pub fn show_info_dialog(
                         }
                     }
                 },
+                append = &gtk4::ListBoxRow {
+                    set_margin_start: 6,
+                    set_margin_end: 6,
+                    set_margin_top: 6,
+                    set_margin_bottom: 6,
+                    set_selectable: false,
+                    set_activatable: false,
+                    set_child = Some(&gtk4::Box) {
+                        set_spacing: 12,
+                        set_orientation: gtk4::Orientation::Horizontal,
+                        append = &gtk4::Label {
+                            set_label: &gettext("Connection")
+                        },
+                        append = &gtk4::Label {
+                            set_label: &connection_label(connected),
+                            set_hexpand: true,
+                            set_halign: gtk4::Align::End,
+                        }
+                    }
+                },
                 append = &gtk4::ListBoxRow {
                     set_visible: firmware_version.is_some(),
                     set_margin_start: 6,
@@ -74,10 +104,10 @@ pub fn show_info_dialog(
                         set_spacing: 12,
                         set_orientation: gtk4::Orientation::Horizontal,
                         append = &gtk4::Label {
-                            set_label: "Firmware Version"
+                            set_label: &gettext("Firmware Version")
                         },
                         append = &gtk4::Label {
-                            set_label: &firmware_version.map_or_else(String::new, |(a, b, c)| format!("{}.{}.{}", a, b, c)),
+                            set_label: &firmware_version.map_or_else(String::new, |x| x.to_string()),
                             set_hexpand: true,
                             set_halign: gtk4::Align::End,
                         }
@@ -94,7 +124,7 @@ pub fn show_info_dialog(
                         set_spacing: 12,
                         set_orientation: gtk4::Orientation::Horizontal,
                         append = &gtk4::Label {
-                            set_label: "Unique ID"
+                            set_label: &gettext("Unique ID")
                         },
                         append = &gtk4::Label {
                             set_label: serial,
@@ -130,6 +160,102 @@ pub fn show_prompt_dialog(
     dialog.show();
 }
 
+/// Lists `changes`, and calls `cb` with whether the user accepted them
+pub fn show_changes_dialog(
+    main_window: &gtk4::ApplicationWindow,
+    changes: &[String],
+    cb: impl Fn(bool) + 'static,
+) {
+    let dialog = gtk4::MessageDialog::builder()
+        .transient_for(main_window)
+        .modal(true)
+        .message_type(gtk4::MessageType::Question)
+        .buttons(gtk4::ButtonsType::OkCancel)
+        .text(&gettext("Apply these changes to the mouse?"))
+        .secondary_text(&changes.join("\n"))
+        .build();
+    dialog.connect_response(move |dialog, response| {
+        cb(response == gtk4::ResponseType::Ok);
+        dialog.close();
+    });
+    dialog.show();
+}
+
+/// Which bindings to keep when the device's differ from the saved profile
+pub enum Reconcile {
+    ApplySaved,
+    AdoptDevice,
+    Cancel,
+}
+
+/// Lists `changes` from the device's bindings to the saved ones, and calls
+/// `cb` with which to keep
+pub fn show_reconcile_dialog(
+    main_window: &gtk4::ApplicationWindow,
+    changes: &[String],
+    cb: impl Fn(Reconcile) + 'static,
+) {
+    let dialog = gtk4::MessageDialog::builder()
+        .transient_for(main_window)
+        .modal(true)
+        .message_type(gtk4::MessageType::Question)
+        .text(&gettext("Device differs from saved profile"))
+        .secondary_text(&changes.join("\n"))
+        .build();
+    dialog.add_button(&gettext("Cancel"), gtk4::ResponseType::Cancel);
+    dialog.add_button(&gettext("Adopt Device"), gtk4::ResponseType::Reject);
+    dialog.add_button(&gettext("Apply Saved"), gtk4::ResponseType::Accept);
+    dialog.set_default_response(gtk4::ResponseType::Cancel);
+    dialog.connect_response(move |dialog, response| {
+        cb(match response {
+            gtk4::ResponseType::Accept => Reconcile::ApplySaved,
+            gtk4::ResponseType::Reject => Reconcile::AdoptDevice,
+            _ => Reconcile::Cancel,
+        });
+        dialog.close();
+    });
+    dialog.show();
+}
+
+/// Offers `buttons` to copy the binding of `from` to, and calls `cb` with
+/// the chosen ones
+pub fn show_copy_binding_dialog(
+    main_window: &gtk4::ApplicationWindow,
+    from: HardwareButton,
+    buttons: &[HardwareButton],
+    cb: impl Fn(Vec<HardwareButton>) + 'static,
+) {
+    let dialog = gtk4::MessageDialog::builder()
+        .transient_for(main_window)
+        .modal(true)
+        .message_type(gtk4::MessageType::Question)
+        .buttons(gtk4::ButtonsType::OkCancel)
+        .text(&format!("{}: {}", gettext("Copy Binding"), from.label()))
+        .secondary_text(&gettext("Buttons to give the same binding"))
+        .build();
+    let message_area = dialog.message_area().downcast::<gtk4::Box>().unwrap();
+    let checks: Vec<_> = buttons
+        .iter()
+        .map(|button| {
+            let check = gtk4::CheckButton::with_label(&button.label());
+            message_area.append(&check);
+            (*button, check)
+        })
+        .collect();
+    dialog.connect_response(move |dialog, response| {
+        if response == gtk4::ResponseType::Ok {
+            let chosen = checks
+                .iter()
+                .filter(|(_, check)| check.is_active())
+                .map(|(button, _)| *button)
+                .collect();
+            cb(chosen);
+        }
+        dialog.close();
+    });
+    dialog.show();
+}
+
 pub fn show_error_dialog(main_window: &gtk4::ApplicationWindow, text: &str) {
     let dialog = gtk4::MessageDialog::builder()
         .transient_for(main_window)
@@ -142,38 +268,60 @@ pub fn show_error_dialog(main_window: &gtk4::ApplicationWindow, text: &str) {
     dialog.show();
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum FileDialog {
+    Import,
+    Export,
+    ExportCheatsheet,
+    RecordDiagnostics,
+}
+
 fn show_file_dialog(
     main_window: &gtk4::ApplicationWindow,
     cb: impl Fn(PathBuf) + 'static,
-    export: bool,
+    kind: FileDialog,
 ) {
-    let (title, accept_label, action) = if export {
-        (
-            "Export Configuration",
-            "_Export",
-            gtk4::FileChooserAction::Save,
-        )
-    } else {
-        (
-            "Import Configuration",
-            "_Import",
+    let (title, accept_label, action) = match kind {
+        FileDialog::Import => (
+            gettext("Import Configuration"),
+            gettext("_Import"),
             gtk4::FileChooserAction::Open,
-        )
+        ),
+        FileDialog::Export => (
+            gettext("Export Configuration"),
+            gettext("_Export"),
+            gtk4::FileChooserAction::Save,
+        ),
+        FileDialog::ExportCheatsheet => (
+            gettext("Export Cheat Sheet"),
+            gettext("_Export"),
+            gtk4::FileChooserAction::Save,
+        ),
+        FileDialog::RecordDiagnostics => (
+            gettext("Record Diagnostics"),
+            gettext("_Record"),
+            gtk4::FileChooserAction::Save,
+        ),
+    };
+    let (filter_name, extension) = match kind {
+        FileDialog::ExportCheatsheet => ("Markdown", "md"),
+        FileDialog::RecordDiagnostics => ("Text", "txt"),
+        _ => ("json", "json"),
     };
 
     let dialog = gtk4::FileChooserDialog::new(
-        Some(title),
+        Some(&title),
         Some(main_window),
         action,
         &[
-            ("_Cancel", gtk4::ResponseType::Cancel),
-            (accept_label, gtk4::ResponseType::Ok),
+            (&gettext("_Cancel"), gtk4::ResponseType::Cancel),
+            (&accept_label, gtk4::ResponseType::Ok),
         ],
     );
 
     let filter = gtk4::FileFilter::new();
-    filter.set_name(Some("json"));
-    filter.add_pattern("*.json");
+    filter.set_name(Some(filter_name));
+    filter.add_pattern(&format!("*.{}", extension));
     dialog.add_filter(&filter);
 
     dialog
@@ -184,8 +332,8 @@ fn show_file_dialog(
         .set_show_title_buttons(false);
     dialog.set_modal(true);
     dialog.set_margin_start(6);
-    if export {
-        dialog.set_current_name("Untitled.json");
+    if kind != FileDialog::Import {
+        dialog.set_current_name(&format!("Untitled.{}", extension));
     }
 
     dialog.connect_response(move |dialog, response| {
@@ -201,9 +349,23 @@ fn show_file_dialog(
 }
 
 pub fn show_import_dialog(main_window: &gtk4::ApplicationWindow, cb: impl Fn(PathBuf) + 'static) {
-    show_file_dialog(main_window, cb, false);
+    show_file_dialog(main_window, cb, FileDialog::Import);
 }
 
 pub fn show_export_dialog(main_window: &gtk4::ApplicationWindow, cb: impl Fn(PathBuf) + 'static) {
-    show_file_dialog(main_window, cb, true);
+    show_file_dialog(main_window, cb, FileDialog::Export);
+}
+
+pub fn show_export_cheatsheet_dialog(
+    main_window: &gtk4::ApplicationWindow,
+    cb: impl Fn(PathBuf) + 'static,
+) {
+    show_file_dialog(main_window, cb, FileDialog::ExportCheatsheet);
+}
+
+pub fn show_record_diagnostics_dialog(
+    main_window: &gtk4::ApplicationWindow,
+    cb: impl Fn(PathBuf) + 'static,
+) {
+    show_file_dialog(main_window, cb, FileDialog::RecordDiagnostics);
 }
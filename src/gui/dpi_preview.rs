@@ -0,0 +1,100 @@
+//! Area to move the pointer over, to try out the sensitivity setting without
+//! leaving the window. Shows the recent path of the pointer and how far it
+//! has moved. This uses the pointer position GTK reports, so desktop pointer
+//! acceleration also applies.
+
+use gtk4::{glib, prelude::*};
+
+use crate::i18n::gettext;
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+const TRAIL_LEN: usize = 64;
+
+#[derive(Default)]
+struct Motion {
+    trail: VecDeque<(f64, f64)>,
+    distance: f64,
+}
+
+impl Motion {
+    fn add(&mut self, x: f64, y: f64) {
+        if let Some((last_x, last_y)) = self.trail.back() {
+            self.distance += (x - last_x).hypot(y - last_y);
+        }
+        if self.trail.len() == TRAIL_LEN {
+            self.trail.pop_front();
+        }
+        self.trail.push_back((x, y));
+    }
+}
+
+fn distance_label(distance: f64) -> String {
+    format!("{}: {}", gettext("Pixels moved"), distance.round())
+}
+
+pub fn dpi_preview() -> gtk4::Box {
+    let motion = Rc::new(RefCell::new(Motion::default()));
+
+    let area = gtk4::DrawingArea::new();
+    area.set_content_height(120);
+    area.set_hexpand(true);
+    area.add_css_class("view");
+    area.set_draw_func(glib::clone!(@strong motion => move |area, cr, _, _| {
+        let color = area.style_context().color();
+        cr.set_source_rgba(
+            color.red().into(),
+            color.green().into(),
+            color.blue().into(),
+            color.alpha().into(),
+        );
+        cr.set_line_width(2.);
+        let motion = motion.borrow();
+        let mut points = motion.trail.iter();
+        if let Some((x, y)) = points.next() {
+            cr.move_to(*x, *y);
+            for (x, y) in points {
+                cr.line_to(*x, *y);
+            }
+            let _ = cr.stroke();
+        }
+    }));
+
+    let label = gtk4::Label::new(Some(&distance_label(0.)));
+    label.set_halign(gtk4::Align::Start);
+    label.set_hexpand(true);
+
+    let controller = gtk4::EventControllerMotion::new();
+    controller.connect_motion(
+        glib::clone!(@strong motion, @weak area, @weak label => move |_, x, y| {
+            let mut motion = motion.borrow_mut();
+            motion.add(x, y);
+            label.set_label(&distance_label(motion.distance));
+            area.queue_draw();
+        }),
+    );
+    controller.connect_leave(glib::clone!(@strong motion, @weak area => move |_| {
+        // Keep the distance, but don't draw a line to where the pointer
+        // enters again
+        motion.borrow_mut().trail.clear();
+        area.queue_draw();
+    }));
+    area.add_controller(&controller);
+
+    let reset_button = gtk4::Button::with_label(&gettext("Reset"));
+    reset_button.connect_clicked(
+        glib::clone!(@strong motion, @weak area, @weak label => move |_| {
+            *motion.borrow_mut() = Motion::default();
+            label.set_label(&distance_label(0.));
+            area.queue_draw();
+        }),
+    );
+
+    let footer = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+    footer.append(&label);
+    footer.append(&reset_button);
+
+    let vbox = gtk4::Box::new(gtk4::Orientation::Vertical, 6);
+    vbox.append(&area);
+    vbox.append(&footer);
+    vbox
+}
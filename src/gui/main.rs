@@ -1,34 +1,61 @@
-use gtk4::{gdk, gdk_pixbuf, gio, glib, pango, prelude::*};
+use gtk4::{accessible::Property, gdk, gdk_pixbuf, gio, glib, pango, prelude::*};
 use relm4::{
     actions::{RelmAction, RelmActionGroup},
     send, view, AppUpdate, Model, RelmApp, RelmComponent, RelmWorker, Sender, Widgets,
 };
-use std::{collections::HashMap, env, path::PathBuf, process::Command};
+use std::{
+    collections::HashMap,
+    env, mem,
+    path::PathBuf,
+    process::Command,
+    time::{Duration, Instant},
+};
 
-use mouse_configurator::Event;
+#[macro_use]
+extern crate mouse_configurator;
+use mouse_configurator::app::{bindings, config, i18n, keycode};
+use mouse_configurator::{
+    Button, Capabilities, DeviceProfile, Event, FirmwareVersion, PressType, Recording,
+};
 
-mod bindings;
-use bindings::HardwareButton;
+use i18n::gettext;
+mod battery;
+use bindings::{HardwareButton, MediaKeys};
 mod binding_dialog;
 use binding_dialog::{BindingDialogModel, BindingDialogMsg};
 mod buttons_widget;
+mod command_binding;
 use buttons_widget::{ButtonsWidget, BUTTONS, IMAGE_WIDTH};
+use config::{load_config, save_config, Binding, Change, MouseConfig, Profile};
 mod device_monitor_process;
 use device_monitor_process::DeviceMonitorProcess;
 mod dialogs;
 use dialogs::*;
-mod keycode;
+mod dpi_preview;
 mod profile;
-use profile::{
-    apply_profile_diff, load_config, save_config, Binding, MouseConfig, MouseState, Profile,
-};
+use profile::{apply_profile_diff, MouseState};
 mod swap_button_dialog;
 use swap_button_dialog::{SwapButtonDialogModel, SwapButtonDialogMsg};
+#[cfg(feature = "test-binding")]
+mod uinput;
 mod util;
 mod worker;
 use worker::{DeviceId, WorkerModel, WorkerMsg};
 
 const DPI_STEP: f64 = 50.;
+// Most DPI stages for a device without them in firmware, where switching is
+// only done here, so the limit is only to keep the list short
+const MAX_DPI_STAGES: usize = 6;
+// Battery is queried this often, in case the mouse doesn't report it itself
+const BATTERY_REFRESH_SECONDS: u32 = 60;
+// The mouse doesn't report DPI changes made with its own buttons, so the
+// shown DPI is queried this often
+const DPI_REFRESH_SECONDS: u32 = 5;
+// DPI is written this long after the last change, so dragging the slider
+// doesn't flood the device with writes
+const DPI_APPLY_DELAY: Duration = Duration::from_millis(250);
+// Longest to wait on close for the device to confirm written bindings
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
 
 #[derive(relm4::Components)]
 struct AppComponents {
@@ -45,10 +72,69 @@ struct Device {
 }
 
 impl Device {
+    // Connection, host channel and firmware version, once reported, for the
+    // device page
+    fn status_label(&self) -> String {
+        let mut label = connection_label(self.state.connected);
+        if !self.state.connected {
+            return label;
+        }
+        if let Some(host_id) = self.state.host_id {
+            // Numbered from 1, like the channel button on the mouse
+            label = format!("{} · {} {}", label, gettext("Channel"), host_id + 1);
+        }
+        if let Some(version) = self.state.capabilities.firmware_version {
+            label = format!("{} · {}: {}", label, gettext("Firmware Version"), version);
+        }
+        label
+    }
+
+    // Saved binding of `button`, or its default
+    fn binding(&self, button: HardwareButton) -> Binding {
+        self.config
+            .profile()
+            .bindings
+            .get(&button)
+            .cloned()
+            .unwrap_or(Binding::Preset(button.def_binding().id))
+    }
+
     fn round_dpi(&self, dpi: f64) -> u16 {
         ((dpi / DPI_STEP).round() * DPI_STEP) as u16
     }
 
+    // Stage of the current profile matching the configured DPI
+    fn current_dpi_stage(&self) -> Option<u16> {
+        let dpi = self.round_dpi(self.config.dpi);
+        Some(dpi).filter(|dpi| self.config.profile().dpi_stages.contains(dpi))
+    }
+
+    fn max_dpi_stages(&self) -> usize {
+        match self
+            .state
+            .device_profile
+            .and_then(|x| x.dpi_stages.as_ref())
+        {
+            Some(dpi_stages) => dpi_stages.max_count.into(),
+            None => MAX_DPI_STAGES,
+        }
+    }
+
+    // Whether the configured DPI can be added as a stage, within the range
+    // the device reports
+    fn can_add_dpi_stage(&self) -> bool {
+        let dpi = self.round_dpi(self.config.dpi);
+        let in_range = match self.state.capabilities.dpi_range {
+            Some((min, max, step)) => {
+                dpi >= min && dpi <= max && (dpi - min).checked_rem(step).unwrap_or(0) == 0
+            }
+            None => false,
+        };
+        in_range
+            && self.current_dpi_stage().is_none()
+            && self.config.profile().dpi_stages.len() < self.max_dpi_stages()
+    }
+
     fn apply_profile_diff(
         &mut self,
         device_id: DeviceId,
@@ -67,6 +153,24 @@ impl Device {
             }
         }
     }
+
+    fn apply_cut_off_diff(
+        &mut self,
+        device_id: DeviceId,
+        worker: &RelmWorker<WorkerModel, AppModel>,
+    ) {
+        if let (Some(new), Some(old)) = (self.config.cut_off, self.state.cut_off) {
+            if old != new {
+                send!(worker, WorkerMsg::SetCutOff(device_id, new));
+            }
+        }
+    }
+}
+
+// Change waiting for the user to confirm it, after seeing what it changes
+enum Pending {
+    SelectProfile(usize),
+    Import(MouseConfig),
 }
 
 #[derive(Default)]
@@ -82,10 +186,28 @@ struct AppModel {
     rename_config: bool,
     device_monitor: Option<DeviceMonitorProcess>,
     error: Option<String>,
+    pending: Option<Pending>,
+    // Descriptions of the pending changes, to show in a dialog
+    confirm_changes: Option<Vec<String>>,
+    // Device with bindings differing from the saved profile, and the
+    // differences, to ask which to keep
+    reconcile: Option<(DeviceId, Vec<String>)>,
+    // Incremented on each DPI change, so only the last one's delayed write
+    // applies
+    dpi_change: u64,
+    // Button to copy the binding of, and buttons that can take it
+    copy_binding: Option<(HardwareButton, Vec<HardwareButton>)>,
+    // Set once the worker has finished writing, to close the window
+    quit: bool,
+    // File the reports of all devices are recorded to
+    recording: Option<(PathBuf, Recording)>,
 }
 
 impl AppModel {
-    fn new(device_monitor: Option<DeviceMonitorProcess>) -> Self {
+    fn new(
+        device_monitor: Option<DeviceMonitorProcess>,
+        recording: Option<(PathBuf, Recording)>,
+    ) -> Self {
         let devices: Vec<_> = load_config()
             .into_iter()
             .map(|(serial, config)| Device {
@@ -100,18 +222,65 @@ impl AppModel {
             devices,
             selected_device,
             device_monitor,
+            recording,
             ..Default::default()
         }
     }
 
+    // Ask to confirm `changes` first, if there are any
+    fn confirm(&mut self, pending: Pending, changes: &[Change], components: &AppComponents) {
+        if changes.is_empty() {
+            self.apply_pending(pending, components);
+        } else {
+            self.confirm_changes = Some(changes.iter().map(Change::describe).collect());
+            self.pending = Some(pending);
+        }
+    }
+
+    fn apply_pending(&mut self, pending: Pending, components: &AppComponents) {
+        if let Some(device) = self.device_mut() {
+            match pending {
+                Pending::SelectProfile(profile) => device.config.select_profile(profile),
+                Pending::Import(config) => device.config = config,
+            }
+            if let Some(device_id) = device.id.clone() {
+                device.apply_profile_diff(device_id.clone(), &components.worker);
+                device.apply_dpi_diff(device_id.clone(), &components.worker);
+                device.apply_cut_off_diff(device_id, &components.worker);
+            }
+            self.profiles_changed = true;
+            self.bindings_changed = true;
+        }
+    }
+
     fn device(&self) -> Option<&Device> {
         Some(&self.devices[self.selected_device?])
     }
 
+    // Whether to show the panel for a feature of the selected device
+    fn device_supports(&self, has: fn(&Capabilities) -> bool) -> bool {
+        match self.device() {
+            Some(device) => device.state.supports(has),
+            None => true,
+        }
+    }
+
+    // Whether the selected device's profile is locked against edits
+    fn profile_locked(&self) -> bool {
+        self.device().is_some_and(|x| x.config.profile().locked)
+    }
+
     fn device_mut(&mut self) -> Option<&mut Device> {
         Some(&mut self.devices[self.selected_device?])
     }
 
+    // Whether events from `id` are for the device shown
+    fn is_selected(&self, id: &DeviceId) -> bool {
+        self.device_by_id
+            .get(id)
+            .is_some_and(|idx| Some(*idx) == self.selected_device)
+    }
+
     fn device_by_id_mut(&mut self, id: &DeviceId) -> Option<&mut Device> {
         Some(&mut self.devices[*self.device_by_id.get(id)?])
     }
@@ -121,7 +290,7 @@ impl AppModel {
         device_id: DeviceId,
         device: String,
         serial: String,
-        version: (u16, u16, u16),
+        version: FirmwareVersion,
     ) {
         if let Some(idx) = self.devices.iter().position(|d| d.serial == serial) {
             let mut device = &mut self.devices[idx];
@@ -129,7 +298,7 @@ impl AppModel {
                 self.device_by_id.remove(&old_id);
             }
             device.state.set_connected();
-            device.state.firmware_version = Some(version);
+            device.state.capabilities.firmware_version = Some(version);
             device.id = Some(device_id.clone());
             self.device_by_id.insert(device_id.clone(), idx);
         } else {
@@ -140,7 +309,7 @@ impl AppModel {
                 serial,
             };
             device.state.set_connected();
-            device.state.firmware_version = Some(version);
+            device.state.capabilities.firmware_version = Some(version);
             device.id = Some(device_id.clone());
             self.devices.push(device);
             let idx = self.devices.len() - 1;
@@ -218,19 +387,70 @@ enum AppMsg {
     DeviceRemoved(DeviceId),
     ToggleRenameConfig,
     RenameConfig(Option<String>),
+    SetLocked(bool),
     Event(DeviceId, Event),
+    // An event the device pushed without being asked, after it was changed
+    // from another host or its own controls. Handled like `Event`, after
+    // dropping what the app assumed about the device.
+    DeviceStateChanged(DeviceId, Event),
+    Capabilities(DeviceId, &'static DeviceProfile, Option<Capabilities>),
     SetDpi(f64),
+    ApplyDpi(u64),
+    AddDpiStage,
+    RemoveDpiStage,
+    SetCutOff(u8),
+    SetLed(u8, u8, u8),
     SetBinding(HardwareButton, Binding),
     SelectButton(Option<HardwareButton>),
     SetLeftHanded(bool),
+    SetMediaKeys(MediaKeys),
+    ResetBinding(HardwareButton),
+    SetButtonEnabled(HardwareButton, bool),
+    ShowCopyBinding(HardwareButton),
+    CopyBinding(HardwareButton, Vec<HardwareButton>),
+    ResetAll,
+    BindingNotApplied(DeviceId, u8, Vec<Button>),
+    ApplyProgress(DeviceId, Option<(usize, usize)>),
     Reset,
+    FactoryReset,
     Remove,
     SelectDevice(Option<usize>),
     SaveConfig,
+    Close,
+    Quit,
     ShowAboutMouse,
+    RefreshBattery,
+    RefreshDpi,
     SelectProfile(usize),
     ExportConfig(PathBuf),
+    ExportCheatsheet(PathBuf),
     ImportConfig(PathBuf),
+    RecordDiagnostics(Option<PathBuf>),
+    ApplyPending,
+    CancelPending,
+    Reconcile(DeviceId, Reconcile),
+}
+
+impl AppMsg {
+    // Changes the selected profile, so ignored while it's locked
+    fn edits_profile(&self) -> bool {
+        matches!(
+            self,
+            AppMsg::SetDpi(_)
+                | AppMsg::AddDpiStage
+                | AppMsg::RemoveDpiStage
+                | AppMsg::SetCutOff(_)
+                | AppMsg::SetLed(..)
+                | AppMsg::SetBinding(..)
+                | AppMsg::SelectButton(Some(_))
+                | AppMsg::SetLeftHanded(_)
+                | AppMsg::SetMediaKeys(_)
+                | AppMsg::ResetBinding(_)
+                | AppMsg::SetButtonEnabled(..)
+                | AppMsg::CopyBinding(..)
+                | AppMsg::ResetAll
+        )
+    }
 }
 
 impl Model for AppModel {
@@ -240,12 +460,22 @@ impl Model for AppModel {
 }
 
 impl AppUpdate for AppModel {
-    fn update(&mut self, msg: AppMsg, components: &AppComponents, _sender: Sender<AppMsg>) -> bool {
+    fn update(&mut self, msg: AppMsg, components: &AppComponents, sender: Sender<AppMsg>) -> bool {
         self.bindings_changed = false;
         self.device_list_changed = false;
         self.show_about_mouse = false;
         self.profiles_changed = false;
         self.error = None;
+        self.confirm_changes = None;
+        self.reconcile = None;
+        self.copy_binding = None;
+
+        if msg.edits_profile() && self.profile_locked() {
+            self.error = Some(gettext(
+                "This configuration is locked. Unlock it to make changes.",
+            ));
+            return true;
+        }
 
         match msg {
             AppMsg::ToggleRenameConfig => {
@@ -257,6 +487,11 @@ impl AppUpdate for AppModel {
                     self.profiles_changed = true;
                 }
             }
+            AppMsg::SetLocked(locked) => {
+                if let Some(device) = self.device_mut() {
+                    device.config.profile_mut().locked = locked;
+                }
+            }
             AppMsg::SetDeviceMonitor => {
                 if let Some(device_monitor) = self.device_monitor.take() {
                     send!(
@@ -270,58 +505,195 @@ impl AppUpdate for AppModel {
             }
             AppMsg::DeviceRemoved(id) => {
                 self.remove_device_id(&id);
+                self.bindings_changed = true;
             }
-            AppMsg::Event(device_id, event) => match event {
-                Event::Battery { level, .. } => {
-                    let device = self.device_by_id_mut(&device_id).unwrap();
-                    device.state.battery_percent = Some(level);
+            AppMsg::DeviceStateChanged(device_id, event) => {
+                if let Some(device) = self.device_by_id_mut(&device_id) {
+                    match &event {
+                        // Compared with the profile again, asking before
+                        // overwriting the new bindings
+                        Event::Buttons { .. } => device.state.bindings = None,
+                        // Settings changed on the device are kept
+                        Event::Mouse {
+                            dpi,
+                            left_handed,
+                            cut_off,
+                            ..
+                        } => {
+                            device.config.dpi = (*dpi).into();
+                            device.config.cut_off = Some(*cut_off);
+                            device.config.profile_mut().left_handed = *left_handed;
+                        }
+                        _ => {}
+                    }
+                }
+                send!(sender, AppMsg::Event(device_id, event));
+            }
+            AppMsg::Event(device_id, event) => {
+                if let Some(device) = self.device_by_id_mut(&device_id) {
+                    device.state.capabilities.update(&event);
                 }
-                Event::Mouse {
-                    dpi, left_handed, ..
-                } => {
-                    let device = self.device_by_id_mut(&device_id).unwrap();
+                match event {
+                    Event::Battery {
+                        level,
+                        low_level,
+                        crit_level,
+                        ..
+                    } => {
+                        let device = self.device_by_id_mut(&device_id).unwrap();
+                        device.state.battery_percent = Some(level);
+                        device.state.battery_alert =
+                            battery::BatteryAlert::for_level(level, low_level, crit_level);
+                        if let Some(alert) = device
+                            .state
+                            .battery_alerts
+                            .update(level, low_level, crit_level)
+                        {
+                            battery::notify(alert, level);
+                        }
+                    }
+                    Event::Mouse {
+                        dpi,
+                        left_handed,
+                        cut_off,
+                        host_id,
+                        ..
+                    } => {
+                        let device = self.device_by_id_mut(&device_id).unwrap();
+                        device.state.host_id = Some(host_id);
 
-                    // Sync dpi from config
-                    if device.state.dpi.is_none() {
-                        device.state.dpi = Some(dpi.into());
-                        device.apply_dpi_diff(device_id.clone(), &components.worker);
+                        if mem::take(&mut device.state.factory_reset) {
+                            device.config.dpi = dpi.into();
+                            device.config.cut_off = Some(cut_off);
+                            device.config.profile_mut().left_handed = left_handed;
+                        }
+
+                        // Sync dpi from config
+                        if device.state.dpi.replace(dpi.into()).is_none() {
+                            device.apply_dpi_diff(device_id.clone(), &components.worker);
+                        }
+
+                        // Sync cut off from config
+                        if device.state.cut_off.replace(cut_off).is_none() {
+                            device.apply_cut_off_diff(device_id.clone(), &components.worker);
+                        }
+
+                        // Sync left_handed from config
+                        if device.state.left_handed.is_none() {
+                            device.state.left_handed = Some(left_handed);
+                            device.apply_profile_diff(device_id.clone(), &components.worker);
+                        }
                     }
+                    Event::Buttons {
+                        buttons, host_id, ..
+                    } => {
+                        let device = self.device_by_id_mut(&device_id).unwrap();
+                        device.state.host_id = Some(host_id);
+                        if device.state.bindings.is_none() {
+                            device.state.set_bindings_from_buttons(host_id, &buttons);
 
-                    // Sync left_handed from config
-                    if device.state.left_handed.is_none() {
-                        device.state.left_handed = Some(left_handed);
-                        device.apply_profile_diff(device_id.clone(), &components.worker);
+                            // Such as when changed from another computer. Ask
+                            // before overwriting.
+                            let changes = match &device.state.bindings {
+                                Some(bindings) => device.config.profile().binding_changes(bindings),
+                                None => Vec::new(),
+                            };
+                            if !changes.is_empty() {
+                                device.state.bindings_held = true;
+                                let changes = changes.iter().map(Change::describe).collect();
+                                self.reconcile = Some((device_id.clone(), changes));
+                            }
+                            self.bindings_changed |= self.is_selected(&device_id);
+                        }
                     }
-                }
-                Event::Buttons {
-                    buttons, host_id, ..
-                } => {
-                    let device = self.device_by_id_mut(&device_id).unwrap();
-                    if device.state.bindings.is_none() {
-                        device.state.set_bindings_from_buttons(host_id, &buttons);
+                    Event::Firmware {
+                        device,
+                        serial,
+                        version,
+                    } => {
+                        self.add_or_update_device(device_id, device, serial, version);
                     }
+                    Event::FactoryReset => {
+                        // Read everything again from the device
+                        let device = self.device_by_id_mut(&device_id).unwrap();
+                        let firmware_version = device.state.capabilities.firmware_version;
+                        device.state.set_connected();
+                        device.state.capabilities.firmware_version = firmware_version;
+                        device.state.factory_reset = true;
+                        self.bindings_changed |= self.is_selected(&device_id);
+                        send!(components.worker, WorkerMsg::HasFirmware(device_id));
+                    }
+                    Event::Led { red, green, blue } => {
+                        let device = self.device_by_id_mut(&device_id).unwrap();
+                        device.state.led = Some((red, green, blue));
+                    }
+                    Event::CommandError { kind, code } => {
+                        eprintln!("Packet kind {} reported command error {}", kind, code);
+                    }
+                    Event::Unknown { kind, data } => {
+                        eprintln!("Unhandled packet kind {}: {:02x?}", kind, data);
+                    }
+                    _ => {}
                 }
-                Event::Firmware {
-                    device,
-                    serial,
-                    version,
-                } => {
-                    self.add_or_update_device(device_id, device, serial, version);
-                }
-                _ => {}
-            },
+            }
             AppMsg::SetDpi(value) => {
                 if let Some(device) = self.device_mut() {
                     device.config.dpi = value;
+                    self.dpi_change += 1;
+                    let change = self.dpi_change;
+                    glib::timeout_add_local_once(DPI_APPLY_DELAY, move || {
+                        send!(sender, AppMsg::ApplyDpi(change));
+                    });
+                }
+            }
+            AppMsg::ApplyDpi(change) => {
+                // Any device, in case the selection changed since
+                if change == self.dpi_change {
+                    for device in &mut self.devices {
+                        if let Some(device_id) = device.id.clone() {
+                            device.apply_dpi_diff(device_id, &components.worker);
+                        }
+                    }
+                }
+            }
+            AppMsg::SetLed(red, green, blue) => {
+                if let Some(device) = self.device() {
                     if let Some(device_id) = device.id.clone() {
-                        device.apply_dpi_diff(device_id, &components.worker);
+                        send!(
+                            components.worker,
+                            WorkerMsg::SetLed(device_id, red, green, blue)
+                        );
+                    }
+                }
+            }
+            AppMsg::SetCutOff(value) => {
+                if let Some(device) = self.device_mut() {
+                    device.config.cut_off = Some(value);
+                    if let Some(device_id) = device.id.clone() {
+                        device.apply_cut_off_diff(device_id, &components.worker);
                     }
                 }
             }
             AppMsg::SelectButton(button) => {
                 let button = self.swap_buttons(button);
                 if let Some(id) = button {
-                    send!(components.dialog, BindingDialogMsg::Show(id))
+                    let binding = self
+                        .device()
+                        .and_then(|x| x.config.profile().bindings.get(&id))
+                        .cloned();
+                    let support_down_up = self
+                        .device()
+                        .is_some_and(|x| x.state.supports_press_type(PressType::Down));
+                    let enabled = !self
+                        .device()
+                        .is_some_and(|x| x.config.profile().disabled.contains(&id));
+                    let media_keys = self
+                        .device()
+                        .map_or(MediaKeys::default(), |x| x.config.media_keys);
+                    send!(
+                        components.dialog,
+                        BindingDialogMsg::Show(id, binding, support_down_up, enabled, media_keys)
+                    )
                 } else {
                     let left_handed = self
                         .device()
@@ -334,11 +706,8 @@ impl AppUpdate for AppModel {
             }
             AppMsg::SetBinding(button, binding) => {
                 if let Some(device) = self.device_mut() {
-                    if binding == Binding::Preset(button.def_binding().id) {
-                        device.config.profile_mut().bindings.remove(&button);
-                    } else {
-                        device.config.profile_mut().bindings.insert(button, binding);
-                    }
+                    let binding = binding.with_media_keys(device.config.media_keys);
+                    device.config.profile_mut().set_binding(button, binding);
                     if let Some(device_id) = device.id.clone() {
                         device.apply_profile_diff(device_id, &components.worker);
                     }
@@ -354,12 +723,129 @@ impl AppUpdate for AppModel {
                     self.bindings_changed = true;
                 }
             }
+            AppMsg::SetMediaKeys(media_keys) => {
+                if let Some(device) = self.device_mut() {
+                    if device.config.media_keys != media_keys {
+                        device.config.media_keys = media_keys;
+                        for profile in device.config.profiles_mut() {
+                            profile.set_media_keys(media_keys);
+                        }
+                        if let Some(device_id) = device.id.clone() {
+                            device.apply_profile_diff(device_id, &components.worker);
+                        }
+                        self.bindings_changed = true;
+                    }
+                }
+            }
+            AppMsg::ResetBinding(button) => {
+                if let Some(device) = self.device_mut() {
+                    device.config.profile_mut().bindings.remove(&button);
+                    device.config.profile_mut().disabled.remove(&button);
+                    if let Some(device_id) = device.id.clone() {
+                        device.apply_profile_diff(device_id, &components.worker);
+                    }
+                    self.bindings_changed = true;
+                }
+            }
+            AppMsg::ShowCopyBinding(from) => {
+                if let Some(device) = self.device() {
+                    let binding = device.binding(from);
+                    let support_down_up = device.state.supports_press_type(PressType::Down);
+                    let buttons = if binding.can_program(support_down_up) {
+                        HardwareButton::iter().filter(|x| *x != from).collect()
+                    } else {
+                        Vec::new()
+                    };
+                    self.copy_binding = Some((from, buttons));
+                }
+            }
+            AppMsg::CopyBinding(from, buttons) => {
+                if let Some(device) = self.device_mut() {
+                    let binding = device.binding(from);
+                    let profile = device.config.profile_mut();
+                    for button in buttons {
+                        profile.set_binding(button, binding.clone());
+                    }
+                    if let Some(device_id) = device.id.clone() {
+                        device.apply_profile_diff(device_id, &components.worker);
+                    }
+                    self.bindings_changed = true;
+                }
+            }
+            AppMsg::SetButtonEnabled(button, enabled) => {
+                if let Some(device) = self.device_mut() {
+                    let disabled = &mut device.config.profile_mut().disabled;
+                    if enabled {
+                        disabled.remove(&button);
+                    } else {
+                        disabled.insert(button);
+                    }
+                    if let Some(device_id) = device.id.clone() {
+                        device.apply_profile_diff(device_id, &components.worker);
+                    }
+                    self.bindings_changed = true;
+                }
+            }
+            AppMsg::Capabilities(device_id, device_profile, capabilities) => {
+                if let Some(device) = self.device_by_id_mut(&device_id) {
+                    device.state.device_profile = Some(device_profile);
+                    device.state.capabilities.queries = capabilities;
+                    device.state.capabilities.button_count = device_profile.buttons;
+                }
+            }
+            AppMsg::ApplyProgress(device_id, progress) => {
+                if let Some(device) = self.device_by_id_mut(&device_id) {
+                    device.state.apply_progress = progress;
+                }
+            }
+            AppMsg::BindingNotApplied(device_id, host_id, buttons) => {
+                // Show what the device actually has, rather than what was requested
+                if let Some(device) = self.device_by_id_mut(&device_id) {
+                    device.state.set_bindings_from_buttons(host_id, &buttons);
+                    self.bindings_changed = true;
+                }
+                self.error = Some(gettext("Failed to save binding to the mouse."));
+            }
+            AppMsg::AddDpiStage => {
+                if let Some(device) = self.device_mut() {
+                    if device.can_add_dpi_stage() {
+                        let dpi = device.round_dpi(device.config.dpi);
+                        let stages = &mut device.config.profile_mut().dpi_stages;
+                        stages.push(dpi);
+                        stages.sort_unstable();
+                        if let Some(device_id) = device.id.clone() {
+                            device.apply_profile_diff(device_id, &components.worker);
+                        }
+                    }
+                }
+            }
+            AppMsg::RemoveDpiStage => {
+                if let Some(device) = self.device_mut() {
+                    if let Some(dpi) = device.current_dpi_stage() {
+                        device.config.profile_mut().dpi_stages.retain(|x| *x != dpi);
+                        if let Some(device_id) = device.id.clone() {
+                            device.apply_profile_diff(device_id, &components.worker);
+                        }
+                    }
+                }
+            }
+            AppMsg::ResetAll => {
+                if let Some(device) = self.device_mut() {
+                    device.config.profile_mut().bindings.clear();
+                    device.config.profile_mut().disabled.clear();
+                    if let Some(device_id) = device.id.clone() {
+                        device.apply_profile_diff(device_id, &components.worker);
+                    }
+                    self.bindings_changed = true;
+                }
+            }
             AppMsg::Reset => {
                 if let Some(device) = self.device_mut() {
                     for profile in device.config.profiles_mut() {
                         *profile = Profile::default();
                     }
                     device.config.dpi = 1200.; // XXX depend on device
+                    device.config.cut_off = None;
 
                     if let Some(device_id) = device.id.clone() {
                         device.apply_profile_diff(device_id.clone(), &components.worker);
@@ -369,6 +855,21 @@ impl AppUpdate for AppModel {
                     self.bindings_changed = true;
                 }
             }
+            AppMsg::FactoryReset => {
+                if let Some(device) = self.device_mut() {
+                    // The device's settings are adopted once it has reset
+                    for profile in device.config.profiles_mut() {
+                        *profile = Profile::default();
+                    }
+                    device.config.cut_off = None;
+
+                    if let Some(device_id) = device.id.clone() {
+                        send!(components.worker, WorkerMsg::FactoryReset(device_id));
+                    }
+
+                    self.bindings_changed = true;
+                }
+            }
             AppMsg::Remove => {
                 if let Some(device) = self.selected_device {
                     self.remove_device(device);
@@ -382,20 +883,50 @@ impl AppUpdate for AppModel {
             AppMsg::SaveConfig => {
                 save_config(self.devices.iter().map(|x| (&x.serial, &x.config)));
             }
+            AppMsg::Close => {
+                save_config(self.devices.iter().map(|x| (&x.serial, &x.config)));
+                // Write a DPI change still waiting for the slider to settle
+                for device in &mut self.devices {
+                    if let Some(device_id) = device.id.clone() {
+                        device.apply_dpi_diff(device_id, &components.worker);
+                    }
+                }
+                send!(
+                    components.worker,
+                    WorkerMsg::Shutdown(Instant::now() + SHUTDOWN_TIMEOUT)
+                );
+            }
+            AppMsg::Quit => {
+                self.quit = true;
+            }
             AppMsg::ShowAboutMouse => {
                 self.show_about_mouse = true;
             }
+            AppMsg::RefreshBattery => {
+                for device in &self.devices {
+                    if let (Some(id), true) = (&device.id, device.state.connected) {
+                        send!(components.worker, WorkerMsg::QueryBattery(id.clone()));
+                    }
+                }
+            }
+            AppMsg::RefreshDpi => {
+                // Only the selected device's DPI is shown
+                if let Some(device) = self.device() {
+                    if let (Some(id), true) = (&device.id, device.state.connected) {
+                        send!(components.worker, WorkerMsg::QueryDpi(id.clone()));
+                    }
+                }
+            }
             AppMsg::SelectProfile(profile) => {
                 if let Some(device) = self.device_mut() {
                     if profile != device.config.profile_num()
                         && profile < device.config.profiles().len()
                     {
-                        device.config.select_profile(profile);
-                        if let Some(device_id) = device.id.clone() {
-                            device.apply_profile_diff(device_id, &components.worker);
-                        }
-                        self.profiles_changed = true;
-                        self.bindings_changed = true;
+                        let changes = device
+                            .config
+                            .profile()
+                            .diff(&device.config.profiles()[profile]);
+                        self.confirm(Pending::SelectProfile(profile), &changes, components);
                     }
                 }
             }
@@ -403,24 +934,101 @@ impl AppUpdate for AppModel {
                 if let Some(device) = self.device_mut() {
                     match MouseConfig::import(&path) {
                         Ok(config) => {
-                            device.config = config;
+                            let changes = device.config.diff(&config);
+                            self.confirm(Pending::Import(config), &changes, components);
                         }
                         Err(err) => {
-                            self.error = Some(format!("Failed to import config: {}", err));
+                            self.error =
+                                Some(format!("{}: {}", gettext("Failed to import config"), err));
                         }
                     }
                 }
             }
+            AppMsg::ApplyPending => {
+                if let Some(pending) = self.pending.take() {
+                    self.apply_pending(pending, components);
+                }
+            }
+            AppMsg::Reconcile(device_id, reconcile) => {
+                if let Some(device) = self.device_by_id_mut(&device_id) {
+                    match reconcile {
+                        Reconcile::ApplySaved => {
+                            device.state.bindings_held = false;
+                            device.apply_profile_diff(device_id, &components.worker);
+                        }
+                        Reconcile::AdoptDevice => {
+                            device.state.bindings_held = false;
+                            let profile = device.config.profile_mut();
+                            for (button, binding) in device.state.bindings.iter().flatten() {
+                                // Keep the saved binding where the device's isn't
+                                // understood, or it is disabled on both
+                                if *binding == Binding::Unknown
+                                    || profile.binding(*button).as_ref() == Some(binding)
+                                {
+                                    continue;
+                                }
+                                profile.disabled.remove(button);
+                                profile.bindings.insert(*button, binding.clone());
+                            }
+                            device.apply_profile_diff(device_id, &components.worker);
+                        }
+                        // Leave both, without writing the saved bindings,
+                        // until the device reconnects
+                        Reconcile::Cancel => {}
+                    }
+                }
+                self.bindings_changed = true;
+            }
+            AppMsg::CancelPending => {
+                self.pending = None;
+                // Show the profile that is still selected
+                self.profiles_changed = true;
+            }
             AppMsg::ExportConfig(path) => {
                 if let Some(device) = self.device_mut() {
                     match device.config.export(&path) {
                         Ok(()) => {}
                         Err(err) => {
-                            self.error = Some(format!("Failed to export config: {}", err));
+                            self.error =
+                                Some(format!("{}: {}", gettext("Failed to export config"), err));
                         }
                     }
                 }
             }
+            AppMsg::RecordDiagnostics(path) => {
+                let recording = match path {
+                    // Without serial numbers, to attach to a public bug report
+                    Some(path) => match Recording::create(&path) {
+                        Ok(recording) => Some((path, recording.redact_serial(true))),
+                        Err(err) => {
+                            self.error = Some(format!(
+                                "{}: {}",
+                                gettext("Failed to record diagnostics"),
+                                err
+                            ));
+                            None
+                        }
+                    },
+                    None => None,
+                };
+                send!(
+                    components.worker,
+                    WorkerMsg::Record(recording.as_ref().map(|(_, x)| x.clone()))
+                );
+                self.recording = recording;
+            }
+            AppMsg::ExportCheatsheet(path) => {
+                if let Some(device) = self.device() {
+                    let sheet = device.config.profile().to_cheatsheet();
+                    if let Err(err) = std::fs::write(&path, sheet) {
+                        self.error = Some(format!(
+                            "{}: {}",
+                            gettext("Failed to export cheat sheet"),
+                            err
+                        ));
+                    }
+                }
+            }
         }
         true
     }
@@ -430,7 +1038,7 @@ impl AppUpdate for AppModel {
 impl Widgets<AppModel, ()> for AppWidgets {
     view! {
         main_window = gtk4::ApplicationWindow {
-            set_title: Some("Mouse Configurator"),
+            set_title: Some(&gettext("Mouse Configurator")),
             set_default_size: args!(1280, 768),
             set_titlebar = Some(&gtk4::HeaderBar) {
                 pack_start = &gtk4::Button {
@@ -444,7 +1052,19 @@ impl Widgets<AppModel, ()> for AppWidgets {
                 pack_end = &gtk4::MenuButton {
                     set_menu_model: Some(&menu),
                     set_icon_name: "open-menu-symbolic"
-                }
+                },
+                // Hidden until the mouse reports a battery level
+                pack_end = &gtk4::Box {
+                    set_orientation: gtk4::Orientation::Horizontal,
+                    set_spacing: 6,
+                    set_visible: watch! { model.device().is_some_and(|x| x.state.connected && x.state.battery_percent.is_some()) },
+                    append = &gtk4::Image {
+                        set_icon_name: watch! { Some(model.device().map_or("battery-symbolic", |x| x.state.battery_alert.icon_name())) },
+                    },
+                    append = &gtk4::Label {
+                        set_label: watch! { &format!("{}%", model.device().and_then(|x| x.state.battery_percent).unwrap_or(0)) }
+                    },
+                },
             },
             set_child = Some(&gtk4::ScrolledWindow) {
                 set_hscrollbar_policy: gtk4::PolicyType::Never,
@@ -459,17 +1079,17 @@ impl Widgets<AppModel, ()> for AppWidgets {
                             set_pixel_size: 128,
                         },
                         append = &gtk4::Label {
-                            set_label: "No Mouse Detected",
+                            set_label: &gettext("No Mouse Detected"),
                             set_attributes = Some(&pango::AttrList) {
                                 insert: pango::AttrInt::new_weight(pango::Weight::Bold),
                                 insert: pango::AttrFloat::new_scale(pango::SCALE_LARGE)
                             },
                         },
                         append = &gtk4::Label {
-                            set_label: "If using USB connection, make sure it is plugged in properly.",
+                            set_label: &gettext("If using USB connection, make sure it is plugged in properly."),
                         },
                         append = &gtk4::LinkButton {
-                            set_label: "Check Bluetooth Settings",
+                            set_label: &gettext("Check Bluetooth Settings"),
                             connect_activate_link => |_| {
                                 let _ = Command::new("gnome-control-center").arg("bluetooth").spawn();
                                 gtk4::Inhibit(true)
@@ -503,17 +1123,6 @@ impl Widgets<AppModel, ()> for AppWidgets {
                             set_orientation: gtk4::Orientation::Horizontal,
                             set_halign: gtk4::Align::Center,
                             set_spacing: 12,
-                            append = &gtk4::Box {
-                                set_orientation: gtk4::Orientation::Horizontal,
-                                set_spacing: 6,
-                                set_visible: watch! { model.device().map_or(false, |x| x.state.connected) },
-                                append = &gtk4::Image {
-                                    set_from_icon_name: Some("battery-symbolic"),
-                                },
-                                append = &gtk4::Label {
-                                    set_label: watch! { &format!("{}%", model.device().and_then(|x| x.state.battery_percent).unwrap_or(0)) }
-                                },
-                            },
                             append = &gtk4::Button {
                                 add_css_class: "flat",
                                 set_child = Some(&gtk4::Box) {
@@ -523,18 +1132,22 @@ impl Widgets<AppModel, ()> for AppWidgets {
                                         set_from_icon_name: Some("help-info-symbolic"),
                                     },
                                     append = &gtk4::Label {
-                                        set_label: "About This Mouse",
+                                        set_label: &gettext("About This Mouse"),
                                     }
                                 },
                                 connect_clicked(sender) => move |_| send!(sender, AppMsg::ShowAboutMouse)
                             },
+                            append = &gtk4::Label {
+                                add_css_class: "dim-label",
+                                set_label: watch! { &model.device().map_or_else(String::new, Device::status_label) },
+                            },
                         },
                         append = &gtk4::Box {
                             set_margin_bottom: 18,
                             set_spacing: 8,
                             set_orientation: gtk4::Orientation::Horizontal,
                             append = &gtk4::Label {
-                                set_label: "Configuration",
+                                set_label: &gettext("Configuration"),
                             },
                             append: profiles_stack = &gtk4::Stack {
                                 add_child: profiles_dropdown = &gtk4::DropDown {
@@ -553,10 +1166,21 @@ impl Widgets<AppModel, ()> for AppWidgets {
                                 connect_clicked(sender) => move |_| {
                                     send!(sender, AppMsg::ToggleRenameConfig);
                                 }
+                            },
+                            append = &gtk4::ToggleButton {
+                                set_icon_name: watch! { if model.profile_locked() { "changes-prevent-symbolic" } else { "changes-allow-symbolic" } },
+                                set_tooltip_text: Some(&gettext("Lock Configuration")),
+                                update_property: &[Property::Label(&gettext("Lock Configuration"))],
+                                set_active: watch! { model.profile_locked() },
+                                connect_toggled(sender) => move |button| {
+                                    send!(sender, AppMsg::SetLocked(button.is_active()));
+                                }
                             }
                         },
                         // One element box to work around weird size allocation behavior
                         append = &gtk4::Box {
+                            set_visible: watch! { model.device_supports(|x| x.buttons) },
+                            set_sensitive: watch! { !model.profile_locked() },
                             set_margin_top: 6,
                             set_margin_bottom: 6,
                             set_vexpand: false,
@@ -571,21 +1195,54 @@ impl Widgets<AppModel, ()> for AppWidgets {
                             }
                         },
                         append = &gtk4::Label {
-                            set_label: "Select a button to change its binding. Your settings are automatically saved to firmware.",
+                            set_visible: watch! { model.device_supports(|x| x.buttons) },
+                            set_label: watch! { &if model.profile_locked() {
+                                gettext("This configuration is locked. Unlock it to make changes.")
+                            } else {
+                                gettext("Select a button to change its binding. Your settings are automatically saved to firmware.")
+                            } },
+                            set_margin_bottom: 6,
+                        },
+                        append = &gtk4::ProgressBar {
+                            set_visible: watch! { model.device().is_some_and(|x| x.state.apply_progress.is_some()) },
+                            set_show_text: true,
+                            set_margin_bottom: 6,
+                            set_fraction: watch! { model.device().and_then(|x| x.state.apply_progress).map_or(0., |(done, total)| done as f64 / total as f64) },
+                            set_text: watch! { model.device().and_then(|x| x.state.apply_progress).map(|(done, total)| if done < total {
+                                format!("{}: {} / {}", gettext("Applying Bindings"), done, total)
+                            } else {
+                                gettext("Checking Bindings")
+                            }).as_deref() },
+                        },
+                        append = &gtk4::CheckButton {
+                            set_visible: watch! { model.device_supports(|x| x.buttons) },
+                            set_sensitive: watch! { !model.profile_locked() },
+                            set_halign: gtk4::Align::Center,
                             set_margin_bottom: 18,
+                            set_label: Some(&gettext("Send Volume Controls as Keyboard Keys")),
+                            set_tooltip_text: Some(&gettext("For desktops where volume bindings do nothing")),
+                            set_active: watch! { model.device().is_some_and(|x| x.config.media_keys == MediaKeys::Keyboard) },
+                            connect_toggled(sender) => move |button| {
+                                let media_keys = if button.is_active() { MediaKeys::Keyboard } else { MediaKeys::Consumer };
+                                send!(sender, AppMsg::SetMediaKeys(media_keys));
+                            }
                         },
                         append = &gtk4::Label {
-                            set_label: "Sensitivity",
+                            set_visible: watch! { model.device_supports(|x| x.mouse) },
+                            set_label: &gettext("Sensitivity"),
                             set_attributes = Some(&pango::AttrList) {
                                 insert: pango::AttrInt::new_weight(pango::Weight::Bold)
                             },
                             set_halign: gtk4::Align::Start
                         },
                         append = &gtk4::ListBox {
+                            set_visible: watch! { model.device_supports(|x| x.mouse) },
                             add_css_class: "frame",
+                            set_header_func: util::header_func,
                             append = &gtk4::ListBoxRow {
                                 set_selectable: false,
                                 set_activatable: false,
+                                set_sensitive: watch! { !model.profile_locked() },
                                 set_child = Some(&gtk4::Box) {
                                     set_orientation: gtk4::Orientation::Horizontal,
                                     set_margin_top: 6,
@@ -596,13 +1253,13 @@ impl Widgets<AppModel, ()> for AppWidgets {
                                         set_margin_end: 36,
                                         set_orientation: gtk4::Orientation::Vertical,
                                         append = &gtk4::Label {
-                                            set_label: "Mouse Cursor Speed",
+                                            set_label: &gettext("Mouse Cursor Speed"),
                                             set_attributes = Some(&pango::AttrList) {
                                                 insert: pango::AttrInt::new_weight(pango::Weight::Bold)
                                             }
                                         },
                                         append = &gtk4::Label {
-                                            set_label: "Sensitivity (DPI)",
+                                            set_label: &gettext("Sensitivity (DPI)"),
                                         }
                                     },
                                     append = &gtk4::Label {
@@ -618,14 +1275,163 @@ impl Widgets<AppModel, ()> for AppWidgets {
                                         }
                                     }
                                 }
+                            },
+                            append = &gtk4::ListBoxRow {
+                                set_selectable: false,
+                                set_activatable: false,
+                                set_sensitive: watch! { !model.profile_locked() },
+                                set_child = Some(&gtk4::Box) {
+                                    set_orientation: gtk4::Orientation::Horizontal,
+                                    set_spacing: 6,
+                                    set_margin_top: 6,
+                                    set_margin_bottom: 6,
+                                    set_margin_start: 6,
+                                    set_margin_end: 6,
+                                    append = &gtk4::Box {
+                                        set_margin_end: 30,
+                                        set_orientation: gtk4::Orientation::Vertical,
+                                        append = &gtk4::Label {
+                                            set_label: &gettext("DPI Stages"),
+                                            set_attributes = Some(&pango::AttrList) {
+                                                insert: pango::AttrInt::new_weight(pango::Weight::Bold)
+                                            }
+                                        },
+                                        append = &gtk4::Label {
+                                            set_label: &gettext("Select one to switch to it"),
+                                        }
+                                    },
+                                    append: dpi_stages_box = &gtk4::Box {
+                                        set_hexpand: true,
+                                        set_spacing: 6,
+                                        set_orientation: gtk4::Orientation::Horizontal,
+                                    },
+                                    append = &gtk4::Button {
+                                        add_css_class: "flat",
+                                        set_icon_name: "list-add-symbolic",
+                                        set_tooltip_text: Some(&gettext("Add Current Sensitivity")),
+                                        update_property: &[Property::Label(&gettext("Add Current Sensitivity"))],
+                                        set_sensitive: watch! { model.device().is_some_and(|device| device.can_add_dpi_stage()) },
+                                        connect_clicked(sender) => move |_| {
+                                            send!(sender, AppMsg::AddDpiStage);
+                                        }
+                                    },
+                                    append = &gtk4::Button {
+                                        add_css_class: "flat",
+                                        set_icon_name: "list-remove-symbolic",
+                                        set_tooltip_text: Some(&gettext("Remove Current Stage")),
+                                        update_property: &[Property::Label(&gettext("Remove Current Stage"))],
+                                        set_sensitive: watch! { model.device().is_some_and(|device| device.current_dpi_stage().is_some()) },
+                                        connect_clicked(sender) => move |_| {
+                                            send!(sender, AppMsg::RemoveDpiStage);
+                                        }
+                                    }
+                                }
+                            },
+                            append = &gtk4::ListBoxRow {
+                                set_selectable: false,
+                                set_activatable: false,
+                                set_sensitive: watch! { !model.profile_locked() },
+                                set_visible: watch! { model.device().is_some_and(|device| device.state.capabilities.cut_off()) },
+                                set_child = Some(&gtk4::Box) {
+                                    set_orientation: gtk4::Orientation::Horizontal,
+                                    set_margin_top: 6,
+                                    set_margin_bottom: 6,
+                                    set_margin_start: 6,
+                                    set_margin_end: 6,
+                                    append = &gtk4::Box {
+                                        set_margin_end: 36,
+                                        set_orientation: gtk4::Orientation::Vertical,
+                                        append = &gtk4::Label {
+                                            set_label: &gettext("Lift-off Distance"),
+                                            set_attributes = Some(&pango::AttrList) {
+                                                insert: pango::AttrInt::new_weight(pango::Weight::Bold)
+                                            }
+                                        },
+                                        append = &gtk4::Label {
+                                            set_label: &gettext("Height where tracking stops"),
+                                        }
+                                    },
+                                    append: cut_off_scale = &gtk4::Scale {
+                                        set_hexpand: true,
+                                        set_draw_value: true,
+                                        set_digits: 0,
+                                        set_round_digits: 0,
+                                        set_increments: args!(1., 1.),
+                                        connect_change_value(sender) => move |_, _, value| {
+                                            send!(sender, AppMsg::SetCutOff(value.round().clamp(0., 255.) as u8));
+                                            gtk4::Inhibit(false)
+                                        }
+                                    }
+                                }
+                            },
+                            append = &gtk4::ListBoxRow {
+                                set_selectable: false,
+                                set_activatable: false,
+                                set_child = Some(&gtk4::Box) {
+                                    set_orientation: gtk4::Orientation::Vertical,
+                                    set_spacing: 6,
+                                    set_margin_top: 6,
+                                    set_margin_bottom: 6,
+                                    set_margin_start: 6,
+                                    set_margin_end: 6,
+                                    append = &gtk4::Label {
+                                        set_halign: gtk4::Align::Start,
+                                        set_label: watch! { &model.device().and_then(|device| device.state.dpi).map_or_else(
+                                            || gettext("Move the pointer here to try the sensitivity"),
+                                            |dpi| format!("{} ({} DPI)", gettext("Move the pointer here to try the sensitivity"), dpi.round()),
+                                        ) },
+                                    },
+                                    append = &dpi_preview::dpi_preview() -> gtk4::Box {},
+                                }
                             }
-                        }
+                        },
+                        append = &gtk4::Label {
+                            set_visible: watch! { model.device().is_some_and(|device| device.state.led.is_some()) },
+                            set_label: &gettext("Status Light"),
+                            set_attributes = Some(&pango::AttrList) {
+                                insert: pango::AttrInt::new_weight(pango::Weight::Bold)
+                            },
+                            set_halign: gtk4::Align::Start
+                        },
+                        append = &gtk4::ListBox {
+                            set_visible: watch! { model.device().is_some_and(|device| device.state.led.is_some()) },
+                            add_css_class: "frame",
+                            append = &gtk4::ListBoxRow {
+                                set_selectable: false,
+                                set_activatable: false,
+                                set_sensitive: watch! { !model.profile_locked() },
+                                set_child = Some(&gtk4::Box) {
+                                    set_orientation: gtk4::Orientation::Horizontal,
+                                    set_margin_top: 6,
+                                    set_margin_bottom: 6,
+                                    set_margin_start: 6,
+                                    set_margin_end: 6,
+                                    append = &gtk4::Label {
+                                        set_hexpand: true,
+                                        set_halign: gtk4::Align::Start,
+                                        set_label: &gettext("Color"),
+                                    },
+                                    append = &gtk4::ColorButton {
+                                        set_use_alpha: false,
+                                        set_rgba: watch! { &model.device().and_then(|device| device.state.led).map_or(gdk::RGBA::BLACK, |(red, green, blue)| {
+                                            gdk::RGBA::new(red as f32 / 255., green as f32 / 255., blue as f32 / 255., 1.)
+                                        }) },
+                                        connect_color_set(sender) => move |button| {
+                                            let rgba = button.rgba();
+                                            let byte = |x: f32| (x * 255.).round().clamp(0., 255.) as u8;
+                                            send!(sender, AppMsg::SetLed(byte(rgba.red()), byte(rgba.green()), byte(rgba.blue())));
+                                        }
+                                    }
+                                }
+                            }
+                        },
                     }
                 }
             },
+            // Closed on `AppMsg::Quit`, once queued writes are done
             connect_close_request(sender) => move |_| {
-                send!(sender, AppMsg::SaveConfig);
-                gtk4::Inhibit(false)
+                send!(sender, AppMsg::Close);
+                gtk4::Inhibit(true)
             }
         }
     }
@@ -634,6 +1440,8 @@ impl Widgets<AppModel, ()> for AppWidgets {
         menu: {
             "Import Configuration" => ImportConfig,
             "Export Configuration" => ExportConfig,
+            "Export Cheat Sheet" => ExportCheatsheet,
+            "Reset Buttons to Default" => ResetBindingsAction,
             "Reset to Default" => ResetAction,
             "Remove Device" => RemoveAction,
             "About" => AboutAction,
@@ -643,13 +1451,17 @@ impl Widgets<AppModel, ()> for AppWidgets {
     additional_fields! {
         buttons: Vec<(Option<HardwareButton>, gtk4::Button)>,
         first_view_run: bool,
+        // Stages and current DPI that `dpi_stages_box` shows
+        dpi_stages_shown: Option<(Vec<u16>, u16)>,
         desktop_settings: gio::Settings,
         device_actions: gio::SimpleActionGroup,
+        app_actions: gio::SimpleActionGroup,
         profiles_dropdown_signal: glib::SignalHandlerId,
     }
 
     fn post_init() {
         let first_view_run = true;
+        let dpi_stages_shown = None;
 
         let profiles_dropdown_signal = profiles_dropdown.connect_selected_notify(
             glib::clone!(@strong sender => move |drop_down| {
@@ -689,7 +1501,7 @@ impl Widgets<AppModel, ()> for AppWidgets {
                button = &gtk4::Button {
                     set_margin_start: 8,
                     set_margin_end: 8,
-                    set_label: "Unknown",
+                    set_label: &gettext("Unknown"),
                     add_css_class: "mouse-button",
                     add_css_class: "flat",
                     connect_clicked(sender) => move |_| {
@@ -709,6 +1521,20 @@ impl Widgets<AppModel, ()> for AppWidgets {
                 show_about_dialog(&main_window);
             }));
         app_group.add_action(about_action);
+        // Turned on once a file is chosen, in `post_view`
+        let record_action: RelmAction<RecordDiagnosticsAction> = RelmAction::new_stateful(
+            &false,
+            glib::clone!(@strong main_window, @strong sender => move |_, recording| {
+                if *recording {
+                    send!(sender, AppMsg::RecordDiagnostics(None));
+                } else {
+                    show_record_diagnostics_dialog(&main_window, glib::clone!(@strong sender => move |path| {
+                        send!(sender, AppMsg::RecordDiagnostics(Some(path)));
+                    }));
+                }
+            }),
+        );
+        app_group.add_action(record_action);
 
         let import_action: RelmAction<ImportConfig> = RelmAction::new_stateless(
             glib::clone!(@strong main_window, @strong sender => move |_| {
@@ -726,9 +1552,17 @@ impl Widgets<AppModel, ()> for AppWidgets {
             }),
         );
         device_group.add_action(export_action);
+        let export_cheatsheet_action: RelmAction<ExportCheatsheet> = RelmAction::new_stateless(
+            glib::clone!(@strong main_window, @strong sender => move |_| {
+                show_export_cheatsheet_dialog(&main_window, glib::clone!(@strong sender => move |path| {
+                    send!(sender, AppMsg::ExportCheatsheet(path));
+                }));
+            }),
+        );
+        device_group.add_action(export_cheatsheet_action);
         let reset_action: RelmAction<ResetAction> = RelmAction::new_stateless(
             glib::clone!(@strong main_window, @strong sender => move |_| {
-                show_prompt_dialog(&main_window, "Reset sensitivity and all configurations for this device?",
+                show_prompt_dialog(&main_window, &gettext("Reset sensitivity and all configurations for this device?"),
                     glib::clone!(@strong sender => move || {
                         send!(sender, AppMsg::Reset);
                     }));
@@ -736,15 +1570,45 @@ impl Widgets<AppModel, ()> for AppWidgets {
         );
         // XXX Only show, make sensitive when device not connected?
         device_group.add_action(reset_action);
+        let reset_bindings_action: RelmAction<ResetBindingsAction> = RelmAction::new_stateless(
+            glib::clone!(@strong main_window, @strong sender => move |_| {
+                show_prompt_dialog(&main_window, &gettext("Reset all buttons in this configuration to their default bindings?"),
+                    glib::clone!(@strong sender => move || {
+                        send!(sender, AppMsg::ResetAll);
+                    }));
+            }),
+        );
+        device_group.add_action(reset_bindings_action);
         let remove_action: RelmAction<RemoveAction> = RelmAction::new_stateless(
             glib::clone!(@strong main_window, @strong sender => move |_| {
-                show_prompt_dialog(&main_window, "Remove device and saved configurations?",
+                show_prompt_dialog(&main_window, &gettext("Remove device and saved configurations?"),
                     glib::clone!(@strong sender => move || {
                         send!(sender, AppMsg::Remove);
                     }));
             }),
         );
         device_group.add_action(remove_action);
+        let factory_reset_action: RelmAction<FactoryResetAction> = RelmAction::new_stateless(
+            glib::clone!(@strong main_window, @strong sender => move |_| {
+                show_prompt_dialog(&main_window, &gettext("Restore the mouse to factory settings? This erases the bindings, sensitivity, and other settings stored on the device, and resets this device's configurations."),
+                    glib::clone!(@strong sender => move || {
+                        send!(sender, AppMsg::FactoryReset);
+                    }));
+            }),
+        );
+        device_group.add_action(factory_reset_action);
+
+        // `menu!` has no submenus
+        let advanced_menu = gio::Menu::new();
+        advanced_menu.append(
+            Some(&gettext("Factory Reset…")),
+            Some("device.factory_reset"),
+        );
+        advanced_menu.append(
+            Some(&gettext("Record Diagnostics")),
+            Some("app.record_diagnostics"),
+        );
+        menu.insert_submenu(6, Some(&gettext("Advanced")), &advanced_menu);
 
         let app_actions = app_group.into_action_group();
         let device_actions = device_group.into_action_group();
@@ -758,13 +1622,74 @@ impl Widgets<AppModel, ()> for AppWidgets {
                 glib::Continue(sender.send(AppMsg::SaveConfig).is_ok())
             }),
         );
+        glib::timeout_add_seconds(
+            BATTERY_REFRESH_SECONDS,
+            glib::clone!(@strong sender => move || {
+                glib::Continue(sender.send(AppMsg::RefreshBattery).is_ok())
+            }),
+        );
+        glib::timeout_add_seconds(
+            DPI_REFRESH_SECONDS,
+            glib::clone!(@strong sender => move || {
+                glib::Continue(sender.send(AppMsg::RefreshDpi).is_ok())
+            }),
+        );
     }
 
     fn post_view() {
+        if model.quit {
+            main_window.destroy();
+            return;
+        }
+
         if let Some(error) = model.error.as_ref() {
             show_error_dialog(&main_window, error);
         }
 
+        self.app_actions
+            .lookup_action("record_diagnostics")
+            .unwrap()
+            .downcast_ref::<gio::SimpleAction>()
+            .unwrap()
+            .set_state(&model.recording.is_some().to_variant());
+
+        if let Some(changes) = model.confirm_changes.as_ref() {
+            show_changes_dialog(
+                main_window,
+                changes,
+                glib::clone!(@strong sender => move |accepted| {
+                    if accepted {
+                        send!(sender, AppMsg::ApplyPending);
+                    } else {
+                        send!(sender, AppMsg::CancelPending);
+                    }
+                }),
+            );
+        }
+
+        if let Some((from, buttons)) = model.copy_binding.as_ref() {
+            let from = *from;
+            show_copy_binding_dialog(
+                main_window,
+                from,
+                buttons,
+                glib::clone!(@strong sender => move |buttons| {
+                    send!(sender, AppMsg::CopyBinding(from, buttons));
+                }),
+            );
+        }
+
+        if let Some((device_id, changes)) = model.reconcile.as_ref() {
+            let device_id = device_id.clone();
+            show_reconcile_dialog(
+                main_window,
+                changes,
+                glib::clone!(@strong sender => move |reconcile| {
+                    send!(sender, AppMsg::Reconcile(device_id.clone(), reconcile));
+                }),
+            );
+        }
+
         if model.selected_device.is_some() {
             let connected = model.device().map_or(false, |x| x.state.connected);
             self.device_actions
@@ -773,8 +1698,53 @@ impl Widgets<AppModel, ()> for AppWidgets {
                 .downcast_ref::<gio::SimpleAction>()
                 .unwrap()
                 .set_enabled(!connected);
+            let can_factory_reset = connected
+                && model
+                    .device()
+                    .and_then(|x| x.state.device_profile)
+                    .is_some_and(|x| x.factory_reset.is_some());
+            self.device_actions
+                .lookup_action("factory_reset")
+                .unwrap()
+                .downcast_ref::<gio::SimpleAction>()
+                .unwrap()
+                .set_enabled(can_factory_reset);
 
             self.stack.set_visible_child(&self.device_page);
+
+            if let Some(device) = model.device() {
+                let shown = (
+                    device.config.profile().dpi_stages.clone(),
+                    device.round_dpi(device.config.dpi),
+                );
+                if self.dpi_stages_shown.as_ref() != Some(&shown) {
+                    while let Some(child) = self.dpi_stages_box.first_child() {
+                        self.dpi_stages_box.remove(&child);
+                    }
+                    for stage in &shown.0 {
+                        let button = gtk4::Button::with_label(&stage.to_string());
+                        if *stage == shown.1 {
+                            button.add_css_class("suggested-action");
+                        }
+                        let dpi = f64::from(*stage);
+                        button.connect_clicked(glib::clone!(@strong sender => move |_| {
+                            send!(sender, AppMsg::SetDpi(dpi));
+                        }));
+                        self.dpi_stages_box.append(&button);
+                    }
+                    self.dpi_stages_shown = Some(shown);
+                }
+            }
+
+            // Range is only known once the device reports it
+            if let Some(device) = model.device() {
+                if let Some(max) = device.state.capabilities.cut_off_max.filter(|x| *x > 0) {
+                    cut_off_scale.set_range(0., max.into());
+                    if let Some(cut_off) = device.config.cut_off.or(device.state.cut_off) {
+                        cut_off_scale.set_value(cut_off.into());
+                    }
+                }
+            }
             let in_rename_config = self.profiles_stack.visible_child().as_ref()
                 == Some(self.profiles_entry.upcast_ref::<gtk4::Widget>());
             if model.rename_config {
@@ -826,10 +1796,10 @@ impl Widgets<AppModel, ()> for AppWidgets {
                         set_child = Some(&gtk4::Box) {
                             set_orientation: gtk4::Orientation::Vertical,
                             append = &gtk4::Label {
-                                set_label: "HP 930 series Creator Wireless Mouse" // TODO don't hard-code
+                                set_label: device_to_model(&device.config.device)
                             },
                             append = &gtk4::Label {
-                                set_label: &format!("Unique ID: {}", device.serial )
+                                set_label: &format!("{}: {}", gettext("Unique ID"), device.serial)
                             }
                         }
                     }
@@ -840,34 +1810,42 @@ impl Widgets<AppModel, ()> for AppWidgets {
 
         if let Some(device) = model.device() {
             if self.first_view_run || model.bindings_changed {
-                let bindings = &device.config.profile().bindings;
+                // Show what the device reports, once known
                 for (id, button) in &self.buttons {
                     if let Some(id) = model.swap_buttons(*id) {
+                        let binding = match &device.state.bindings {
+                            Some(bindings) => bindings.get(&id).cloned(),
+                            None => device.config.profile().binding(id),
+                        };
                         button.set_label(
-                            &bindings
-                                .get(&id)
-                                .map_or_else(|| id.def_binding().label.to_string(), |x| x.label()),
+                            &binding.map_or_else(|| id.def_binding().label(), |x| x.label()),
                         );
                     } else {
-                        button.set_label("Left Click");
+                        button.set_label(&gettext("Left Click"));
                     }
                 }
             }
 
             if self.first_view_run || model.profiles_changed {
                 let default_labels = &[
-                    "Configuration One",
-                    "Configuration Two",
-                    "Configuration Three",
-                    "Configuration Four",
+                    N_!("Configuration One"),
+                    N_!("Configuration Two"),
+                    N_!("Configuration Three"),
+                    N_!("Configuration Four"),
                 ];
                 let labels: Vec<_> = device
                     .config
                     .profiles()
                     .iter()
                     .enumerate()
-                    .map(|(n, profile)| profile.name.as_deref().unwrap_or(default_labels[n]))
+                    .map(|(n, profile)| {
+                        profile
+                            .name
+                            .clone()
+                            .unwrap_or_else(|| gettext(default_labels[n]))
+                    })
                     .collect();
+                let labels: Vec<_> = labels.iter().map(String::as_str).collect();
                 self.profiles_dropdown
                     .block_signal(&self.profiles_dropdown_signal);
                 self.profiles_dropdown
@@ -883,7 +1861,8 @@ impl Widgets<AppModel, ()> for AppWidgets {
                     &main_window,
                     &device.config.device,
                     &device.serial,
-                    device.state.firmware_version,
+                    device.state.capabilities.firmware_version,
+                    device.state.connected,
                 );
             }
         }
@@ -894,14 +1873,29 @@ impl Widgets<AppModel, ()> for AppWidgets {
 
 relm4::new_action_group!(AppActionGroup, "app");
 relm4::new_stateless_action!(AboutAction, AppActionGroup, "about");
+relm4::new_stateful_action!(
+    RecordDiagnosticsAction,
+    AppActionGroup,
+    "record_diagnostics",
+    (),
+    bool
+);
 
 relm4::new_action_group!(DeviceActionGroup, "device");
 relm4::new_stateless_action!(ImportConfig, DeviceActionGroup, "import_config");
 relm4::new_stateless_action!(ExportConfig, DeviceActionGroup, "export_config");
+relm4::new_stateless_action!(ExportCheatsheet, DeviceActionGroup, "export_cheatsheet");
 relm4::new_stateless_action!(ResetAction, DeviceActionGroup, "reset_config");
+relm4::new_stateless_action!(ResetBindingsAction, DeviceActionGroup, "reset_bindings");
 relm4::new_stateless_action!(RemoveAction, DeviceActionGroup, "remove");
+relm4::new_stateless_action!(FactoryResetAction, DeviceActionGroup, "factory_reset");
 
 fn main() {
+    // Warnings from the library and `app`, and more with `RUST_LOG`
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    i18n::init();
+
+    let mut recording = None;
     let mut args = env::args().skip(1);
     match args.next().as_deref() {
         Some("--device-monitor") => {
@@ -914,6 +1908,15 @@ fn main() {
             configs.insert(serial, MouseConfig::new("Brain".to_string()));
             save_config(configs.iter());
         }
+        // `--record-diagnostics <path> [--redact-serial]`
+        Some("--record-diagnostics") => {
+            let path = PathBuf::from(args.next().expect("Expected a path to record to"));
+            let redact = args.next().as_deref() == Some("--redact-serial");
+            match Recording::create(&path) {
+                Ok(x) => recording = Some((path, x.redact_serial(redact))),
+                Err(err) => eprintln!("Failed to record to {}: {}", path.display(), err),
+            }
+        }
         _ => {}
     }
 
@@ -937,7 +1940,7 @@ fn main() {
     );
 
     glib::set_prgname(Some("org.pop_os.mouseconfigurator"));
-    glib::set_application_name("Mouse Configurator");
+    glib::set_application_name(&gettext("Mouse Configurator"));
     let app = gtk4::Application::builder()
         .application_id("org.pop_os.mouseconfigurator")
         .build();
@@ -948,6 +1951,6 @@ fn main() {
         None
     };
 
-    let app = RelmApp::with_app(AppModel::new(device_monitor), app);
+    let app = RelmApp::with_app(AppModel::new(device_monitor, recording), app);
     app.run();
 }
@@ -1,142 +1,88 @@
 use relm4::{send, RelmWorker};
-use std::{
-    collections::HashMap,
-    env,
-    fs::File,
-    path::{Path, PathBuf},
-};
+use std::collections::HashMap;
 
 use super::{
-    bindings::{Entry, HardwareButton, PresetBinding},
+    battery::{BatteryAlert, BatteryAlerts},
+    bindings::{BindingRepr, HardwareButton},
+    command_binding,
+    config::{binding_buttons, Binding, MouseConfig},
     worker::{DeviceId, WorkerModel, WorkerMsg},
     AppModel,
 };
-use mouse_configurator::{Button, PressType};
-
-#[derive(Clone, serde::Serialize, serde::Deserialize, PartialEq)]
-#[serde(untagged)]
-pub enum Binding {
-    Preset(PresetBinding),
-    // TODO Custom
-    // Binding read from device, that isn't recognized
-    Unknown,
-}
-
-impl Binding {
-    pub fn label(&self) -> String {
-        match self {
-            Binding::Preset(binding) => binding.entry().label.to_string(),
-            Binding::Unknown => "Unknown".to_string(),
-        }
-    }
-}
-
-#[derive(Default, Clone, serde::Serialize, serde::Deserialize)]
-pub struct Profile {
-    pub name: Option<String>,
-    pub bindings: HashMap<HardwareButton, Binding>,
-    pub left_handed: bool,
-}
-
-#[derive(Clone, serde::Serialize, serde::Deserialize)]
-pub struct MouseConfig {
-    // Must always be non-empty
-    profiles: Vec<Profile>,
-    // Must Always be in range
-    profile_num: usize,
-    pub dpi: f64,
-    pub device: String,
-}
-
-impl MouseConfig {
-    // XXX Default DPI should depend on device model
-    pub fn new(device: String) -> Self {
-        let profiles = (0..4).map(|_| Profile::default()).collect();
-        Self {
-            profiles,
-            profile_num: 0,
-            device,
-            dpi: 1200.,
-        }
-    }
-
-    pub fn profile(&self) -> &Profile {
-        &self.profiles[self.profile_num]
-    }
-
-    pub fn profile_mut(&mut self) -> &mut Profile {
-        &mut self.profiles[self.profile_num]
-    }
-
-    pub fn profiles(&self) -> &[Profile] {
-        &self.profiles
-    }
-
-    pub fn profiles_mut(&mut self) -> &mut [Profile] {
-        &mut self.profiles
-    }
-
-    pub fn select_profile(&mut self, profile: usize) {
-        if profile < self.profiles.len() {
-            self.profile_num = profile;
-        }
-    }
-
-    pub fn profile_num(&self) -> usize {
-        self.profile_num
-    }
-
-    pub fn import(path: &Path) -> Result<Self, String> {
-        let file = File::open(path).map_err(|x| x.to_string())?;
-        serde_json::from_reader(file).map_err(|x| x.to_string())
-    }
-
-    pub fn export(&self, path: &Path) -> Result<(), String> {
-        let file = File::create(path).map_err(|x| x.to_string())?;
-        serde_json::to_writer_pretty(file, self).map_err(|x| x.to_string())
-    }
-}
+use mouse_configurator::{Button, Capabilities, DeviceCapabilities, DeviceProfile, Op, PressType};
 
 #[derive(Default)]
 pub struct MouseState {
     pub connected: bool,
     pub battery_percent: Option<u8>,
+    pub battery_alert: BatteryAlert,
     pub dpi: Option<f64>,
+    pub cut_off: Option<u8>,
+    // Last programmed, on devices with `DeviceProfile::dpi_stages`
+    pub dpi_stages: Option<Vec<u16>>,
     pub bindings: Option<HashMap<HardwareButton, Binding>>,
+    // Bindings read from the device differ from the profile, and the user
+    // hasn't chosen which to keep. The profile's aren't written until then.
+    pub bindings_held: bool,
+    // Restored to factory settings, so the next `Event::Mouse` is adopted
+    // into the config rather than overwritten from it
+    pub factory_reset: bool,
     pub left_handed: Option<bool>,
-    pub firmware_version: Option<(u16, u16, u16)>,
+    // Host slot the mouse is connected through, from its last report
+    pub host_id: Option<u8>,
+    // What the device supports, updated from its events
+    pub capabilities: DeviceCapabilities,
+    pub device_profile: Option<&'static DeviceProfile>,
+    // Status LED color, if the device reported one
+    pub led: Option<(u8, u8, u8)>,
+    pub battery_alerts: BatteryAlerts,
+    // Buttons written and total while writing bindings; kept at the total
+    // until the device confirms them
+    pub apply_progress: Option<(usize, usize)>,
 }
 
 impl MouseState {
     pub fn set_bindings_from_buttons(&mut self, host_id: u8, buttons: &[Button]) {
         let mut bindings = HashMap::new();
+        let mut down_up = HashMap::<HardwareButton, (Vec<Op>, Vec<Op>)>::new();
 
         for button in buttons {
             if button.host_id != host_id {
                 continue;
             }
-            let id = match HardwareButton::from_u8(button.id) {
+            let id = match HardwareButton::from_button(button) {
                 Some(id) => id,
                 None => {
                     eprintln!("Unrecognized button id: {}", button.id);
                     continue;
                 }
             };
-            let binding = match button.decode_action() {
-                Ok(action) => {
-                    if let Some(entry) = Entry::for_binding(&action) {
-                        Binding::Preset(entry.id)
-                    } else {
-                        eprintln!("Unrecognized action: {:?}", action);
-                        Binding::Unknown
-                    }
-                }
+            let action = match button.decode_action() {
+                Ok(action) => action,
                 Err(err) => {
                     eprintln!("Unable to decode button action: {}", err);
-                    Binding::Unknown
+                    if button.press_type == PressType::Normal {
+                        bindings.insert(id, Binding::Unknown);
+                    }
+                    continue;
                 }
             };
-            bindings.insert(id, binding);
+            match button.press_type {
+                PressType::Normal => {
+                    bindings.insert(id, BindingRepr::from_ops(&action).into());
+                }
+                PressType::Down => down_up.entry(id).or_default().0 = action,
+                PressType::Up => down_up.entry(id).or_default().1 = action,
+                // Not configured by this program
+                PressType::Long | PressType::Double => {}
+            }
+        }
+
+        // Down and up actions take the place of the normal one
+        for (id, (down, up)) in down_up {
+            if !down.is_empty() || !up.is_empty() {
+                bindings.insert(id, Binding::DownUp { down, up });
+            }
         }
 
         self.bindings = Some(bindings);
@@ -152,6 +98,18 @@ impl MouseState {
         *self = Self::default();
         self.connected = true;
     }
+
+    /// Whether the device reported its buttons support `press_type`. Unlike
+    /// `supports`, false until that is known.
+    pub fn supports_press_type(&self, press_type: PressType) -> bool {
+        self.capabilities.supports_press_type(press_type)
+    }
+
+    /// Whether the device answered the query for a feature when probed,
+    /// assuming it does until that is known
+    pub fn supports(&self, has: fn(&Capabilities) -> bool) -> bool {
+        self.capabilities.answers(has)
+    }
 }
 
 // Update bindings in state to match config, and generate messages to apply changes
@@ -162,10 +120,13 @@ pub(super) fn apply_profile_diff(
     worker: &RelmWorker<WorkerModel, AppModel>,
 ) {
     let config_profile = config.profile();
+    let clear_down_up = state.supports_press_type(PressType::Down);
 
-    if let Some(state_bindings) = state.bindings.as_mut() {
+    if let Some(state_bindings) = state.bindings.as_mut().filter(|_| !state.bindings_held) {
+        let mut changed_buttons = Vec::new();
         for i in HardwareButton::iter() {
-            let config_binding = config_profile.bindings.get(&i);
+            let config_binding = config_profile.binding(i);
+            let config_binding = config_binding.as_ref();
             let state_binding = state_bindings.get(&i);
             if state_binding != config_binding {
                 if let Some(binding) = config_binding {
@@ -173,18 +134,42 @@ pub(super) fn apply_profile_diff(
                 } else {
                     state_bindings.remove(&i);
                 }
-                let binding = match config_binding {
-                    Some(Binding::Preset(preset)) => &preset.entry().binding,
-                    Some(Binding::Unknown) => {
-                        // Shouldn't occur
+                if let Some(Binding::Command { command }) = config_binding {
+                    command_binding::register(i, command);
+                } else {
+                    command_binding::unregister(i);
+                }
+                let buttons = match binding_buttons(i, config_binding, clear_down_up) {
+                    Some(buttons) => buttons,
+                    None => {
+                        // Unknown; shouldn't occur
                         continue;
                     }
-                    None => &[] as &[_],
                 };
-                let button = Button::new(i as u8, 0, PressType::Normal, binding); // XXX
-                send!(worker, WorkerMsg::SetBinding(device_id.clone(), button));
+                changed_buttons.extend(buttons);
             }
         }
+        if !changed_buttons.is_empty() {
+            send!(
+                worker,
+                WorkerMsg::SetBindings(device_id.clone(), changed_buttons)
+            );
+        }
+    }
+
+    let has_dpi_stages = state
+        .device_profile
+        .is_some_and(|profile| profile.dpi_stages.is_some());
+    if has_dpi_stages
+        && state.capabilities.dpi_range.is_some()
+        && !config_profile.dpi_stages.is_empty()
+        && state.dpi_stages.as_ref() != Some(&config_profile.dpi_stages)
+    {
+        state.dpi_stages = Some(config_profile.dpi_stages.clone());
+        send!(
+            worker,
+            WorkerMsg::SetDpiStages(device_id.clone(), config_profile.dpi_stages.clone())
+        );
     }
 
     if let Some(state_left_handed) = state.left_handed.as_mut() {
@@ -197,66 +182,3 @@ pub(super) fn apply_profile_diff(
         }
     }
 }
-
-fn data_dir() -> PathBuf {
-    if let Ok(dir) = env::var("XDG_DATA_HOME") {
-        dir.into()
-    } else if let Ok(dir) = env::var("HOME") {
-        let mut path = PathBuf::from(dir);
-        path.push(".local/share");
-        path
-    } else {
-        panic!("`XDG_DATA_HOME` and `HOME` undefined")
-    }
-}
-
-fn app_data_dir() -> PathBuf {
-    let mut dir = data_dir();
-    dir.push("org.pop_os.mouseconfigurator");
-    if let Err(err) = std::fs::create_dir_all(&dir) {
-        panic!("Failed to create directory `{}`: {}", dir.display(), err);
-    }
-    dir
-}
-
-// TODO: format? Multiple files?
-// XXX error handling? Don't run `app_data_dir` every save?
-pub fn load_config() -> HashMap<String, MouseConfig> {
-    let mut path = app_data_dir();
-    path.push("config.json");
-
-    let file = match File::open(&path) {
-        Ok(file) => file,
-        Err(_) => {
-            return HashMap::new();
-        }
-    };
-
-    let mut config: HashMap<String, MouseConfig> = match serde_json::from_reader(file) {
-        Ok(config) => config,
-        Err(err) => {
-            eprintln!("Failed to load config: {}", err);
-            return HashMap::new();
-        }
-    };
-    // Ensure there are exactly 4 profiles. May change in future.
-    for mouse in config.values_mut() {
-        mouse.profiles.truncate(4);
-        while mouse.profiles.len() < 4 {
-            mouse.profiles.push(Profile::default());
-        }
-    }
-    config
-}
-
-// TODO: atomic replace
-// TODO: don't collect? `SeqIteratorVisitor`
-pub fn save_config<'a, T: Iterator<Item = (&'a String, &'a MouseConfig)>>(config: T) {
-    let mut path = app_data_dir();
-    path.push("config.json");
-
-    let config: HashMap<_, _> = config.collect();
-
-    let file = File::create(&path).unwrap();
-    serde_json::to_writer(file, &config).unwrap();
-}
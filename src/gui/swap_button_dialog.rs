@@ -1,7 +1,7 @@
 use gtk4::prelude::*;
 use relm4::{send, ComponentUpdate, Model, Sender, Widgets};
 
-use crate::AppMsg;
+use crate::{i18n::gettext, AppMsg};
 
 pub enum SwapButtonDialogMsg {
     Show(bool),
@@ -56,7 +56,7 @@ impl Widgets<SwapButtonDialogModel, super::AppModel> for SwapButtonDialogWidgets
             set_transient_for: parent!(Some(&parent_widgets.main_window)),
             set_modal: true,
             set_hide_on_close: true,
-            set_title: Some("Swap Left and Right Mouse Buttons"),
+            set_title: Some(&gettext("Swap Left and Right Mouse Buttons")),
             set_visible: watch!(model.shown),
             connect_response(sender) => move |_, _| {
                 send!(sender, SwapButtonDialogMsg::Close)
@@ -69,21 +69,21 @@ impl Widgets<SwapButtonDialogModel, super::AppModel> for SwapButtonDialogWidgets
                 set_margin_bottom: 12,
                 set_spacing: 6,
                 append = &gtk4::Label {
-                    set_label: "Select the button you use for primary functions such as selecting and dragging."
+                    set_label: &gettext("Select the button you use for primary functions such as selecting and dragging.")
                 },
                 append = &gtk4::Box {
                     set_halign: gtk4::Align::Center,
                     set_orientation: gtk4::Orientation::Horizontal,
                     append = &gtk4::ToggleButton {
                         set_active: watch! { !model.left_handed },
-                        set_label: "Left",
+                        set_label: &gettext("Left"),
                         connect_clicked(sender) => move |_| {
                             send!(sender, SwapButtonDialogMsg::SetLeftHanded(false))
                         }
                     },
                     append = &gtk4::ToggleButton {
                         set_active: watch! { model.left_handed },
-                        set_label: "Right",
+                        set_label: &gettext("Right"),
                         connect_clicked(sender) => move |_| {
                             send!(sender, SwapButtonDialogMsg::SetLeftHanded(true))
                         }
@@ -0,0 +1,270 @@
+//! Trying out key and media bindings without programming the mouse, by
+//! sending the keys from a temporary uinput keyboard. Needs write access to
+//! `/dev/uinput`. Keys go to whatever has focus, so shortcuts handled by the
+//! desktop are the most useful to try.
+
+use nix::{ioctl_none, ioctl_write_int, ioctl_write_ptr};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    mem,
+    os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+    ptr, slice, thread,
+    time::Duration,
+};
+
+use mouse_configurator::{Op, Value::*};
+
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const SYN_REPORT: u16 = 0;
+const BUS_VIRTUAL: u16 = 0x06;
+
+// Time for the compositor to pick up a new device before it sends keys
+const SETTLE_DELAY: Duration = Duration::from_millis(200);
+
+ioctl_none!(ui_dev_create, b'U', 1);
+ioctl_none!(ui_dev_destroy, b'U', 2);
+ioctl_write_ptr!(ui_dev_setup, b'U', 3, libc::uinput_setup);
+ioctl_write_int!(ui_set_evbit, b'U', 100);
+ioctl_write_int!(ui_set_keybit, b'U', 101);
+
+// Linux key codes for `MOD_*` bits, from bit 0
+const MODIFIER_KEYS: [u16; 8] = [29, 42, 56, 125, 97, 54, 100, 126];
+
+// Linux key codes for keyboard page usages 0x00-0x81, as in the kernel's
+// `hid_keyboard` table. 0 if there is none.
+#[rustfmt::skip]
+const KEYBOARD_KEYS: [u16; 0x82] = [
+      0,   0,   0,   0,  30,  48,  46,  32,  18,  33,  34,  35,  23,  36,  37,  38,
+     50,  49,  24,  25,  16,  19,  31,  20,  22,  47,  17,  45,  21,  44,   2,   3,
+      4,   5,   6,   7,   8,   9,  10,  11,  28,   1,  14,  15,  57,  12,  13,  26,
+     27,  43,  43,  39,  40,  41,  51,  52,  53,  58,  59,  60,  61,  62,  63,  64,
+     65,  66,  67,  68,  87,  88,  99,  70, 119, 110, 102, 104, 111, 107, 109, 106,
+    105, 108, 103,  69,  98,  55,  74,  78,  96,  79,  80,  81,  75,  76,  77,  71,
+     72,  73,  82,  83,  86, 127, 116, 117, 183, 184, 185, 186, 187, 188, 189, 190,
+    191, 192, 193, 194, 134, 138, 130, 132, 128, 129, 131, 137, 133, 135, 136, 113,
+    115, 114,
+];
+
+fn keyboard_key(usage: u8) -> Option<u16> {
+    match usage {
+        0xE0..=0xE7 => Some(MODIFIER_KEYS[usize::from(usage - 0xE0)]),
+        _ => KEYBOARD_KEYS.get(usize::from(usage)).copied(),
+    }
+    .filter(|code| *code != 0)
+}
+
+// Consumer page usages used by the presets
+fn media_key(usage: u16) -> Option<u16> {
+    Some(match usage {
+        0x6F => 225,  // KEY_BRIGHTNESSUP
+        0x70 => 224,  // KEY_BRIGHTNESSDOWN
+        0xB0 => 207,  // KEY_PLAY
+        0xB1 => 119,  // KEY_PAUSE
+        0xB3 => 208,  // KEY_FASTFORWARD
+        0xB4 => 168,  // KEY_REWIND
+        0xB5 => 163,  // KEY_NEXTSONG
+        0xB6 => 165,  // KEY_PREVIOUSSONG
+        0xCD => 164,  // KEY_PLAYPAUSE
+        0xE2 => 113,  // KEY_MUTE
+        0xE9 => 115,  // KEY_VOLUMEUP
+        0xEA => 114,  // KEY_VOLUMEDOWN
+        0x18A => 155, // KEY_MAIL
+        0x192 => 140, // KEY_CALC
+        0x194 => 144, // KEY_FILE
+        0x196 => 150, // KEY_WWW
+        0x223 => 172, // KEY_HOMEPAGE
+        0x224 => 158, // KEY_BACK
+        0x225 => 159, // KEY_FORWARD
+        _ => return None,
+    })
+}
+
+enum Step {
+    // Keys pressed together, then released
+    Keys(Vec<u16>),
+    Pause(Duration),
+}
+
+fn consts(payload: &[mouse_configurator::Value<i8>]) -> Option<Vec<u8>> {
+    payload
+        .iter()
+        .map(|value| match value {
+            Const(value) => Some(*value as u8),
+            _ => None,
+        })
+        .collect()
+}
+
+// `None` if the binding has anything other than keys, media keys, and delays
+fn steps(binding: &[Op]) -> Option<Vec<Step>> {
+    binding
+        .iter()
+        .map(|op| match op {
+            Op::Pause(Const(ms)) => Some(Step::Pause(Duration::from_millis((*ms).max(0) as u64))),
+            Op::Key { payload, .. } => {
+                let payload = consts(payload)?;
+                let (mods, keys) = payload.split_first()?;
+                let mut codes: Vec<u16> = (0..8)
+                    .filter(|bit| mods & (1 << bit) != 0)
+                    .map(|bit| MODIFIER_KEYS[bit])
+                    .collect();
+                for key in keys {
+                    codes.push(keyboard_key(*key)?);
+                }
+                Some(Step::Keys(codes))
+            }
+            Op::Media { payload, .. } => {
+                let payload = consts(payload)?;
+                let usage = match payload.as_slice() {
+                    [low] => u16::from(*low),
+                    [low, high] => u16::from_le_bytes([*low, *high]),
+                    _ => return None,
+                };
+                Some(Step::Keys(vec![media_key(usage)?]))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether `test` can send this binding
+pub fn can_test(binding: &[Op]) -> bool {
+    steps(binding).is_some_and(|steps| !steps.is_empty())
+}
+
+struct Keyboard(File);
+
+impl Keyboard {
+    fn new(codes: impl Iterator<Item = u16>) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open("/dev/uinput")?;
+        let fd = file.as_raw_fd();
+
+        let mut setup: libc::uinput_setup = unsafe { mem::zeroed() };
+        setup.id.bustype = BUS_VIRTUAL;
+        for (dst, src) in setup.name.iter_mut().zip(b"Mouse Configurator Test") {
+            *dst = *src as libc::c_char;
+        }
+        unsafe {
+            ui_set_evbit(fd, EV_KEY.into())?;
+            for code in codes {
+                ui_set_keybit(fd, code.into())?;
+            }
+            ui_dev_setup(fd, &setup)?;
+            ui_dev_create(fd)?;
+        }
+        Ok(Self(file))
+    }
+
+    fn emit(&mut self, type_: u16, code: u16, value: i32) -> io::Result<()> {
+        let event = libc::input_event {
+            time: libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            type_,
+            code,
+            value,
+        };
+        let data = unsafe {
+            slice::from_raw_parts(
+                ptr::addr_of!(event).cast::<u8>(),
+                mem::size_of::<libc::input_event>(),
+            )
+        };
+        self.0.write_all(data)
+    }
+
+    fn keys(&mut self, codes: &[u16], pressed: bool) -> io::Result<()> {
+        let iter: Box<dyn Iterator<Item = &u16>> = if pressed {
+            Box::new(codes.iter())
+        } else {
+            // Release modifiers last
+            Box::new(codes.iter().rev())
+        };
+        for code in iter {
+            self.emit(EV_KEY, *code, pressed.into())?;
+            self.emit(EV_SYN, SYN_REPORT, 0)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        let _ = unsafe { ui_dev_destroy(self.0.as_raw_fd()) };
+    }
+}
+
+fn send(steps: &[Step]) -> io::Result<()> {
+    let codes = steps.iter().flat_map(|step| match step {
+        Step::Keys(codes) => codes.as_slice(),
+        Step::Pause(_) => &[],
+    });
+    let mut keyboard = Keyboard::new(codes.copied())?;
+    thread::sleep(SETTLE_DELAY);
+    for step in steps {
+        match step {
+            Step::Keys(codes) => {
+                keyboard.keys(codes, true)?;
+                keyboard.keys(codes, false)?;
+            }
+            Step::Pause(duration) => thread::sleep(*duration),
+        }
+    }
+    // Let the last events be read before the device goes away
+    thread::sleep(SETTLE_DELAY);
+    Ok(())
+}
+
+/// Send the keys of `binding` once, in the background. Bindings `can_test`
+/// rejects are ignored.
+pub fn test(binding: &[Op]) {
+    let steps = match steps(binding) {
+        Some(steps) => steps,
+        None => return,
+    };
+    thread::spawn(move || {
+        if let Err(err) = send(&steps) {
+            eprintln!("Failed to send test keys through /dev/uinput: {}", err);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bindings::BINDINGS, keycode::*};
+    use mouse_configurator::MouseButton;
+
+    #[test]
+    fn steps_for_presets() {
+        let binding = [Op::key(
+            true,
+            vec![Const(MOD_Ctrl | MOD_Shift), Const(KEY_A)],
+        )];
+        match steps(&binding).unwrap().as_slice() {
+            [Step::Keys(codes)] => assert_eq!(codes, &[29, 42, 30]),
+            _ => panic!("Expected one step"),
+        }
+        let binding = [Op::media_usage(true, MEDIA_Calculator)];
+        match steps(&binding).unwrap().as_slice() {
+            [Step::Keys(codes)] => assert_eq!(codes, &[140]),
+            _ => panic!("Expected one step"),
+        }
+        assert!(!can_test(&[Op::mouse_click(MouseButton::Left)]));
+
+        // Every keyboard and media preset can be tried
+        for entry in BINDINGS.iter().flat_map(|x| &x.entries) {
+            let keys_only = entry
+                .binding
+                .iter()
+                .all(|op| matches!(op, Op::Key { .. } | Op::Media { .. }));
+            assert_eq!(can_test(&entry.binding), keys_only, "{}", entry.label);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use gtk4::prelude::*;
+use gtk4::{glib, pango, prelude::*};
 
 pub fn header_func(row: &gtk4::ListBoxRow, before: Option<&gtk4::ListBoxRow>) {
     if before.is_none() {
@@ -7,3 +7,17 @@ pub fn header_func(row: &gtk4::ListBoxRow, before: Option<&gtk4::ListBoxRow>) {
         row.set_header(Some(&gtk4::Separator::new(gtk4::Orientation::Horizontal)));
     }
 }
+
+/// Bold label, marked as a heading for assistive technologies
+pub fn heading(label: &str) -> gtk4::Label {
+    // `accessible-role` can only be set on construction
+    let heading: gtk4::Label = glib::Object::new(&[
+        ("accessible-role", &gtk4::AccessibleRole::Heading),
+        ("label", &label),
+    ])
+    .unwrap();
+    let attributes = pango::AttrList::new();
+    attributes.insert(pango::AttrInt::new_weight(pango::Weight::Bold));
+    heading.set_attributes(Some(&attributes));
+    heading
+}
@@ -1,22 +1,19 @@
 use gtk4::glib;
-use nix::{
-    errno::Errno,
-    poll::{poll, PollFd, PollFlags},
-};
 use relm4::{send, ComponentUpdate, Model, Sender};
 use std::{
-    collections::HashMap,
-    os::unix::io::AsRawFd,
+    collections::{HashMap, VecDeque},
+    io,
     path::PathBuf,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
     thread,
+    time::{Duration, Instant},
 };
 
 use super::{AppMsg, DeviceMonitorProcess};
-use mouse_configurator::{Button, Event, HpMouse, HpMouseEvents, ReadRes};
+use mouse_configurator::{Button, Capabilities, Event, HpMouse, HpMouseEvents, ReadRes, Recording};
 
 // Opaque ID, unique for one run of program, associated with one device
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
@@ -27,14 +24,44 @@ pub enum WorkerMsg {
     AddDevice(PathBuf, HpMouse),
     Disconnect(DeviceId),
     SetDpi(DeviceId, u16),
+    SetDpiStages(DeviceId, Vec<u16>),
     SetLeftHanded(DeviceId, bool),
-    SetBinding(DeviceId, Button),
+    SetCutOff(DeviceId, u8),
+    SetLed(DeviceId, u8, u8, u8),
+    SetBindings(DeviceId, Vec<Button>),
+    // Write the next of the queued buttons
+    WriteButton(DeviceId),
+    FactoryReset(DeviceId),
+    // Result of the reader thread probing the device's capabilities
+    Probed(DeviceId, io::Result<Capabilities>),
     HasFirmware(DeviceId),
+    QueryBattery(DeviceId),
+    QueryDpi(DeviceId),
+    VerifyButtons(DeviceId, u8, Vec<Button>),
+    // Record reports of all devices, or stop with `None`
+    Record(Option<Recording>),
+    // Finish writing, then ask the app to quit. Waits until the deadline for
+    // written buttons to be verified.
+    Shutdown(Instant),
 }
 
+// Time between checks for verified buttons while shutting down
+const SHUTDOWN_POLL: Duration = Duration::from_millis(50);
+// Longest to show progress after writing, if the device doesn't answer
+const VERIFY_WAIT: Duration = Duration::from_secs(2);
+
 pub struct WorkerModel {
     next_device_id: DeviceId,
-    devices: HashMap<DeviceId, (PathBuf, HpMouse)>, // associate with udev device?
+    devices: HashMap<DeviceId, (PathBuf, Arc<HpMouse>)>, // associate with udev device?
+    // Written buttons to check against the next `Event::Buttons`, and
+    // whether they have already been retried
+    unverified: HashMap<DeviceId, Vec<(Button, bool)>>,
+    // Buttons not yet written, and how many have been written since the
+    // queue was empty. Written one at a time from idle callbacks, so the
+    // window can show progress in between.
+    writes: HashMap<DeviceId, (VecDeque<Button>, usize)>,
+    // Checked by reader threads before each read
+    recording: Arc<Mutex<Option<Recording>>>,
 }
 
 impl Model for WorkerModel {
@@ -54,17 +81,16 @@ impl WorkerModel {
         let device_id = self.next_device_id.clone();
         send!(parent_sender, super::AppMsg::DeviceAdded(device_id.clone()));
 
-        let events = mouse.read();
+        let mouse = Arc::new(mouse);
+        let mut events = mouse.read();
+        events.record(self.recording.lock().unwrap().clone());
         let running = Arc::new(AtomicBool::new(true));
         thread::spawn(
-            glib::clone!(@strong device_id, @strong running, @strong sender, @strong parent_sender => move || {
-                reader_thread(device_id, running, events, sender, parent_sender)
+            glib::clone!(@strong device_id, @strong mouse, @strong running, @strong self.recording as recording, @strong sender, @strong parent_sender => move || {
+                reader_thread(device_id, mouse, running, recording, events, sender, parent_sender)
             }),
         );
 
-        // XXX errors
-        let _ = mouse.query_firmware().unwrap();
-
         self.devices
             .insert(self.next_device_id.clone(), (path, mouse));
         self.next_device_id.0 += 1;
@@ -72,10 +98,15 @@ impl WorkerModel {
 }
 
 impl ComponentUpdate<super::AppModel> for WorkerModel {
-    fn init_model(_parent_model: &super::AppModel) -> Self {
+    fn init_model(parent_model: &super::AppModel) -> Self {
         WorkerModel {
             next_device_id: DeviceId(0),
             devices: HashMap::new(),
+            unverified: HashMap::new(),
+            writes: HashMap::new(),
+            recording: Arc::new(Mutex::new(
+                parent_model.recording.as_ref().map(|(_, x)| x.clone()),
+            )),
         }
     }
 
@@ -89,6 +120,8 @@ impl ComponentUpdate<super::AppModel> for WorkerModel {
         match msg {
             WorkerMsg::Disconnect(id) => {
                 self.devices.remove(&id);
+                self.unverified.remove(&id);
+                self.writes.remove(&id);
                 send!(parent_sender, super::AppMsg::DeviceRemoved(id));
                 eprintln!("End reader");
             }
@@ -100,31 +133,197 @@ impl ComponentUpdate<super::AppModel> for WorkerModel {
             WorkerMsg::AddDevice(path, mouse) => {
                 self.add_device(path, mouse, &sender, &parent_sender);
             }
+            WorkerMsg::Probed(id, res) => {
+                if let Err(err) = res {
+                    eprintln!("Failed to probe device capabilities: {}", err);
+                }
+                // Probing discards the replies it waits for, so ask again
+                // for the reader to pass the firmware on
+                if let Some((_, mouse)) = &self.devices.get(&id) {
+                    if let Err(err) = mouse.query_firmware() {
+                        eprintln!("Failed to query firmware: {}", err);
+                    }
+                }
+            }
             WorkerMsg::HasFirmware(id) => {
                 // XXX errors
                 let mouse = &self.devices.get(&id).unwrap().1;
+                send!(
+                    parent_sender,
+                    super::AppMsg::Capabilities(id.clone(), mouse.profile(), mouse.capabilities())
+                );
                 let _ = mouse.query_battery().unwrap();
-                let _ = mouse.query_button().unwrap();
-                let _ = mouse.query_dpi().unwrap();
+                // Unsupported queries fail without writing
+                let _ = mouse.query_button();
+                let _ = mouse.query_dpi();
+                let _ = mouse.query_led();
+            }
+            WorkerMsg::QueryBattery(id) => {
+                if let Some((_, mouse)) = &self.devices.get(&id) {
+                    // XXX error
+                    let _ = mouse.query_battery();
+                }
+            }
+            WorkerMsg::QueryDpi(id) => {
+                if let Some((_, mouse)) = &self.devices.get(&id) {
+                    // XXX error
+                    let _ = mouse.query_dpi();
+                }
             }
             WorkerMsg::SetDpi(id, value) => {
                 if let Some((_, mouse)) = &self.devices.get(&id) {
                     // XXX error
-                    let _ = mouse.set_dpi(value);
+                    let _ = mouse.set_dpi(0, value, false);
+                    // Read back, so the displayed DPI is what the device reports
+                    let _ = mouse.query_dpi();
                 }
             }
-            WorkerMsg::SetLeftHanded(id, value) => {
+            WorkerMsg::SetDpiStages(id, stages) => {
+                if let Some((_, mouse)) = &self.devices.get(&id) {
+                    // XXX error
+                    let _ = mouse.set_dpi_stages(&stages, false);
+                }
+            }
+            WorkerMsg::SetCutOff(id, value) => {
+                if let Some((_, mouse)) = &self.devices.get(&id) {
+                    // XXX error
+                    let _ = mouse.set_cut_off(value, false);
+                    // Read back, to show the value the device clamped it to
+                    let _ = mouse.query_dpi();
+                }
+            }
+            WorkerMsg::SetLed(id, red, green, blue) => {
+                if let Some((_, mouse)) = &self.devices.get(&id) {
+                    // XXX error
+                    let _ = mouse.set_led(red, green, blue, false);
+                    let _ = mouse.query_led();
+                }
+            }
+            WorkerMsg::FactoryReset(id) => {
                 if let Some((_, mouse)) = &self.devices.get(&id) {
                     // XXX error
-                    let _ = mouse.set_left_handed(value);
+                    let _ = mouse.send_factory_reset();
                 }
             }
-            WorkerMsg::SetBinding(id, button) => {
+            WorkerMsg::SetLeftHanded(id, value) => {
                 if let Some((_, mouse)) = &self.devices.get(&id) {
                     // XXX error
-                    let _ = mouse.set_button(button, false);
+                    let _ = mouse.set_left_handed(value, false);
+                }
+            }
+            WorkerMsg::SetBindings(id, buttons) => {
+                if !self.devices.contains_key(&id) {
+                    return;
+                }
+                let (queue, _) = self.writes.entry(id.clone()).or_default();
+                let start = queue.is_empty();
+                for button in buttons {
+                    queue.retain(|x| {
+                        x.id != button.id
+                            || x.host_id != button.host_id
+                            || x.press_type != button.press_type
+                    });
+                    queue.push_back(button);
+                }
+                if start {
+                    send!(sender, WorkerMsg::WriteButton(id));
+                }
+            }
+            WorkerMsg::WriteButton(id) => {
+                let mouse = match self.devices.get(&id) {
+                    Some((_, mouse)) => mouse,
+                    None => return,
+                };
+                let (queue, written) = match self.writes.get_mut(&id) {
+                    Some(writes) => writes,
+                    None => return,
+                };
+                let button = match queue.pop_front() {
+                    Some(button) => button,
+                    None => return,
+                };
+                // XXX error
+                let _ = mouse.set_button(button.clone(), false);
+                *written += 1;
+                let progress = (*written, *written + queue.len());
+                let unverified = self.unverified.entry(id.clone()).or_default();
+                unverified.retain(|(x, _)| {
+                    x.id != button.id
+                        || x.host_id != button.host_id
+                        || x.press_type != button.press_type
+                });
+                unverified.push((button, false));
+                if queue.is_empty() {
+                    self.writes.remove(&id);
+                    let _ = mouse.query_button();
+                    glib::timeout_add_local_once(
+                        VERIFY_WAIT,
+                        glib::clone!(@strong parent_sender, @strong id => move || {
+                            send!(parent_sender, super::AppMsg::ApplyProgress(id, None));
+                        }),
+                    );
+                } else {
+                    glib::idle_add_local_once(glib::clone!(@strong sender, @strong id => move || {
+                        send!(sender, WorkerMsg::WriteButton(id));
+                    }));
+                }
+                send!(
+                    parent_sender,
+                    super::AppMsg::ApplyProgress(id, Some(progress))
+                );
+            }
+            WorkerMsg::VerifyButtons(id, host_id, buttons) => {
+                let mouse = match self.devices.get(&id) {
+                    Some((_, mouse)) => mouse,
+                    None => return,
+                };
+                let mut retry = Vec::new();
+                let mut failed = false;
+                for (button, retried) in self.unverified.remove(&id).unwrap_or_default() {
+                    if button.applied_in(host_id, &buttons) {
+                        continue;
+                    } else if !retried {
+                        let _ = mouse.set_button(button.clone(), false);
+                        retry.push((button, true));
+                    } else {
+                        eprintln!("Device did not apply binding for button {}", button.id);
+                        failed = true;
+                    }
+                }
+                if failed {
+                    send!(
+                        parent_sender,
+                        super::AppMsg::BindingNotApplied(id.clone(), host_id, buttons)
+                    );
+                }
+                if !retry.is_empty() {
+                    let _ = mouse.query_button();
+                    self.unverified.insert(id, retry);
+                } else if !self.writes.contains_key(&id) {
+                    send!(parent_sender, super::AppMsg::ApplyProgress(id, None));
                 }
             }
+            WorkerMsg::Record(recording) => {
+                *self.recording.lock().unwrap() = recording;
+            }
+            WorkerMsg::Shutdown(deadline) => {
+                let pending = !self.unverified.is_empty() || !self.writes.is_empty();
+                if pending && Instant::now() < deadline {
+                    glib::timeout_add_local_once(SHUTDOWN_POLL, move || {
+                        send!(sender, WorkerMsg::Shutdown(deadline));
+                    });
+                    return;
+                }
+                for (_, mouse) in self.devices.values() {
+                    mouse.flush();
+                }
+                if !self.writes.is_empty() {
+                    eprintln!("Exiting before writing all bindings");
+                } else if !self.unverified.is_empty() {
+                    eprintln!("Exiting before the device confirmed all bindings");
+                }
+                send!(parent_sender, super::AppMsg::Quit);
+            }
         }
     }
 }
@@ -140,32 +339,67 @@ fn device_monitor_thread(device_monitor: DeviceMonitorProcess, sender: Sender<Wo
 
 fn reader_thread(
     device_id: DeviceId,
+    mouse: Arc<HpMouse>,
     running: Arc<AtomicBool>,
+    recording: Arc<Mutex<Option<Recording>>>,
     mut events: HpMouseEvents,
     sender: Sender<WorkerMsg>,
     parent_sender: Sender<super::AppMsg>,
 ) {
+    // Before reading events, since probing reads the replies. Here rather
+    // than on the main thread, since it waits for each one.
+    let res = mouse.probe(&mut events);
+    drop(mouse);
+    send!(sender, WorkerMsg::Probed(device_id.clone(), res));
+
     while running.load(Ordering::SeqCst) {
-        let fd = PollFd::new(events.as_raw_fd(), PollFlags::POLLIN);
-        match poll(&mut [fd], 200) {
-            Ok(0) | Err(Errno::EINTR) => {
+        match events.poll(200) {
+            Ok(false) => {
                 continue;
             }
-            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {
+                continue;
+            }
+            Ok(true) => {}
             Err(err) => panic!("Error polling events: {}", err),
         }
 
+        events.record(recording.lock().unwrap().clone());
+
         match events.read() {
             Ok(ReadRes::EOF) => {
                 break;
             }
             Ok(ReadRes::Packet(event)) => {
-                if let Event::Firmware { .. } = &event {
+                let has_firmware = matches!(event, Event::Firmware { .. });
+                if let Event::Buttons {
+                    host_id, buttons, ..
+                } = &event
+                {
+                    send!(
+                        sender,
+                        WorkerMsg::VerifyButtons(device_id.clone(), *host_id, buttons.clone())
+                    );
+                }
+                if events.is_unsolicited(&event) {
+                    send!(
+                        parent_sender,
+                        AppMsg::DeviceStateChanged(device_id.clone(), event)
+                    );
+                } else {
+                    send!(parent_sender, AppMsg::Event(device_id.clone(), event));
+                }
+                // After the event, so the app knows the device before the
+                // messages `HasFirmware` leads to
+                if has_firmware {
                     send!(sender, WorkerMsg::HasFirmware(device_id.clone()));
                 }
-                send!(parent_sender, AppMsg::Event(device_id.clone(), event))
             }
-            Ok(ReadRes::Continue) => {}
+            Ok(ReadRes::Continue) | Ok(ReadRes::WouldBlock) => {}
+            Err(err) if err.is_gone() => {
+                eprintln!("Device gone: {}", err);
+                break;
+            }
             Err(err) => eprintln!("Error reading event: {}", err), // XXX handle error
         }
     }
@@ -1,11 +1,30 @@
+use nix::poll::{poll, PollFd, PollFlags};
 use std::{
+    fmt,
     fs::File,
     io,
     os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
     path::Path,
 };
 
+/// Backend for reading and writing raw HID reports
+pub trait HidDevice: fmt::Debug + Send + Sync {
+    fn read(&self, buf: &mut [u8]) -> io::Result<usize>;
+
+    fn write(&self, buf: &[u8]) -> io::Result<usize>;
+
+    /// Wait up to `timeout` milliseconds, or indefinitely if negative, for a
+    /// report to be available. Returns `false` on timeout.
+    fn poll(&self, timeout: i32) -> io::Result<bool>;
+
+    /// File descriptor, for backends that have one
+    fn raw_fd(&self) -> Option<RawFd> {
+        None
+    }
+}
+
 // TODO: Use `OwnedFd` when stable
+/// hidraw device node
 #[derive(Debug)]
 pub struct Hid(RawFd);
 
@@ -37,14 +56,119 @@ impl Hid {
                 .into_raw_fd(),
         ))
     }
+}
 
-    pub fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+impl HidDevice for Hid {
+    fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
         let length = nix::unistd::read(self.0, buf)?;
         Ok(length)
     }
 
-    pub fn write(&self, buf: &[u8]) -> io::Result<usize> {
+    fn write(&self, buf: &[u8]) -> io::Result<usize> {
         let length = nix::unistd::write(self.0, buf)?;
         Ok(length)
     }
+
+    fn poll(&self, timeout: i32) -> io::Result<bool> {
+        let fd = PollFd::new(self.0, PollFlags::POLLIN);
+        Ok(poll(&mut [fd], timeout)? != 0)
+    }
+
+    fn raw_fd(&self) -> Option<RawFd> {
+        Some(self.0)
+    }
+}
+
+#[cfg(feature = "hidapi")]
+pub use self::hidapi_device::HidApiDevice;
+
+#[cfg(feature = "hidapi")]
+mod hidapi_device {
+    use std::{
+        fmt, io,
+        sync::Mutex,
+        time::{Duration, Instant},
+    };
+
+    use super::HidDevice;
+
+    // How long `poll` holds the device lock at a time, so writes aren't
+    // blocked for the full timeout
+    const POLL_INTERVAL: i32 = 10;
+
+    fn to_io_error(err: hidapi::HidError) -> io::Error {
+        io::Error::other(err.to_string())
+    }
+
+    /// Device opened through the `hidapi` crate
+    pub struct HidApiDevice {
+        dev: Mutex<hidapi::HidDevice>,
+        // hidapi can't poll without reading, so `poll` keeps the report here
+        pending: Mutex<Option<Vec<u8>>>,
+    }
+
+    impl HidApiDevice {
+        pub fn new(dev: hidapi::HidDevice) -> Self {
+            Self {
+                dev: Mutex::new(dev),
+                pending: Mutex::new(None),
+            }
+        }
+
+        pub fn open(api: &hidapi::HidApi, info: &hidapi::DeviceInfo) -> io::Result<Self> {
+            Ok(Self::new(info.open_device(api).map_err(to_io_error)?))
+        }
+    }
+
+    impl fmt::Debug for HidApiDevice {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("HidApiDevice").finish_non_exhaustive()
+        }
+    }
+
+    impl HidDevice for HidApiDevice {
+        fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+            if let Some(report) = self.pending.lock().unwrap().take() {
+                let len = report.len().min(buf.len());
+                buf[..len].copy_from_slice(&report[..len]);
+                return Ok(len);
+            }
+            self.dev.lock().unwrap().read(buf).map_err(to_io_error)
+        }
+
+        fn write(&self, buf: &[u8]) -> io::Result<usize> {
+            self.dev.lock().unwrap().write(buf).map_err(to_io_error)
+        }
+
+        fn poll(&self, timeout: i32) -> io::Result<bool> {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.is_some() {
+                return Ok(true);
+            }
+
+            let deadline = u64::try_from(timeout)
+                .ok()
+                .map(|x| Instant::now() + Duration::from_millis(x));
+            let mut buf = [0; 4096];
+            loop {
+                let interval = match deadline {
+                    Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                        Some(remaining) => (remaining.as_millis() as i32).min(POLL_INTERVAL),
+                        None => return Ok(false),
+                    },
+                    None => POLL_INTERVAL,
+                };
+                let len = self
+                    .dev
+                    .lock()
+                    .unwrap()
+                    .read_timeout(&mut buf, interval)
+                    .map_err(to_io_error)?;
+                if len != 0 {
+                    *pending = Some(buf[..len].to_vec());
+                    return Ok(true);
+                }
+            }
+        }
+    }
 }
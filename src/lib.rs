@@ -1,86 +1,634 @@
 use std::{
     io,
-    os::unix::io::{AsRawFd, FromRawFd, RawFd},
+    os::unix::io::{FromRawFd, RawFd},
     path::Path,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, AtomicU8, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
+#[cfg(feature = "app")]
+pub mod app;
 pub mod button;
-pub use button::{Button, Op, PressType, Value};
+pub use button::{Button, MouseButton, Op, PressType, Value};
+mod device_profile;
+pub use device_profile::{
+    DeviceProfile, DpiStages, FactoryReset, Led, ProfileSlots, DEVICE_PROFILES, HP_930,
+};
 mod enumerate;
+#[cfg(feature = "hidapi")]
+pub use enumerate::enumerate_hidapi;
 pub use enumerate::{enumerate, monitor, DeviceInfo};
 mod event;
-pub use event::{Event, HpMouseEvents, ReadRes};
+pub use event::{
+    ButtonsCapabilities, Diagnostics, Event, FirmwareVersion, HpMouseEvents, ProtocolError,
+    ReadError, ReadRes,
+};
+use event::{Hex, Query, Reported};
 mod hid;
 use hid::Hid;
+#[cfg(feature = "hidapi")]
+pub use hid::HidApiDevice;
+pub use hid::HidDevice;
+mod mock;
+pub use mock::MockHid;
+mod reconnect;
+pub use reconnect::ReconnectingEvents;
+mod record;
+pub use record::Recording;
+mod session;
+pub use session::DeviceSession;
+#[cfg(feature = "async")]
+mod stream;
+#[cfg(feature = "async")]
+pub use stream::HpMouseEventStream;
+
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(1);
+// Most queries kept waiting for a reply, for a caller that queries without
+// ever reading events
+const MAX_QUERIES: usize = 32;
+// Command flag to apply a setting without saving it to flash
+const NO_SAVE_TO_FLASH: u8 = 1 << 7;
+// Payload bytes in one output report
+const REPORT_PAYLOAD_LEN: usize = 16;
+// Longest packet the header's 10-bit length can describe. Its 6-bit sequence
+// number allows 64 reports, a byte more.
+const MAX_PACKET_LEN: usize = 1023;
+/// Longest encoded action `set_button` or `exec_button` can write. Longer
+/// packets than one report holds are split across several, but the button
+/// list the device reports back must still fit the limit it is reassembled
+/// to, with seven buttons this long.
+///
+/// Not confirmed on hardware: this is what the packet format allows, not
+/// what the firmware is known to accept. Actions of up to 11 bytes, which
+/// fit in one report, are the longest known to work.
+pub const MAX_ACTION_LEN: usize = 64;
+/// Unit of the `auto_report_delay` byte of `Event::Battery`. The delay of 6
+/// `query_battery` has always sent gives a battery report about once a
+/// minute.
+pub const AUTO_REPORT_UNIT: Duration = Duration::from_secs(10);
+// `auto_report_delay` sent by default
+const DEFAULT_AUTO_REPORT_DELAY: u8 = 6;
+// Battery command value leaving a setting unchanged
+const BATTERY_KEEP: u8 = 0xFF;
+
+/// Interval between battery reports the device pushes for an
+/// `auto_report_delay` byte. `None` for 0, which turns them off.
+pub fn auto_report_interval(delay: u8) -> Option<Duration> {
+    (delay != 0).then(|| AUTO_REPORT_UNIT * delay.into())
+}
 
-const HP_SIGNATURE: u16 = 0xCF3;
+/// Queries a device answered in `HpMouse::probe`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// `query_firmware`
+    pub firmware: bool,
+    /// `query_dpi`, for DPI, handedness, and lift-off distance
+    pub mouse: bool,
+    /// `query_button`, for programmable buttons
+    pub buttons: bool,
+}
+
+/// Everything known about what a device supports, gathered from `probe` and
+/// the `Event::Firmware`, `Event::Mouse`, and `Event::Buttons` read since.
+/// Fields are `None` until the event reporting them is read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DeviceCapabilities {
+    pub queries: Option<Capabilities>,
+    pub firmware_version: Option<FirmwareVersion>,
+    pub buttons: Option<ButtonsCapabilities>,
+    /// Minimum, maximum, and step
+    pub dpi_range: Option<(u16, u16, u16)>,
+    /// 0 if lift-off distance can't be set
+    pub cut_off_max: Option<u8>,
+    pub left_handed: Option<bool>,
+    /// Settings can be applied without saving them to flash
+    pub no_save_to_flash: Option<bool>,
+    /// Programmable buttons, from the `DeviceProfile`
+    pub button_count: u8,
+}
+
+impl DeviceCapabilities {
+    /// Record what `event` reports, for a consumer following the events of
+    /// a device rather than asking `HpMouse::device_capabilities`
+    pub fn update(&mut self, event: &Event) {
+        match event {
+            Event::Firmware { version, .. } => self.firmware_version = Some(*version),
+            Event::Mouse {
+                min_dpi,
+                max_dpi,
+                step_dpi,
+                cut_off_max,
+                support_left_handed,
+                support_no_save_to_flash,
+                ..
+            } => {
+                self.dpi_range = Some((*min_dpi, *max_dpi, *step_dpi));
+                self.cut_off_max = Some(*cut_off_max);
+                self.left_handed = Some(*support_left_handed);
+                self.no_save_to_flash = Some(*support_no_save_to_flash);
+            }
+            Event::Buttons { capabilities, .. } => self.buttons = Some(*capabilities),
+            _ => {}
+        }
+    }
+
+    /// Whether the device answered the query for a feature when probed,
+    /// assuming it does until that is known
+    pub fn answers(&self, has: fn(&Capabilities) -> bool) -> bool {
+        match &self.queries {
+            Some(queries) => has(queries),
+            None => true,
+        }
+    }
+
+    /// Whether the buttons reported they support `press_type`. False until
+    /// that is known.
+    pub fn supports_press_type(&self, press_type: PressType) -> bool {
+        self.buttons.is_some_and(|x| x.supports(press_type))
+    }
+
+    pub fn long_press(&self) -> bool {
+        self.supports_press_type(PressType::Long)
+    }
 
+    pub fn double_press(&self) -> bool {
+        self.supports_press_type(PressType::Double)
+    }
+
+    /// Whether lift-off distance can be set
+    pub fn cut_off(&self) -> bool {
+        self.cut_off_max.is_some_and(|max| max > 0)
+    }
+}
+
+/// Connection to a mouse. Commands take `&self`, so one `HpMouse` can be
+/// shared between threads, for instance with an `Arc`.
+///
+/// Reports are written under a lock, so the reports of one command are never
+/// interleaved with another's. Reading doesn't take the lock: `read` returns
+/// an `HpMouseEvents` that can run on its own thread while commands are
+/// written. hidraw transfers exactly one report per `read` and `write`, so a
+/// read never sees part of a written report. There should be only one
+/// `HpMouseEvents` reading at a time, since each report goes to one reader.
 #[derive(Debug)]
 pub struct HpMouse {
-    dev: Arc<Hid>,
+    dev: Arc<dyn HidDevice>,
+    profile: &'static DeviceProfile,
+    // Set by `HpMouseEvents` when it reads `Event::Firmware` and `Event::Mouse`
+    reported: Arc<Mutex<Reported>>,
+    // Held while writing a command's reports
+    write_lock: Mutex<()>,
+    // Set by `probe`
+    capabilities: Mutex<Option<Capabilities>>,
+    // Set by `set_dry_run`
+    dry_run: AtomicBool,
+    // Sent with battery queries; set by `set_auto_report`
+    auto_report_delay: AtomicU8,
 }
 
 impl HpMouse {
+    /// Open hidraw device node of an HP 930. Use `DeviceInfo::open` to match
+    /// the model.
     pub fn open_devnode(path: &Path) -> io::Result<Self> {
-        Ok(Self {
-            dev: Arc::new(Hid::open(path)?),
-        })
+        Self::open_devnode_with_profile(path, &HP_930)
+    }
+
+    pub fn open_devnode_with_profile(
+        path: &Path,
+        profile: &'static DeviceProfile,
+    ) -> io::Result<Self> {
+        Ok(Self::from_device_with_profile(Hid::open(path)?, profile))
+    }
+
+    /// Open a mouse by hidraw device node, such as `/dev/hidraw3`, or by
+    /// serial number, returning it with the events read from it. Both share
+    /// the one open device.
+    ///
+    /// Opening by serial queries the firmware of each connected device to
+    /// find it, so its `Event::Firmware` has already been read from the
+    /// returned events; `firmware_version` has it.
+    pub fn open(path_or_serial: &str) -> io::Result<(Self, HpMouseEvents)> {
+        let path = Path::new(path_or_serial);
+        if path.is_absolute() {
+            let profile = enumerate()
+                .ok()
+                .and_then(|devices| devices.into_iter().find(|x| x.devnode == path))
+                .map_or(&HP_930, |x| x.profile);
+            let mouse = Self::open_devnode_with_profile(path, profile)?;
+            let events = mouse.read();
+            return Ok((mouse, events));
+        }
+        match reconnect::open_serial(path_or_serial) {
+            Some((mouse, events, _)) => Ok((mouse, events)),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no connected mouse with serial {}", path_or_serial),
+            )),
+        }
+    }
+
+    /// Use `dev` as an HP 930
+    pub fn from_device<T: HidDevice + 'static>(dev: T) -> Self {
+        Self::from_device_with_profile(dev, &HP_930)
+    }
+
+    pub fn from_device_with_profile<T: HidDevice + 'static>(
+        dev: T,
+        profile: &'static DeviceProfile,
+    ) -> Self {
+        Self {
+            dev: Arc::new(dev),
+            profile,
+            reported: Arc::new(Mutex::new(Reported::default())),
+            write_lock: Mutex::new(()),
+            capabilities: Mutex::new(None),
+            dry_run: AtomicBool::new(false),
+            auto_report_delay: AtomicU8::new(DEFAULT_AUTO_REPORT_DELAY),
+        }
+    }
+
+    /// Log the reports commands would write, without writing them, to check
+    /// what a command sends without changing the device. Queries aren't
+    /// written either, so no replies to them are read.
+    pub fn set_dry_run(&self, dry_run: bool) {
+        self.dry_run.store(dry_run, Ordering::SeqCst);
+    }
+
+    pub fn profile(&self) -> &'static DeviceProfile {
+        self.profile
+    }
+
+    /// File descriptor of the device, or `None` for a backend without one,
+    /// such as `HidApiDevice` or `MockHid`
+    pub fn raw_fd(&self) -> Option<RawFd> {
+        self.dev.raw_fd()
     }
 
-    //TODO: support multi report packets
+    /// Version from the last `Event::Firmware` read, if any
+    pub fn firmware_version(&self) -> Option<FirmwareVersion> {
+        self.reported.lock().unwrap().firmware_version
+    }
+
+    /// Result of the last `probe`, if any
+    pub fn capabilities(&self) -> Option<Capabilities> {
+        *self.capabilities.lock().unwrap()
+    }
+
+    /// What is known so far about what the device supports
+    pub fn device_capabilities(&self) -> DeviceCapabilities {
+        let reported = self.reported.lock().unwrap();
+        DeviceCapabilities {
+            queries: self.capabilities(),
+            firmware_version: reported.firmware_version,
+            buttons: reported.buttons_capabilities,
+            dpi_range: reported.dpi_range,
+            cut_off_max: reported.cut_off_max,
+            left_handed: reported.support_left_handed,
+            no_save_to_flash: reported.support_no_save_to_flash,
+            button_count: self.profile.buttons,
+        }
+    }
+
+    /// `probe` again, then return the `device_capabilities` it found, for
+    /// instance after a firmware update. Discards events from `events` the
+    /// same way.
+    pub fn refresh_capabilities(
+        &self,
+        events: &mut HpMouseEvents,
+    ) -> io::Result<DeviceCapabilities> {
+        self.probe(events)?;
+        Ok(self.device_capabilities())
+    }
+
+    /// Send the firmware, DPI, and button queries in turn, recording which
+    /// the device answers within a second. Afterwards, commands for features
+    /// it didn't answer for fail with `ErrorKind::Unsupported` instead of
+    /// waiting for a reply that never comes.
+    ///
+    /// Events read from `events` meanwhile are discarded, so this should be
+    /// done before anything else reads.
+    pub fn probe(&self, events: &mut HpMouseEvents) -> io::Result<Capabilities> {
+        // Don't refuse the queries based on an earlier probe
+        *self.capabilities.lock().unwrap() = None;
+
+        self.query_firmware()?;
+        let firmware = answered(wait_event(events, "firmware query", |event| {
+            matches!(event, Event::Firmware { .. }).then_some(())
+        }))?;
+        self.query_dpi()?;
+        let mouse = answered(wait_event(events, "DPI query", |event| {
+            matches!(event, Event::Mouse { .. }).then_some(())
+        }))?;
+        self.query_button()?;
+        let buttons = answered(wait_buttons(events))?;
+
+        let capabilities = Capabilities {
+            firmware,
+            mouse,
+            buttons,
+        };
+        *self.capabilities.lock().unwrap() = Some(capabilities);
+        Ok(capabilities)
+    }
+
+    // Fails if `probe` found the device doesn't answer the query for a feature
+    fn require(&self, has: fn(&Capabilities) -> bool, feature: &str) -> io::Result<()> {
+        match self.capabilities() {
+            Some(capabilities) if !has(&capabilities) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("device doesn't support {}", feature),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn require_mouse(&self) -> io::Result<()> {
+        self.require(|x| x.mouse, "DPI settings")
+    }
+
+    fn require_buttons(&self) -> io::Result<()> {
+        self.require(|x| x.buttons, "programmable buttons")
+    }
+
+    // Fails for a press type the device reported it doesn't support. Allowed
+    // until the buttons are read.
+    fn check_press_type(&self, button: &Button) -> io::Result<()> {
+        match self.buttons_capabilities() {
+            Some(capabilities) if !capabilities.supports(button.press_type) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "button {} doesn't support press type {:?}",
+                    button.id, button.press_type
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// What the buttons support, from the last `Event::Buttons` read, if any
+    pub fn buttons_capabilities(&self) -> Option<ButtonsCapabilities> {
+        self.reported.lock().unwrap().buttons_capabilities
+    }
+
+    /// Whether the device can apply settings without saving them to flash,
+    /// from the last `Event::Mouse` read, if any
+    pub fn support_no_save_to_flash(&self) -> Option<bool> {
+        self.reported.lock().unwrap().support_no_save_to_flash
+    }
+
+    // Command flag for `no_save_to_flash`. Saves to flash if the device doesn't
+    // report support for not saving.
+    fn no_save_to_flash_flag(&self, no_save_to_flash: bool) -> u8 {
+        if !no_save_to_flash {
+            0
+        } else if self.support_no_save_to_flash() == Some(true) {
+            NO_SAVE_TO_FLASH
+        } else {
+            log::warn!("Device doesn't support not saving to flash; saving");
+            0
+        }
+    }
+
+    /// Fails if the device's firmware is older than `required`. Only warns if
+    /// the version isn't known yet, since `query_firmware` may not have been
+    /// answered.
+    pub fn require_firmware(&self, required: FirmwareVersion) -> io::Result<()> {
+        match self.firmware_version() {
+            Some(version) if version < required => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("requires firmware {}, device has {}", required, version),
+            )),
+            Some(_) => Ok(()),
+            None => {
+                log::warn!(
+                    "Firmware version unknown; assuming it is at least {}",
+                    required
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Wait for a command being written from another thread to finish.
+    /// Commands return once all their reports are written, so after this
+    /// nothing is left half-written, and it's safe to exit.
+    pub fn flush(&self) {
+        drop(self.write_lock.lock().unwrap());
+    }
+
+    /// Write a packet, in as many reports as it takes. The reports go out
+    /// back to back, without other writes in between.
     pub fn write_report_1(&self, kind: u16, packet: &[u8]) -> io::Result<()> {
+        let reports = self.report_1(kind, packet)?;
+        let _guard = self.write_lock.lock().unwrap();
+        for data in &reports {
+            self.write_report(data)?;
+        }
+        Ok(())
+    }
+
+    // Reports of the packet, numbered from 0 in the header the way
+    // `HpMouseEvents` reassembles them. An empty packet is still one report.
+    fn report_1(&self, kind: u16, packet: &[u8]) -> io::Result<Vec<[u8; 21]>> {
         let report = 1;
-        let signature = HP_SIGNATURE + kind;
+        let signature = self.profile.signature + kind;
         assert_eq!(signature & 0xF000, 0);
-
-        let mut data = [0; 21];
-        data[0] = report;
-        data[1] = signature as u8;
-        data[2] = (signature >> 8) as u8;
-        data[3] = packet.len() as u8;
-        // data[4] is sequence, length high
-        for i in 0..packet.len() {
-            data[5 + i] = packet[i];
+        if packet.len() > MAX_PACKET_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "packet is {} bytes, more than the limit of {}",
+                    packet.len(),
+                    MAX_PACKET_LEN
+                ),
+            ));
         }
 
-        let len = self.dev.write(&data)?;
-        eprintln!("HID write {}", len);
+        let count = packet.len().div_ceil(REPORT_PAYLOAD_LEN).max(1);
+        let reports = (0..count)
+            .map(|sequence| {
+                let start = sequence * REPORT_PAYLOAD_LEN;
+                let chunk = &packet[start..packet.len().min(start + REPORT_PAYLOAD_LEN)];
+                let mut data = [0; 21];
+                data[0] = report;
+                data[1] = signature as u8;
+                data[2] = (signature >> 8) as u8;
+                data[3] = packet.len() as u8;
+                // Sequence, and high bits of the length
+                data[4] = (sequence as u8) << 2 | (packet.len() >> 8) as u8;
+                data[5..5 + chunk.len()].copy_from_slice(chunk);
+                data
+            })
+            .collect();
+        Ok(reports)
+    }
 
-        for i in 0..len {
-            eprint!(" {:02x}", data[i]);
+    // Caller holds `write_lock`
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(len = data.len()))
+    )]
+    fn write_report(&self, data: &[u8]) -> io::Result<()> {
+        if self.dry_run.load(Ordering::SeqCst) {
+            log::trace!("HID dry run {}:{}", data.len(), Hex(data));
+            return Ok(());
         }
-        eprintln!();
+
+        let len = self.dev.write(data)?;
+        log::trace!("HID write {}:{}", len, Hex(&data[..len]));
 
         Ok(())
     }
 
-    /// Send query for firmware info
+    /// Send query for firmware info: an empty packet of kind 0, answered
+    /// with kind 1, `Event::Firmware`. On the HP 930 the report is
+    /// `01 f3 0c 00 00`, padded with zeros to 21 bytes like every report.
     pub fn query_firmware(&self) -> io::Result<()> {
+        self.require(|x| x.firmware, "firmware info")?;
         self.write_report_1(0, &[])
     }
 
-    /// Send query for battery info
+    /// Send query for battery info. Also sets the auto-report interval, to
+    /// the last `set_auto_report` or a minute. This is a packet of kind 5
+    /// with `ff ff ff` to keep the battery levels and timeout, then the
+    /// delay, answered with kind 6, `Event::Battery`. On the HP 930 the
+    /// report is `01 f8 0c 04 00 ff ff ff 06` by default.
     pub fn query_battery(&self) -> io::Result<()> {
-        let low_level = 0xFF; // do not set
-        let crit_level = 0xFF; // do not set
-        let power_off_timeout = 0xFF; // do not set
-        let auto_report_delay = 0x06; // 60 seconds
+        let auto_report_delay = self.auto_report_delay.load(Ordering::SeqCst);
+        self.write_battery(auto_report_delay)
+    }
+
+    /// Have the device push `Event::Battery` every `interval`, rounded to a
+    /// multiple of `AUTO_REPORT_UNIT`, or stop pushing them with `None`.
+    /// Later `query_battery` calls keep this. The device replies with
+    /// `Event::Battery`, in which `auto_report_delay` shows the setting.
+    pub fn set_auto_report(&self, interval: Option<Duration>) -> io::Result<()> {
+        let delay = match interval {
+            None => 0,
+            Some(interval) => {
+                let units = (interval.as_secs_f64() / AUTO_REPORT_UNIT.as_secs_f64()).round();
+                if !(1. ..BATTERY_KEEP.into()).contains(&units) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "auto-report interval {:?} not in {:?}-{:?}",
+                            interval,
+                            AUTO_REPORT_UNIT,
+                            AUTO_REPORT_UNIT * u32::from(BATTERY_KEEP - 1)
+                        ),
+                    ));
+                }
+                units as u8
+            }
+        };
+        self.write_battery(delay)?;
+        self.auto_report_delay.store(delay, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// `auto_report_delay` from the last `Event::Battery` read, if any. See
+    /// `auto_report_interval`.
+    pub fn auto_report_delay(&self) -> Option<u8> {
+        self.reported.lock().unwrap().auto_report_delay
+    }
+
+    // Battery command, which is also the battery query. Only sets
+    // `auto_report_delay`.
+    fn write_battery(&self, auto_report_delay: u8) -> io::Result<()> {
+        let low_level = BATTERY_KEEP;
+        let crit_level = BATTERY_KEEP;
+        let power_off_timeout = BATTERY_KEEP;
         self.write_report_1(
             5,
             &[low_level, crit_level, power_off_timeout, auto_report_delay],
         )
     }
 
-    /// Send query for button info
+    /// Send query for button info: a packet of kind 13 with command 0 for
+    /// the current host, answered with kind 14, `Event::Buttons`. On the HP
+    /// 930 the report is `01 00 0d 02 00 00 00`.
     pub fn query_button(&self) -> io::Result<()> {
+        self.require_buttons()?;
         let command = 0; // request status command
         let host_id = 0; // current host
-        self.write_report_1(13, &[command, host_id])
+        self.write_report_1(13, &[command, host_id])?;
+        self.expect_reply(Query::Buttons);
+        Ok(())
+    }
+
+    /// Query firmware info, and wait up to `timeout` for the
+    /// `Event::Firmware` reply. Fails with `ErrorKind::TimedOut` if none
+    /// comes, rather than blocking on a device that doesn't answer. Other
+    /// events read from `events` meanwhile are discarded, as with `probe`.
+    pub fn request_firmware(
+        &self,
+        events: &mut HpMouseEvents,
+        timeout: Duration,
+    ) -> io::Result<Event> {
+        self.query_firmware()?;
+        wait_event_for(events, "firmware query", timeout, |event| {
+            matches!(event, Event::Firmware { .. }).then_some(event)
+        })
+    }
+
+    /// Like `request_firmware`, for `query_battery` and its `Event::Battery`
+    pub fn request_battery(
+        &self,
+        events: &mut HpMouseEvents,
+        timeout: Duration,
+    ) -> io::Result<Event> {
+        self.query_battery()?;
+        wait_event_for(events, "battery query", timeout, |event| {
+            matches!(event, Event::Battery { .. }).then_some(event)
+        })
+    }
+
+    /// Like `request_firmware`, for `query_button` and its `Event::Buttons`
+    pub fn request_buttons(
+        &self,
+        events: &mut HpMouseEvents,
+        timeout: Duration,
+    ) -> io::Result<Event> {
+        self.query_button()?;
+        wait_event_for(events, "button query", timeout, |event| {
+            matches!(event, Event::Buttons { .. }).then_some(event)
+        })
     }
 
-    /// Send query for DPI info
+    /// Like `request_firmware`, for `query_dpi` and its `Event::Mouse`
+    pub fn request_mouse(
+        &self,
+        events: &mut HpMouseEvents,
+        timeout: Duration,
+    ) -> io::Result<Event> {
+        self.query_dpi()?;
+        wait_event_for(events, "DPI query", timeout, |event| {
+            matches!(event, Event::Mouse { .. }).then_some(event)
+        })
+    }
+
+    /// Actions stored for button `id` on the current host, one for each
+    /// press type programmed, to check a single button after writing it.
+    /// No firmware is known to answer a query for one button, so this falls
+    /// back to `query_button` and picks `id` out of the reply. Events read
+    /// from `events` meanwhile are discarded, as with `probe`.
+    pub fn read_button(&self, events: &mut HpMouseEvents, id: u8) -> io::Result<Vec<Button>> {
+        self.query_button()?;
+        let (host_id, buttons) = wait_buttons(events)?;
+        Ok(buttons
+            .into_iter()
+            .filter(|button| button.id == id && button.host_id == host_id)
+            .collect())
+    }
+
+    /// Send query for DPI info: a packet of kind 17 with command 4 for the
+    /// current host, answered with kind 18, `Event::Mouse`. On the HP 930
+    /// the report is `01 04 0d 04 00 00 04 00 00`.
     pub fn query_dpi(&self) -> io::Result<()> {
+        self.require_mouse()?;
         let host_id = 0; // current host
         let command = 4; // request status command, no save to flash not set
         self.write_report_1(
@@ -88,32 +636,307 @@ impl HpMouse {
             &[
                 host_id, command, 0, 0, // payload
             ],
-        )
+        )?;
+        self.expect_reply(Query::Mouse);
+        Ok(())
     }
 
-    pub fn set_dpi(&self, dpi: u16) -> io::Result<()> {
-        let host_id = 0; // current host
+    // Note a query, so `HpMouseEvents::is_unsolicited` can tell its reply
+    // from an event the device pushed. Queries past `VERIFY_TIMEOUT` are
+    // dropped here too, and the oldest past `MAX_QUERIES`, since nothing
+    // matches them if events aren't read.
+    fn expect_reply(&self, query: Query) {
+        let mut reported = self.reported.lock().unwrap();
+        reported
+            .queries
+            .retain(|(_, time)| time.elapsed() < VERIFY_TIMEOUT);
+        if reported.queries.len() >= MAX_QUERIES {
+            reported.queries.remove(0);
+        }
+        reported.queries.push((query, Instant::now()));
+    }
+
+    /// Set the sensitivity for host `host_id`, or 0 for the current host.
+    /// Fails if the device profile has fewer hosts. Once an `Event::Mouse`
+    /// has been read, also fails if `dpi` isn't in its range and steps.
+    pub fn set_dpi(&self, host_id: u8, dpi: u16, no_save_to_flash: bool) -> io::Result<()> {
+        self.require_mouse()?;
+        self.check_host(host_id)?;
+        if self.reported.lock().unwrap().dpi_range.is_some() {
+            self.check_dpi(dpi)?;
+        }
         let command = 0; // set dpi
+        let flags = self.no_save_to_flash_flag(no_save_to_flash);
         let dpi = dpi.to_le_bytes();
-        self.write_report_1(17, &[host_id, command, dpi[0], dpi[1]])
+        self.write_report_1(17, &[host_id, command | flags, dpi[0], dpi[1]])
+    }
+
+    /// Program the DPI stages a button on the mouse cycles through. Fails if
+    /// the device profile has no `dpi_stages`, there are more than it holds,
+    /// or a stage is outside the range from the last `Event::Mouse` read.
+    pub fn set_dpi_stages(&self, stages: &[u16], no_save_to_flash: bool) -> io::Result<()> {
+        self.require_mouse()?;
+        let dpi_stages = self.profile.dpi_stages.as_ref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Unsupported, "device doesn't have DPI stages")
+        })?;
+        self.require_firmware(dpi_stages.min_firmware)?;
+        if stages.is_empty() || stages.len() > usize::from(dpi_stages.max_count) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} DPI stages, but the device holds 1-{}",
+                    stages.len(),
+                    dpi_stages.max_count
+                ),
+            ));
+        }
+        for dpi in stages {
+            self.check_dpi(*dpi)?;
+        }
+
+        let host_id = 0; // current host
+        let flags = self.no_save_to_flash_flag(no_save_to_flash);
+        let mut data = vec![host_id, dpi_stages.command | flags, stages.len() as u8];
+        for dpi in stages {
+            data.extend_from_slice(&dpi.to_le_bytes());
+        }
+        self.write_report_1(17, &data)
+    }
+
+    // Fails unless `host_id` is 0, for the current host, or one of the hosts
+    // in the device profile
+    fn check_host(&self, host_id: u8) -> io::Result<()> {
+        if host_id > self.profile.hosts {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "host {}, but the device pairs with hosts 1-{}",
+                    host_id, self.profile.hosts
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    // Fails unless `dpi` is in the range from the last `Event::Mouse`, and a
+    // whole number of steps above the minimum
+    fn check_dpi(&self, dpi: u16) -> io::Result<()> {
+        let (min, max, step) = self
+            .reported
+            .lock()
+            .unwrap()
+            .dpi_range
+            .ok_or_else(|| io::Error::other("DPI range unknown; DPI info not read yet"))?;
+        if dpi < min || dpi > max || (dpi - min).checked_rem(step).is_some_and(|x| x != 0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("DPI {} not in {}-{} in steps of {}", dpi, min, max, step),
+            ));
+        }
+        Ok(())
     }
 
-    pub fn set_left_handed(&self, left_handed: bool) -> io::Result<()> {
+    pub fn set_left_handed(&self, left_handed: bool, no_save_to_flash: bool) -> io::Result<()> {
+        self.require_mouse()?;
         let host_id = 0; // current host
         let command = 6; // set handedness
+        let flags = self.no_save_to_flash_flag(no_save_to_flash);
         let value = if left_handed { 1 } else { 0 };
-        self.write_report_1(17, &[host_id, command, value, 0])
+        self.write_report_1(17, &[host_id, command | flags, value, 0])
+    }
+
+    /// Set lift-off distance, clamped to the `cut_off_max` from the last
+    /// `Event::Mouse` read. Fails if no `Event::Mouse` has been read, or the
+    /// device doesn't support setting it.
+    pub fn set_cut_off(&self, level: u8, no_save_to_flash: bool) -> io::Result<()> {
+        self.require_mouse()?;
+        let cut_off_max = match self.reported.lock().unwrap().cut_off_max {
+            Some(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "device doesn't support setting lift-off distance",
+                ))
+            }
+            Some(cut_off_max) => cut_off_max,
+            None => {
+                return Err(io::Error::other(
+                    "lift-off distance range unknown; DPI info not read yet",
+                ))
+            }
+        };
+        let host_id = 0; // current host
+        let command = 3; // set cut off
+        let flags = self.no_save_to_flash_flag(no_save_to_flash);
+        let level = level.min(cut_off_max);
+        self.write_report_1(17, &[host_id, command | flags, level, 0])
+    }
+
+    // Fails if the device has no profile slots, or its firmware predates them
+    fn profile_slots(&self) -> io::Result<&'static ProfileSlots> {
+        let slots = self.profile.profile_slots.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "device doesn't have on-device profiles",
+            )
+        })?;
+        self.require_firmware(slots.min_firmware)?;
+        Ok(slots)
+    }
+
+    /// Send query for the active on-device profile, reported as
+    /// `Event::ActiveProfile`
+    pub fn query_active_profile(&self) -> io::Result<()> {
+        let slots = self.profile_slots()?;
+        let command = 0; // request status command
+        self.write_report_1(slots.packet_kind, &[command, 0])
+    }
+
+    /// Switch to on-device profile `index`
+    pub fn set_active_profile(&self, index: u8) -> io::Result<()> {
+        let slots = self.profile_slots()?;
+        if index >= slots.count {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("profile {} out of range 0-{}", index, slots.count - 1),
+            ));
+        }
+        let command = 1; // set active profile
+        self.write_report_1(slots.packet_kind, &[command, index])
+    }
+
+    // Fails if the device has no LED, or its firmware predates it
+    fn led(&self) -> io::Result<&'static Led> {
+        let led = self.profile.led.as_ref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Unsupported, "device doesn't have an LED")
+        })?;
+        self.require_firmware(led.min_firmware)?;
+        Ok(led)
+    }
+
+    /// Send query for the LED color, reported as `Event::Led`
+    pub fn query_led(&self) -> io::Result<()> {
+        let led = self.led()?;
+        let command = 0; // request status command
+        self.write_report_1(led.packet_kind, &[command, 0, 0, 0])
+    }
+
+    /// Set the LED color. Fails if the device profile has no `led`.
+    pub fn set_led(&self, red: u8, green: u8, blue: u8, no_save_to_flash: bool) -> io::Result<()> {
+        let led = self.led()?;
+        let command = 1; // set color
+        let flags = self.no_save_to_flash_flag(no_save_to_flash);
+        self.write_report_1(led.packet_kind, &[command | flags, red, green, blue])
+    }
+
+    fn factory_reset_command(&self) -> io::Result<&'static FactoryReset> {
+        let factory_reset = self.profile.factory_reset.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "device doesn't have a factory reset command",
+            )
+        })?;
+        self.require_firmware(factory_reset.min_firmware)?;
+        Ok(factory_reset)
+    }
+
+    /// Start restoring the device's factory settings, without waiting. The
+    /// device reports `Event::FactoryReset` when done, after which its
+    /// settings should be queried again. Fails if the device profile has no
+    /// `factory_reset`.
+    pub fn send_factory_reset(&self) -> io::Result<()> {
+        let factory_reset = self.factory_reset_command()?;
+        self.write_report_1(factory_reset.packet_kind, &[factory_reset.command])
+    }
+
+    /// Restore the device's factory settings, reading `events` until it
+    /// confirms, then query its state again. Unlike `reset`, this also
+    /// resets DPI, lift-off distance, and power settings.
+    pub fn factory_reset(&self, events: &mut HpMouseEvents) -> io::Result<()> {
+        let kind = self.factory_reset_command()?.packet_kind;
+        self.send_factory_reset()?;
+        wait_event(events, "factory reset", |event| match event {
+            Event::FactoryReset => Some(Ok(())),
+            Event::CommandError { kind: x, code } if x == kind => Some(Err(io::Error::other(
+                format!("factory reset failed with error {}", code),
+            ))),
+            _ => None,
+        })??;
+
+        // Queries a probe found unanswered fail without writing
+        let queries = [
+            Self::query_firmware,
+            Self::query_battery,
+            Self::query_dpi,
+            Self::query_button,
+        ];
+        for query in queries {
+            match query(self) {
+                Err(err) if err.kind() == io::ErrorKind::Unsupported => {}
+                res => res?,
+            }
+        }
+        Ok(())
     }
 
     pub fn set_button(&self, button: Button, no_save_to_flash: bool) -> io::Result<()> {
+        self.set_buttons(&[button], no_save_to_flash)
+    }
+
+    /// Set several buttons, such as a whole profile, as one batch. Every
+    /// button is checked before anything is written, so an invalid one
+    /// writes none of them, and the writes go out back to back without
+    /// other writes in between.
+    ///
+    /// Each button is still its own packet, of one or more reports; as far
+    /// as is known the protocol has no way to pack several into one. Like
+    /// `set_button`, this doesn't wait for replies, so the saving over
+    /// separate calls is in queries: callers checking the result need one
+    /// `query_button` for the batch instead of one per button.
+    pub fn set_buttons(&self, buttons: &[Button], no_save_to_flash: bool) -> io::Result<()> {
+        self.require_buttons()?;
         let command = 1;
-        let no_save_to_flash = if no_save_to_flash { 1 << 7 } else { 0 };
-        let mut data = vec![command | no_save_to_flash];
-        button.encode(&mut data);
-        self.write_report_1(13, &data)
+        let reports = buttons
+            .iter()
+            .map(|button| {
+                check_action_len(button, MAX_ACTION_LEN)?;
+                self.check_press_type(button)?;
+                let mut data = vec![command | self.no_save_to_flash_flag(no_save_to_flash)];
+                button.encode(&mut data);
+                self.report_1(13, &data)
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let _guard = self.write_lock.lock().unwrap();
+        for data in reports.iter().flatten() {
+            self.write_report(data)?;
+        }
+        Ok(())
+    }
+
+    /// Set button, then query buttons from `events` to check the device
+    /// applied it. Writes again once before failing.
+    pub fn set_button_verified(
+        &self,
+        events: &mut HpMouseEvents,
+        button: Button,
+        no_save_to_flash: bool,
+    ) -> io::Result<()> {
+        for _ in 0..2 {
+            self.set_button(button.clone(), no_save_to_flash)?;
+            self.query_button()?;
+            let (host_id, buttons) = wait_buttons(events)?;
+            if button.applied_in(host_id, &buttons) {
+                return Ok(());
+            }
+        }
+        Err(io::Error::other(format!(
+            "device did not apply binding for button {}",
+            button.id
+        )))
     }
 
     pub fn exec_button(&self, button: Button) -> io::Result<()> {
+        self.require_buttons()?;
+        check_action_len(&button, MAX_ACTION_LEN)?;
         let command = 2;
         let host_id = 0;
         let mut data = vec![command, host_id];
@@ -124,32 +947,758 @@ impl HpMouse {
     pub fn reset(&self) -> io::Result<()> {
         // TODO: Other devices may have different number of buttons?
         // TODO: Press types
+        let mut buttons = Vec::new();
         for id in 0..7 {
             for host in [1, 2, 3, 255] {
-                let button = Button::new(id, host, PressType::Normal, &[]);
-                self.set_button(button, false)?;
+                buttons.push(Button::new(id, host, PressType::Normal, &[]));
             }
         }
-        self.set_left_handed(false)?;
+        self.set_buttons(&buttons, false)?;
+        self.set_left_handed(false, false)?;
         Ok(())
     }
 
     // Using multiple readers will result in inconsistent behavior
     pub fn read(&self) -> HpMouseEvents {
-        HpMouseEvents::new(self.dev.clone())
+        HpMouseEvents::new(self.dev.clone(), self.profile, self.reported.clone())
     }
 }
 
-impl AsRawFd for HpMouse {
-    fn as_raw_fd(&self) -> RawFd {
-        self.dev.as_raw_fd()
+fn check_action_len(button: &Button, max: usize) -> io::Result<()> {
+    if button.action.len() > max {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "action for button {} is {} bytes, more than the limit of {}",
+                button.id,
+                button.action.len(),
+                max
+            ),
+        ));
+    }
+    Ok(())
+}
+
+// Read until `Event::Buttons`, giving up after `VERIFY_TIMEOUT`
+fn wait_buttons(events: &mut HpMouseEvents) -> io::Result<(u8, Vec<Button>)> {
+    wait_event(events, "button query", |event| match event {
+        Event::Buttons {
+            host_id, buttons, ..
+        } => Some((host_id, buttons)),
+        _ => None,
+    })
+}
+
+// Read until `f` returns `Some` for an event, giving up after `VERIFY_TIMEOUT`
+fn wait_event<T>(
+    events: &mut HpMouseEvents,
+    query: &str,
+    f: impl FnMut(Event) -> Option<T>,
+) -> io::Result<T> {
+    wait_event_for(events, query, VERIFY_TIMEOUT, f)
+}
+
+// `wait_event`, giving up after `timeout`
+fn wait_event_for<T>(
+    events: &mut HpMouseEvents,
+    query: &str,
+    timeout: Duration,
+    mut f: impl FnMut(Event) -> Option<T>,
+) -> io::Result<T> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let timeout = deadline
+            .checked_duration_since(Instant::now())
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::TimedOut, format!("no response to {}", query))
+            })?;
+        // Only read once readable, since a read on a blocking device that
+        // doesn't answer would never return
+        match events.poll(timeout.as_millis() as i32) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+
+        match events.read() {
+            Ok(ReadRes::Packet(event)) => {
+                if let Some(value) = f(event) {
+                    return Ok(value);
+                }
+            }
+            Ok(ReadRes::EOF) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(_) => {}
+            // Reading resynchronizes, so the reply may still come
+            Err(ReadError::Protocol(err)) => {
+                log::debug!("Ignoring {} while waiting for a reply to {}", err, query)
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+// `false` if `wait_event` timed out
+fn answered<T>(res: io::Result<T>) -> io::Result<bool> {
+    match res {
+        Ok(_) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::TimedOut => Ok(false),
+        Err(err) => Err(err),
     }
 }
 
 impl FromRawFd for HpMouse {
     unsafe fn from_raw_fd(fd: RawFd) -> Self {
-        HpMouse {
-            dev: Arc::new(Hid::from_raw_fd(fd)),
+        Self::from_device(Hid::from_raw_fd(fd))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::button::encode_action;
+
+    // Mouse packet with the DPI `range` of minimum, maximum, and step, at the
+    // minimum, a cut off of up to 3, and `flags` of left handed support, left
+    // handed, and no save to flash support
+    pub(crate) fn mouse_report(range: (u16, u16, u16), flags: u8) -> Vec<u8> {
+        let (min, max, step) = range;
+        let mut packet = vec![0];
+        for value in [max, min, min, step] {
+            packet.extend_from_slice(&value.to_le_bytes());
+        }
+        packet.extend_from_slice(&[0, 0, 0, 3, 0, flags]);
+        MockHid::report_1(18, &packet)
+    }
+
+    // Buttons packet for host 1, reporting button 0 with `action`
+    fn buttons_report(action: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0, 7, 1, 1, 0];
+        packet.extend_from_slice(&[0, 1, 0, action.len() as u8]);
+        packet.extend_from_slice(action);
+        MockHid::report_1(14, &packet)
+    }
+
+    #[test]
+    fn test_set_dpi() {
+        let dev = MockHid::default();
+        let mouse = HpMouse::from_device(dev.clone());
+        mouse.set_dpi(0, 1200, false).unwrap();
+        let mut expected = [0; 21];
+        expected[..9].copy_from_slice(&[1, 0x04, 0x0D, 4, 0, 0, 0, 0xB0, 0x04]);
+        dev.assert_writes(&[&expected]);
+
+        // Another host, but only those the profile has
+        mouse.set_dpi(2, 1200, false).unwrap();
+        expected[5] = 2;
+        dev.assert_writes(&[&expected]);
+        let err = mouse.set_dpi(4, 1200, false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(dev.writes().is_empty());
+    }
+
+    #[test]
+    fn test_set_dpi_checked() {
+        let dev = MockHid::with_reports(vec![mouse_report((400, 3200, 50), 0)]);
+        let mouse = HpMouse::from_device(dev.clone());
+        mouse.read().nth(1);
+        for dpi in [350, 1225, 3250] {
+            let err = mouse.set_dpi(0, dpi, false).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+        assert!(dev.writes().is_empty());
+        mouse.set_dpi(0, 1250, false).unwrap();
+
+        // In the byte order the device reports it in
+        let written = dev.writes().pop().unwrap();
+        assert_eq!(written[7..9], 1250u16.to_le_bytes());
+    }
+
+    #[test]
+    fn test_no_save_to_flash() {
+        for support in [false, true] {
+            let mouse_info = mouse_report((400, 3200, 50), (support as u8) << 2);
+            let dev = MockHid::with_reports(vec![mouse_info]);
+            let mouse = HpMouse::from_device(dev.clone());
+            // Saves to flash until support is known
+            mouse.set_left_handed(true, true).unwrap();
+            mouse.read().nth(1);
+            assert_eq!(mouse.support_no_save_to_flash(), Some(support));
+            mouse.set_left_handed(true, true).unwrap();
+            let writes = dev.writes();
+            assert_eq!(writes[0][6], 6);
+            assert_eq!(writes[1][6], if support { 6 | 1 << 7 } else { 6 });
+        }
+    }
+
+    #[test]
+    fn test_set_cut_off() {
+        let dev = MockHid::with_reports(vec![mouse_report((400, 3200, 50), 0)]);
+        let mouse = HpMouse::from_device(dev.clone());
+        // Range unknown until the mouse packet is read
+        assert!(mouse.set_cut_off(1, false).is_err());
+        mouse.read().nth(1);
+        mouse.set_cut_off(1, false).unwrap();
+        mouse.set_cut_off(10, false).unwrap();
+        let writes = dev.writes();
+        assert_eq!(writes.len(), 2);
+        assert_eq!(&writes[0][5..8], &[0, 3, 1]);
+        assert_eq!(&writes[1][5..8], &[0, 3, 3]);
+    }
+
+    #[test]
+    fn test_read_battery() {
+        let dev = MockHid::with_reports(vec![MockHid::report_1(6, &[10, 5, 0, 6, 80])]);
+        let mut events = HpMouse::from_device(dev).read();
+        // Before any report
+        assert!(matches!(events.next(), Some(Ok(Event::Connected))));
+        assert!(matches!(
+            events.next(),
+            Some(Ok(Event::Battery { level: 80, .. }))
+        ));
+        assert!(events.next().is_none());
+    }
+
+    #[test]
+    fn test_read_concatenated() {
+        let mut battery = [0; 16];
+        battery[..5].copy_from_slice(&[10, 5, 0, 6, 80]);
+        let mut mouse_info = [0; 16];
+        mouse_info[12] = 3; // cut_off_max
+        let mut report = MockHid::report_1(6, &battery);
+        report.extend(MockHid::report_1(18, &mouse_info));
+        let dev = MockHid::with_reports(vec![report]);
+        let mut events = HpMouse::from_device(dev).read();
+        events.next();
+        assert!(matches!(
+            events.next(),
+            Some(Ok(Event::Battery { level: 80, .. }))
+        ));
+        assert!(events.poll(0).unwrap());
+        assert!(matches!(
+            events.next(),
+            Some(Ok(Event::Mouse { cut_off_max: 3, .. }))
+        ));
+        assert!(events.next().is_none());
+        assert_eq!(events.diagnostics().reports, 2);
+    }
+
+    #[test]
+    fn test_read_long_report() {
+        // A firmware packet in one 42 byte report, the length of two
+        let mut packet = vec![0xB8, 0x0B, 0, 0, 16];
+        packet.extend_from_slice(b"HP 930 Creator M");
+        packet.push(15);
+        packet.extend_from_slice(b"SERIAL123456789");
+        let report = MockHid::report_1(1, &packet);
+        assert_eq!(report.len(), 42);
+        let dev = MockHid::with_reports(vec![report]);
+        let mut events = HpMouse::from_device(dev).read();
+        events.next();
+        assert!(matches!(
+            events.next(),
+            Some(Ok(Event::Firmware { device, .. })) if device == "HP 930 Creator M"
+        ));
+        assert!(events.next().is_none());
+    }
+
+    #[test]
+    fn test_set_auto_report() {
+        let dev = MockHid::with_reports(vec![MockHid::report_1(6, &[10, 5, 0, 3, 80])]);
+        let mouse = HpMouse::from_device(dev.clone());
+        assert_eq!(mouse.auto_report_delay(), None);
+        mouse.query_battery().unwrap();
+        mouse
+            .set_auto_report(Some(Duration::from_secs(30)))
+            .unwrap();
+        mouse.query_battery().unwrap();
+        mouse.set_auto_report(None).unwrap();
+        let err = mouse
+            .set_auto_report(Some(Duration::from_secs(1)))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let delays: Vec<_> = dev.writes().iter().map(|x| x[8]).collect();
+        assert_eq!(delays, &[6, 3, 3, 0]);
+
+        mouse.read().nth(1);
+        assert_eq!(mouse.auto_report_delay(), Some(3));
+        assert_eq!(auto_report_interval(3), Some(Duration::from_secs(30)));
+        assert_eq!(auto_report_interval(0), None);
+    }
+
+    #[test]
+    fn test_next_timeout() {
+        let dev = MockHid::with_reports(vec![MockHid::report_1(6, &[10, 5, 0, 6, 80])])
+            .silent_when_empty();
+        let mut events = HpMouse::from_device(dev.clone()).read();
+        let timeout = Duration::from_millis(10);
+        assert!(matches!(
+            events.next_timeout(timeout),
+            Some(Ok(Event::Connected))
+        ));
+        assert!(matches!(
+            events.next_timeout(timeout),
+            Some(Ok(Event::Battery { level: 80, .. }))
+        ));
+        assert!(events.next_timeout(timeout).is_none());
+
+        // Still reading after the timeout
+        dev.push_report(MockHid::report_1(6, &[10, 5, 0, 6, 70]));
+        assert!(matches!(
+            events.next_timeout(timeout),
+            Some(Ok(Event::Battery { level: 70, .. }))
+        ));
+    }
+
+    #[test]
+    fn test_next_timeout_deadline() {
+        // Reports that are no event keep arriving, more often than the timeout
+        let dev = MockHid::default().silent_when_empty();
+        let mut events = HpMouse::from_device(dev.clone()).read();
+        events.next();
+        let sender = std::thread::spawn(move || {
+            for _ in 0..40 {
+                dev.push_report(vec![2, 0, 0, 0]);
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        });
+        let start = std::time::Instant::now();
+        assert!(events.next_timeout(Duration::from_millis(30)).is_none());
+        assert!(start.elapsed() < Duration::from_millis(150));
+        sender.join().unwrap();
+    }
+
+    #[test]
+    fn test_set_timeout() {
+        let dev = MockHid::with_reports(vec![MockHid::report_1(6, &[10, 5, 0, 6, 80])])
+            .silent_when_empty();
+        let mut events = HpMouse::from_device(dev.clone()).read();
+        events.set_timeout(Some(Duration::from_millis(10)));
+        assert!(matches!(events.next(), Some(Ok(Event::Connected))));
+        assert!(matches!(
+            events.next(),
+            Some(Ok(Event::Battery { level: 80, .. }))
+        ));
+        match events.next() {
+            Some(Err(ReadError::Busy(err))) => assert_eq!(err.kind(), io::ErrorKind::TimedOut),
+            res => panic!("Expected timeout, got {:?}", res),
+        }
+
+        // Still reading after the timeout
+        dev.push_report(MockHid::report_1(6, &[10, 5, 0, 6, 70]));
+        assert!(matches!(
+            events.next(),
+            Some(Ok(Event::Battery { level: 70, .. }))
+        ));
+    }
+
+    #[test]
+    fn test_set_button_verified() {
+        let button = Button::new(0, 0, PressType::Normal, &[Op::Kill]);
+        let action = encode_action(&[Op::Kill]);
+
+        // Mismatch, then applied on retry
+        let dev = MockHid::with_reports(vec![buttons_report(&[1]), buttons_report(&action)]);
+        let mouse = HpMouse::from_device(dev.clone());
+        let mut events = mouse.read();
+        assert!(mouse
+            .set_button_verified(&mut events, button.clone(), false)
+            .is_ok());
+        assert_eq!(dev.writes().len(), 4);
+
+        // Mismatch on both attempts
+        let dev = MockHid::with_reports(vec![buttons_report(&[1]), buttons_report(&[1])]);
+        let mouse = HpMouse::from_device(dev);
+        let mut events = mouse.read();
+        assert!(mouse
+            .set_button_verified(&mut events, button, false)
+            .is_err());
+
+        // Default action isn't reported
+        let button = Button::new(0, 0, PressType::Normal, &[]);
+        assert!(button.applied_in(1, &[]));
+    }
+
+    #[test]
+    fn test_probe() {
+        let firmware = MockHid::report_1(1, &[0xB8, 0x0B, 0, 0, 0, 0]);
+        let mouse_info = mouse_report((400, 3200, 100), 0);
+        let dev = MockHid::with_reports(vec![firmware, mouse_info]).silent_when_empty();
+        let mouse = HpMouse::from_device(dev.clone());
+        let mut events = mouse.read();
+        let capabilities = mouse.probe(&mut events).unwrap();
+        assert_eq!(
+            capabilities,
+            Capabilities {
+                firmware: true,
+                mouse: true,
+                buttons: false,
+            }
+        );
+        assert_eq!(mouse.capabilities(), Some(capabilities));
+        assert_eq!(dev.writes().len(), 3);
+
+        // Fails fast, without writing
+        let button = Button::new(0, 0, PressType::Normal, &[]);
+        let err = mouse.set_button(button, false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(mouse.query_button().is_err());
+        assert!(mouse.query_dpi().is_ok());
+        assert_eq!(dev.writes().len(), 4);
+    }
+
+    #[test]
+    fn test_device_session() {
+        let firmware = MockHid::report_1(
+            1,
+            &[0x4F, 0x04, 0, 0, 3, b'9', b'3', b'0', 3, b'A', b'B', b'C'],
+        );
+        let battery = MockHid::report_1(6, &[10, 5, 0, 6, 80]);
+        let dev = MockHid::with_reports(vec![battery.clone(), firmware, battery]);
+        let mut events = HpMouse::from_device(dev).read();
+        let mut session = DeviceSession::new();
+
+        let tags: Vec<_> = (&mut events)
+            .take(4)
+            .map(|event| session.tag(&event.unwrap()).map(String::from))
+            .collect();
+        assert_eq!(tags, [None, None, Some("ABC".into()), Some("ABC".into())]);
+        assert_eq!(session.device(), Some("930"));
+        assert!(session.is_device("ABC"));
+        assert!(!session.is_device("DEF"));
+
+        session.tag(&Event::Connected);
+        assert!(!session.is_device("ABC"));
+    }
+
+    #[test]
+    fn test_unsolicited() {
+        let mouse_info = mouse_report((400, 3200, 50), 0);
+        let buttons = buttons_report(&[1, 0]);
+        let dev = MockHid::with_reports(vec![mouse_info.clone(), mouse_info, buttons]);
+        let mouse = HpMouse::from_device(dev);
+        let mut events = mouse.read();
+        mouse.query_dpi().unwrap();
+
+        let mut unsolicited = Vec::new();
+        for _ in 0..4 {
+            let event = events.next().unwrap().unwrap();
+            unsolicited.push(events.is_unsolicited(&event));
         }
+        // The first mouse event answers the query
+        assert_eq!(unsolicited, [false, false, true, true]);
+    }
+
+    #[test]
+    fn test_unanswered_queries() {
+        let mouse = HpMouse::from_device(MockHid::default());
+        for _ in 0..100 {
+            mouse.query_dpi().unwrap();
+        }
+        assert_eq!(mouse.reported.lock().unwrap().queries.len(), MAX_QUERIES);
+    }
+
+    // Output shared with the test, to read back what was recorded
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let firmware = MockHid::report_1(
+            1,
+            &[0x4F, 0x04, 0, 0, 3, b'9', b'3', b'0', 3, b'A', b'B', b'C'],
+        );
+        let battery = MockHid::report_1(6, &[10, 5, 0, 6, 80]);
+        // Unknown signature
+        let invalid = vec![1, 0, 0, 1, 0, 0];
+        let reports = vec![firmware, battery, invalid];
+
+        for redact in [false, true] {
+            let out = SharedBuf::default();
+            let dev = MockHid::with_reports(reports.clone());
+            let mut events = HpMouse::from_device(dev).read();
+            events.record(Some(Recording::new(out.clone()).redact_serial(redact)));
+            assert_eq!(events.count(), 4);
+
+            let text = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+            let lines: Vec<_> = text
+                .lines()
+                .map(|line| line.split_once(' ').unwrap().1)
+                .collect();
+            assert_eq!(lines.len(), 6, "{}", text);
+            assert!(lines[2].starts_with("report 01 "));
+            assert!(lines[3].starts_with("event Battery {"));
+            assert_eq!(lines[4], "report 01 00 00 01 00 00");
+            assert!(lines[5].starts_with("error "));
+            if redact {
+                assert!(!text.contains("ABC") && !text.contains("41 42 43"));
+                assert!(lines[0].ends_with(" (redacted)"));
+                assert!(lines[1].contains("serial: (redacted)"));
+            } else {
+                assert!(lines[0].contains(" 03 41 42 43"));
+                assert!(lines[1].contains("serial: \"ABC\""));
+            }
+        }
+    }
+
+    #[test]
+    fn test_refresh_capabilities() {
+        let firmware = MockHid::report_1(1, &[0xB8, 0x0B, 0, 0, 0, 0]);
+        let mouse_info = mouse_report((800, 3200, 50), 0b101);
+        let dev = MockHid::with_reports(vec![firmware, mouse_info]).silent_when_empty();
+        let mouse = HpMouse::from_device(dev);
+        assert_eq!(
+            mouse.device_capabilities(),
+            DeviceCapabilities {
+                button_count: HP_930.buttons,
+                ..DeviceCapabilities::default()
+            }
+        );
+
+        let mut events = mouse.read();
+        let capabilities = mouse.refresh_capabilities(&mut events).unwrap();
+        assert_eq!(capabilities, mouse.device_capabilities());
+        assert!(capabilities.firmware_version.is_some());
+        assert_eq!(capabilities.dpi_range, Some((800, 3200, 50)));
+        assert_eq!(capabilities.left_handed, Some(true));
+        assert_eq!(capabilities.no_save_to_flash, Some(true));
+        assert!(capabilities.cut_off());
+        assert!(capabilities.answers(|x| x.mouse));
+        assert!(!capabilities.answers(|x| x.buttons));
+        assert_eq!(capabilities.buttons, None);
+        assert!(!capabilities.long_press());
+    }
+
+    #[test]
+    fn test_action_len() {
+        let dev = MockHid::default();
+        let mouse = HpMouse::from_device(dev.clone());
+        let pause = Op::pause(1000);
+        let len = |ops: &[Op]| encode_action(ops).len();
+        let mut ops = vec![pause.clone()];
+        while len(&ops) <= MAX_ACTION_LEN {
+            ops.push(pause.clone());
+        }
+        let fits = &ops[..ops.len() - 1];
+        assert!(len(fits) <= MAX_ACTION_LEN);
+
+        let button = Button::new(0, 0, PressType::Normal, fits);
+        assert!(mouse.set_button(button, false).is_ok());
+        let written = dev.writes().len();
+        let button = Button::new(0, 0, PressType::Normal, &ops);
+        let err = mouse.set_button(button.clone(), false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains(&MAX_ACTION_LEN.to_string()));
+        assert!(mouse.exec_button(button).is_err());
+        assert_eq!(dev.writes().len(), written);
+    }
+
+    #[test]
+    fn test_set_button_fragmented() {
+        let dev = MockHid::default();
+        let mouse = HpMouse::from_device(dev.clone());
+        let ops = vec![Op::pause(1000); 5];
+        let button = Button::new(2, 0, PressType::Normal, &ops);
+        // More than the 16 bytes one report holds
+        let len = 5 + button.action.len() as u8;
+        assert!((17..=32).contains(&len));
+        mouse.set_button(button.clone(), false).unwrap();
+        let writes = dev.writes();
+        assert_eq!(writes.len(), 2);
+        assert_eq!(writes[0][3..5], [len, 0]);
+        assert_eq!(writes[1][3..5], [len, 1 << 2]);
+
+        // Reassembled as read, to the packet that was split
+        let mut events = HpMouse::from_device(MockHid::with_reports(writes)).read();
+        match events.nth(1) {
+            Some(Ok(Event::Unknown { kind: 13, data })) => {
+                let mut expected = vec![1];
+                button.encode(&mut expected);
+                assert_eq!(data, expected);
+            }
+            res => panic!("Expected set button packet, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_set_buttons() {
+        let dev = MockHid::default();
+        let mouse = HpMouse::from_device(dev.clone());
+        let buttons = [
+            Button::new(0, 0, PressType::Normal, &[Op::Kill]),
+            Button::new(1, 0, PressType::Normal, &[]),
+        ];
+        mouse.set_buttons(&buttons, false).unwrap();
+        for button in &buttons {
+            mouse.set_button(button.clone(), false).unwrap();
+        }
+        let writes = dev.writes();
+        assert_eq!(writes.len(), 4);
+        assert_eq!(writes[..2], writes[2..]);
+
+        // Nothing written if one is too long
+        let dev = MockHid::default();
+        let mouse = HpMouse::from_device(dev.clone());
+        let ops = vec![Op::pause(1000); MAX_ACTION_LEN];
+        let too_long = Button::new(2, 0, PressType::Normal, &ops);
+        let buttons = [buttons[0].clone(), too_long];
+        assert!(mouse.set_buttons(&buttons, false).is_err());
+        assert!(dev.writes().is_empty());
+
+        // Press type the buttons report they don't support
+        let dev = MockHid::with_reports(vec![buttons_report(&[])]);
+        let mouse = HpMouse::from_device(dev.clone());
+        let down = Button::new(0, 0, PressType::Down, &[]);
+        mouse.set_button(down.clone(), false).unwrap();
+        mouse.read().nth(1);
+        let err = mouse.set_button(down, false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert_eq!(dev.writes().len(), 1);
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        let battery = MockHid::report_1(6, &[10, 5, 0, 6, 80]);
+        let dev = MockHid::with_reports(vec![battery]).blocking_when_empty();
+        let mouse = HpMouse::from_device(dev);
+        let mut events = mouse.read();
+        let timeout = Duration::from_millis(20);
+        let event = mouse.request_battery(&mut events, timeout).unwrap();
+        assert!(matches!(event, Event::Battery { level: 80, .. }));
+
+        // The device never answers, and isn't read once the poll times out
+        let err = mouse.request_firmware(&mut events, timeout).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_request_no_answer() {
+        let dev = MockHid::default().blocking_when_empty();
+        let mouse = HpMouse::from_device(dev.clone());
+        let mut events = mouse.read();
+        let timeout = Duration::from_millis(20);
+        let requests = [
+            HpMouse::request_firmware,
+            HpMouse::request_battery,
+            HpMouse::request_buttons,
+            HpMouse::request_mouse,
+        ];
+        for request in requests {
+            let err = request(&mouse, &mut events, timeout).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        }
+        assert_eq!(dev.writes().len(), 4);
+    }
+
+    #[test]
+    fn test_request_after_protocol_error() {
+        // A report too short for a header, then the reply
+        let battery = MockHid::report_1(6, &[10, 5, 0, 6, 80]);
+        let dev = MockHid::with_reports(vec![vec![1, 0xF9], battery]).blocking_when_empty();
+        let mouse = HpMouse::from_device(dev);
+        let mut events = mouse.read();
+        let timeout = Duration::from_millis(20);
+        let event = mouse.request_battery(&mut events, timeout).unwrap();
+        assert!(matches!(event, Event::Battery { level: 80, .. }));
+    }
+
+    #[test]
+    fn test_query_reports() {
+        let dev = MockHid::default();
+        let mouse = HpMouse::from_device(dev.clone());
+        mouse.query_firmware().unwrap();
+        mouse.query_battery().unwrap();
+        mouse.query_button().unwrap();
+        mouse.query_dpi().unwrap();
+        // As documented on each query
+        let expected: [&[u8]; 4] = [
+            &[1, 0xF3, 0x0C, 0, 0],
+            &[1, 0xF8, 0x0C, 4, 0, 0xFF, 0xFF, 0xFF, 6],
+            &[1, 0x00, 0x0D, 2, 0, 0, 0],
+            &[1, 0x04, 0x0D, 4, 0, 0, 4, 0, 0],
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|bytes| {
+                let mut report = [0; 21];
+                report[..bytes.len()].copy_from_slice(bytes);
+                report
+            })
+            .collect();
+        let expected: Vec<&[u8]> = expected.iter().map(|x| &x[..]).collect();
+        dev.assert_writes(&expected);
+    }
+
+    #[test]
+    fn test_read_button() {
+        let report = buttons_report(&encode_action(&[Op::Kill]));
+        let dev = MockHid::with_reports(vec![report.clone(), report]).silent_when_empty();
+        let mouse = HpMouse::from_device(dev.clone());
+        let mut events = mouse.read();
+        let buttons = mouse.read_button(&mut events, 0).unwrap();
+        assert_eq!(buttons.len(), 1);
+        assert_eq!(buttons[0].decode_action().unwrap(), &[Op::Kill]);
+        assert!(mouse.read_button(&mut events, 3).unwrap().is_empty());
+        assert_eq!(dev.writes().len(), 2);
+    }
+
+    #[test]
+    fn test_no_raw_fd() {
+        let mouse = HpMouse::from_device(MockHid::default());
+        assert_eq!(mouse.raw_fd(), None);
+        assert_eq!(mouse.read().raw_fd(), None);
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let dev = MockHid::default();
+        let mouse = HpMouse::from_device(dev.clone());
+        mouse.set_dry_run(true);
+        mouse.set_dpi(0, 1200, false).unwrap();
+        mouse.reset().unwrap();
+        assert!(dev.writes().is_empty());
+        mouse.set_dry_run(false);
+        mouse.set_dpi(0, 1200, false).unwrap();
+        assert_eq!(dev.writes().len(), 1);
+    }
+
+    #[test]
+    fn test_shared_between_threads() {
+        let reports = (0..100)
+            .map(|_| MockHid::report_1(6, &[10, 5, 0, 6, 80]))
+            .collect();
+        let dev = MockHid::with_reports(reports);
+        let mouse = Arc::new(HpMouse::from_device(dev.clone()));
+
+        let mut events = mouse.read();
+        let reader = std::thread::spawn(move || events.by_ref().skip(1).count());
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let mouse = mouse.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        mouse.set_dpi(0, 1200, false).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        assert_eq!(reader.join().unwrap(), 100);
+        let writes = dev.writes();
+        assert_eq!(writes.len(), 100);
+        assert!(writes.iter().all(|x| x == &writes[0]));
     }
 }
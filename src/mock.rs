@@ -0,0 +1,121 @@
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{HidDevice, HP_930};
+
+#[derive(Debug, Default)]
+struct Inner {
+    reports: VecDeque<Vec<u8>>,
+    writes: Vec<Vec<u8>>,
+    silent_when_empty: bool,
+    blocking_when_empty: bool,
+}
+
+/// Device that returns scripted reports, and records writes, for testing
+/// without hardware. Reads return EOF once the scripted reports run out,
+/// unless `silent_when_empty` is set.
+///
+/// Clones share state, so a clone can be kept to inspect writes after passing
+/// one to `HpMouse::from_device`.
+#[derive(Clone, Debug, Default)]
+pub struct MockHid(Arc<Mutex<Inner>>);
+
+impl MockHid {
+    pub fn with_reports(reports: Vec<Vec<u8>>) -> Self {
+        Self(Arc::new(Mutex::new(Inner {
+            reports: reports.into(),
+            writes: Vec::new(),
+            silent_when_empty: false,
+            blocking_when_empty: false,
+        })))
+    }
+
+    /// Once the scripted reports run out, act like a device that doesn't
+    /// answer: polls time out and reads would block.
+    pub fn silent_when_empty(self) -> Self {
+        self.0.lock().unwrap().silent_when_empty = true;
+        self
+    }
+
+    /// Like `silent_when_empty`, but like a blocking hidraw device, which
+    /// would hang on a read with nothing to return. Such a read panics
+    /// instead, so a test catches code that reads without polling first.
+    pub fn blocking_when_empty(self) -> Self {
+        let mut inner = self.0.lock().unwrap();
+        inner.silent_when_empty = true;
+        inner.blocking_when_empty = true;
+        drop(inner);
+        self
+    }
+
+    /// Raw report 1, as read from an HP 930, containing a single packet
+    pub fn report_1(kind: u16, packet: &[u8]) -> Vec<u8> {
+        let signature = (HP_930.signature + kind).to_le_bytes();
+        let length = (packet.len() as u16).to_le_bytes();
+        let mut data = vec![1, signature[0], signature[1], length[0], length[1]];
+        data.extend_from_slice(packet);
+        data
+    }
+
+    pub fn push_report(&self, report: Vec<u8>) {
+        self.0.lock().unwrap().reports.push_back(report);
+    }
+
+    /// Reports written so far
+    pub fn writes(&self) -> Vec<Vec<u8>> {
+        self.0.lock().unwrap().writes.clone()
+    }
+
+    /// Panics unless exactly `expected` has been written since the last call,
+    /// then clears the recorded writes
+    pub fn assert_writes(&self, expected: &[&[u8]]) {
+        let writes = std::mem::take(&mut self.0.lock().unwrap().writes);
+        assert_eq!(writes, expected, "unexpected HID writes");
+    }
+}
+
+impl HidDevice for MockHid {
+    fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut inner = self.0.lock().unwrap();
+        match inner.reports.pop_front() {
+            Some(report) => {
+                let len = report.len().min(buf.len());
+                buf[..len].copy_from_slice(&report[..len]);
+                Ok(len)
+            }
+            None if inner.blocking_when_empty => panic!("read would block forever"),
+            None if inner.silent_when_empty => Err(io::ErrorKind::WouldBlock.into()),
+            None => Ok(0),
+        }
+    }
+
+    fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().writes.push(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    // Readable unless silent, since there is either a report or EOF
+    // Reports pushed from another thread meanwhile end the wait
+    fn poll(&self, timeout: i32) -> io::Result<bool> {
+        // Never indefinitely, so a test can't hang
+        let timeout = Duration::from_millis(u64::try_from(timeout).unwrap_or(0));
+        let deadline = Instant::now() + timeout;
+        loop {
+            let inner = self.0.lock().unwrap();
+            if !inner.silent_when_empty || !inner.reports.is_empty() {
+                return Ok(true);
+            }
+            drop(inner);
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Ok(false);
+            }
+            thread::sleep(left.min(Duration::from_millis(1)));
+        }
+    }
+}
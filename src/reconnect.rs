@@ -0,0 +1,121 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{enumerate, Event, HpMouse, HpMouseEvents, ReadError};
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+const FIRMWARE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Event iterator that reopens the device, matched by serial, when it is
+/// disconnected, instead of ending. Yields `Event::Reconnected` followed by
+/// the device's `Event::Firmware` after reopening, in place of another
+/// `Event::Connected`.
+///
+/// `ReadError::Gone` is treated as a disconnect. After `ReadError::Busy`, it
+/// waits before reading again, backing off like reconnecting.
+pub struct ReconnectingEvents {
+    serial: String,
+    mouse: HpMouse,
+    events: Option<HpMouseEvents>,
+    pending: Option<Event>,
+    backoff: Duration,
+}
+
+impl ReconnectingEvents {
+    pub fn new(mouse: HpMouse, serial: String) -> Self {
+        let events = mouse.read();
+        Self {
+            serial,
+            mouse,
+            events: Some(events),
+            pending: None,
+            backoff: INITIAL_BACKOFF,
+        }
+    }
+
+    /// Device to send commands to. Replaced when reconnected.
+    pub fn mouse(&self) -> &HpMouse {
+        &self.mouse
+    }
+}
+
+// Open the connected device with `serial`, returning it with its events and
+// the `Event::Firmware` already read from them
+pub(crate) fn open_serial(serial: &str) -> Option<(HpMouse, HpMouseEvents, Event)> {
+    for device_info in enumerate().ok()? {
+        let mouse = match device_info.open() {
+            Ok(mouse) => mouse,
+            Err(_) => continue,
+        };
+        let mut events = mouse.read();
+        if mouse.query_firmware().is_err() {
+            continue;
+        }
+        if let Some(event) = wait_firmware(&mut events) {
+            if matches!(&event, Event::Firmware { serial: x, .. } if x == serial) {
+                return Some((mouse, events, event));
+            }
+        }
+    }
+    None
+}
+
+// Read until `Event::Firmware`, giving up after `FIRMWARE_TIMEOUT`
+fn wait_firmware(events: &mut HpMouseEvents) -> Option<Event> {
+    let deadline = Instant::now() + FIRMWARE_TIMEOUT;
+    loop {
+        let timeout = deadline.checked_duration_since(Instant::now())?;
+        match events.next_timeout(timeout)? {
+            Ok(event @ Event::Firmware { .. }) => return Some(event),
+            Ok(_) => {}
+            Err(_) => return None,
+        }
+    }
+}
+
+impl Iterator for ReconnectingEvents {
+    type Item = Result<Event, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.pending.take() {
+            return Some(Ok(event));
+        }
+
+        loop {
+            if let Some(events) = self.events.as_mut() {
+                match events.next() {
+                    Some(Err(err)) if err.is_gone() => {
+                        log::info!("Device gone, reconnecting: {}", err);
+                        self.events = None;
+                    }
+                    Some(Err(err)) if err.is_busy() => {
+                        thread::sleep(self.backoff);
+                        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                        return Some(Err(err));
+                    }
+                    Some(res) => {
+                        if res.is_ok() {
+                            self.backoff = INITIAL_BACKOFF;
+                        }
+                        return Some(res);
+                    }
+                    None => self.events = None,
+                }
+            }
+
+            thread::sleep(self.backoff);
+            if let Some((mouse, mut events, firmware)) = open_serial(&self.serial) {
+                events.skip_connected();
+                self.mouse = mouse;
+                self.events = Some(events);
+                self.pending = Some(firmware);
+                self.backoff = INITIAL_BACKOFF;
+                return Some(Ok(Event::Reconnected));
+            }
+            self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+}
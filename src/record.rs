@@ -0,0 +1,101 @@
+//! Recording the raw reports a device sends, and what they parse to, to
+//! attach to a bug report. Each line starts with the seconds since the
+//! recording started:
+//!
+//! ```text
+//! 0.000000 report 01 4f 04 05 00 64 14 00 00 04
+//! 0.000012 event Battery { low_level: 100, crit_level: 20, ... }
+//! 0.131877 report 01 00 00 00 00 ...
+//! 0.131890 error Invalid header signature 0000
+//! ```
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crate::{Event, ReadError};
+
+struct Inner {
+    out: Box<dyn Write + Send>,
+    start: Instant,
+    redact_serial: bool,
+}
+
+/// Where `HpMouseEvents::record` writes. Clones write to the same output, so
+/// one recording can cover several devices.
+#[derive(Clone)]
+pub struct Recording(Arc<Mutex<Inner>>);
+
+impl Recording {
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(Inner {
+            out: Box::new(out),
+            start: Instant::now(),
+            redact_serial: false,
+        })))
+    }
+
+    /// Record to a new file at `path`, replacing any existing one
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Leave the serial number out of `Event::Firmware`, and the payload out
+    /// of the reports it is parsed from
+    pub fn redact_serial(self, redact: bool) -> Self {
+        self.0.lock().unwrap().redact_serial = redact;
+        self
+    }
+
+    pub(crate) fn redacts_serial(&self) -> bool {
+        self.0.lock().unwrap().redact_serial
+    }
+
+    fn line(&self, kind: &str, text: &str) {
+        let mut inner = self.0.lock().unwrap();
+        let time = inner.start.elapsed().as_secs_f64();
+        // A recording that can't be written shouldn't stop reading events
+        let _ = writeln!(inner.out, "{:.6} {} {}", time, kind, text);
+        let _ = inner.out.flush();
+    }
+
+    /// `redact` keeps only the report ID and header
+    pub(crate) fn report(&self, report: &[u8], redact: bool) {
+        let shown = if redact {
+            &report[..report.len().min(5)]
+        } else {
+            report
+        };
+        let mut text = shown
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if shown.len() < report.len() {
+            text.push_str(" (redacted)");
+        }
+        self.line("report", &text);
+    }
+
+    pub(crate) fn result(&self, res: &Result<Event, ReadError>) {
+        match res {
+            Ok(Event::Firmware {
+                version,
+                device,
+                serial: _,
+            }) if self.redacts_serial() => self.line(
+                "event",
+                &format!(
+                    "Firmware {{ version: {:?}, device: {:?}, serial: (redacted) }}",
+                    version, device
+                ),
+            ),
+            Ok(event) => self.line("event", &format!("{:?}", event)),
+            Err(err) => self.line("error", &err.to_string()),
+        }
+    }
+}
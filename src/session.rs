@@ -0,0 +1,47 @@
+use crate::Event;
+
+/// Identity of the device an `HpMouseEvents` stream comes from, cached from
+/// the `Event::Firmware` it yields after connecting. With several devices,
+/// this tells which one an event belongs to without keeping a separate map
+/// from streams to serials.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeviceSession {
+    device: Option<String>,
+    serial: Option<String>,
+}
+
+impl DeviceSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serial of the device `event` came from, if known by now. Pass every
+    /// event of the stream, so the identity follows `Event::Firmware`.
+    pub fn tag(&mut self, event: &Event) -> Option<&str> {
+        match event {
+            Event::Firmware { device, serial, .. } => {
+                self.device = Some(device.clone());
+                self.serial = Some(serial.clone());
+            }
+            // Another device may be opened in its place
+            Event::Connected => *self = Self::default(),
+            _ => {}
+        }
+        self.serial()
+    }
+
+    /// Device name from the firmware event
+    pub fn device(&self) -> Option<&str> {
+        self.device.as_deref()
+    }
+
+    pub fn serial(&self) -> Option<&str> {
+        self.serial.as_deref()
+    }
+
+    /// Whether events of this session come from the device with `serial`.
+    /// False until the firmware event.
+    pub fn is_device(&self, serial: &str) -> bool {
+        self.serial() == Some(serial)
+    }
+}
@@ -0,0 +1,243 @@
+//! Events as a `futures_core::Stream`, for async code. The device's file
+//! descriptor is registered with the `async-io` reactor, which runs its own
+//! thread, so this works with any executor. Backends without a descriptor
+//! wait for readiness on a helper thread instead.
+
+use async_io::Async;
+use futures_core::Stream;
+use std::{
+    os::unix::io::{AsFd, BorrowedFd, RawFd},
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+use crate::{Event, HidDevice, HpMouseEvents, ReadError};
+
+// How long the helper thread polls at a time, so it notices the stream was
+// dropped
+const POLL_INTERVAL: i32 = 200;
+
+// Descriptor of the device `HpMouseEventStream::events` reads, which keeps it
+// open for as long as the stream holds this
+struct DeviceFd(RawFd);
+
+impl AsFd for DeviceFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.0) }
+    }
+}
+
+// How the stream learns the device is readable
+enum Readiness {
+    Reactor(Async<DeviceFd>),
+    Thread(Arc<Shared>),
+}
+
+#[derive(Default)]
+struct Waiter {
+    // Task to wake once the device is readable. `None` while nothing waits.
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    waiter: Mutex<Waiter>,
+    cond: Condvar,
+}
+
+fn wait_thread(dev: Arc<dyn HidDevice>, shared: Arc<Shared>) {
+    loop {
+        {
+            let mut waiter = shared.waiter.lock().unwrap();
+            while waiter.waker.is_none() && !waiter.closed {
+                waiter = shared.cond.wait(waiter).unwrap();
+            }
+            if waiter.closed {
+                return;
+            }
+        }
+        // Errors are returned by the next read, so wake for those too
+        if !matches!(dev.poll(POLL_INTERVAL), Ok(false)) {
+            if let Some(waker) = shared.waiter.lock().unwrap().waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Stream of the same events `HpMouseEvents` yields as an iterator. Ends
+/// when the device is removed.
+pub struct HpMouseEventStream {
+    // Before `events`, so the descriptor is deregistered before the device
+    // can be closed
+    readiness: Readiness,
+    events: HpMouseEvents,
+}
+
+impl HpMouseEventStream {
+    pub fn new(events: HpMouseEvents) -> Self {
+        // Registered without changing the descriptor to non-blocking, which
+        // would affect the `HpMouse` writing to it; the stream only reads
+        // once the descriptor polls readable
+        let reactor = events
+            .raw_fd()
+            .and_then(|fd| Async::new_nonblocking(DeviceFd(fd)).ok());
+        let readiness = match reactor {
+            Some(reactor) => Readiness::Reactor(reactor),
+            None => {
+                let shared = Arc::new(Shared::default());
+                let dev = events.device().clone();
+                thread::spawn({
+                    let shared = shared.clone();
+                    move || wait_thread(dev, shared)
+                });
+                Readiness::Thread(shared)
+            }
+        };
+        Self { readiness, events }
+    }
+
+    /// Events this reads from, such as for `HpMouseEvents::is_unsolicited`
+    pub fn events(&self) -> &HpMouseEvents {
+        &self.events
+    }
+
+    pub fn events_mut(&mut self) -> &mut HpMouseEvents {
+        &mut self.events
+    }
+}
+
+impl Stream for HpMouseEventStream {
+    type Item = Result<Event, ReadError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            let res = this.events.next_ready();
+            if res.is_ready() {
+                return res;
+            }
+            match &this.readiness {
+                Readiness::Reactor(reactor) => match reactor.poll_readable(cx) {
+                    // Readable since the check, so check again
+                    Poll::Ready(Ok(())) => continue,
+                    Poll::Ready(Err(err)) => {
+                        return Poll::Ready(Some(Err(ReadError::from_io(err))));
+                    }
+                    Poll::Pending => return Poll::Pending,
+                },
+                Readiness::Thread(shared) => {
+                    // Checked again after registering, in case the report
+                    // arrived in between and the helper thread wasn't
+                    // waiting yet
+                    shared.waiter.lock().unwrap().waker = Some(cx.waker().clone());
+                    shared.cond.notify_one();
+                    return this.events.next_ready();
+                }
+            }
+        }
+    }
+}
+
+impl Drop for HpMouseEventStream {
+    fn drop(&mut self) {
+        if let Readiness::Thread(shared) = &self.readiness {
+            shared.waiter.lock().unwrap().closed = true;
+            shared.cond.notify_one();
+        }
+    }
+}
+
+impl HpMouseEvents {
+    /// Read events from async code
+    pub fn into_stream(self) -> HpMouseEventStream {
+        HpMouseEventStream::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HpMouse, MockHid};
+    use std::{
+        os::unix::io::FromRawFd,
+        sync::atomic::{AtomicBool, Ordering},
+        task::Wake,
+        time::Duration,
+    };
+
+    #[derive(Default)]
+    struct Flag(AtomicBool);
+
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_stream() {
+        let dev = MockHid::with_reports(vec![MockHid::report_1(6, &[10, 5, 0, 6, 80])])
+            .silent_when_empty();
+        let mut stream = HpMouse::from_device(dev.clone()).read().into_stream();
+        let flag = Arc::new(Flag::default());
+        let waker = Waker::from(flag.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut next = || Pin::new(&mut stream).poll_next(&mut cx);
+
+        assert!(matches!(next(), Poll::Ready(Some(Ok(Event::Connected)))));
+        assert!(matches!(
+            next(),
+            Poll::Ready(Some(Ok(Event::Battery { level: 80, .. })))
+        ));
+        assert!(next().is_pending());
+
+        // Woken once there is a report
+        dev.push_report(MockHid::report_1(6, &[10, 5, 0, 6, 70]));
+        for _ in 0..50 {
+            if flag.0.load(Ordering::SeqCst) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(flag.0.load(Ordering::SeqCst));
+        assert!(matches!(
+            next(),
+            Poll::Ready(Some(Ok(Event::Battery { level: 70, .. })))
+        ));
+    }
+
+    #[test]
+    fn test_stream_fd() {
+        let (rx, tx) = nix::unistd::pipe().unwrap();
+        let dev = unsafe { crate::Hid::from_raw_fd(rx) };
+        let mut stream = HpMouse::from_device(dev).read().into_stream();
+        assert!(matches!(stream.readiness, Readiness::Reactor(_)));
+        let flag = Arc::new(Flag::default());
+        let waker = Waker::from(flag.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut next = || Pin::new(&mut stream).poll_next(&mut cx);
+
+        assert!(matches!(next(), Poll::Ready(Some(Ok(Event::Connected)))));
+        assert!(next().is_pending());
+
+        // Woken by the reactor once the descriptor is readable
+        nix::unistd::write(tx, &MockHid::report_1(6, &[10, 5, 0, 6, 70])).unwrap();
+        for _ in 0..50 {
+            if flag.0.load(Ordering::SeqCst) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(flag.0.load(Ordering::SeqCst));
+        assert!(matches!(
+            next(),
+            Poll::Ready(Some(Ok(Event::Battery { level: 70, .. })))
+        ));
+        drop(stream);
+        nix::unistd::close(tx).unwrap();
+    }
+}